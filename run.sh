@@ -23,7 +23,7 @@ fi
 
 # Cria o virtual environment se não existir
 if [ ! -d "$SCRIPT_DIR/venv" ]; then
-    echo "Criando virtual environment..."
+    echo "Criando virtual environment..." >&2
     python3 -m venv venv
     if [ $? -ne 0 ]; then
         echo "Falha ao criar venv."
@@ -34,15 +34,15 @@ fi
 # Ativa o venv
 source venv/bin/activate
 
-# Atualiza pip
-pip install --upgrade pip
+# Atualiza pip (saída no stderr: o stdout é do Anvil, ex: eval "$(./run.sh env --export)")
+pip install --upgrade pip >&2
 
 # Instala dependências
 if [ -f requirements.txt ]; then
-    pip install -r requirements.txt
+    pip install -r requirements.txt >&2
 else
     # Se não houver requirements, instala toml manualmente
-    pip install toml
+    pip install toml >&2
 fi
 
 # Roda o Anvil
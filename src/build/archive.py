@@ -0,0 +1,72 @@
+"""Anvil Build - Arquivo para download do dist ('dist --archive').
+
+Empacota dist/qemu (como qemu/), o manifest.json (e a assinatura) e o SHA256SUMS num único
+dist/redstone.tar.gz ou dist/redstone.zip, mantendo a estrutura de diretórios
+e as permissões; extraído, 'sha256sum -c SHA256SUMS' funciona como no dist.
+Entradas em ordem e, com SOURCE_DATE_EPOCH, datas e donos fixos: o mesmo dist
+gera o mesmo arquivo.
+"""
+
+import gzip
+import stat
+import tarfile
+import zipfile
+from datetime import datetime, timezone
+from pathlib import Path
+from typing import Optional
+
+from core.errors import BuildError
+from core.reproducible import source_date_epoch
+
+ARCHIVE_FORMATS = ["tar.gz", "zip"]
+ARCHIVE_NAME = "redstone"
+# Arquivos de dist/ que acompanham a árvore de boot
+ARCHIVE_EXTRAS = ["manifest.json", "manifest.json.sig", "SHA256SUMS"]
+
+def archive_entries(dist: Path) -> list[tuple[Path, str]]:
+    """(caminho, nome no arquivo) de cada diretório e arquivo, em ordem."""
+    qemu = dist / "qemu"
+    entries = [(qemu, "qemu")]
+    entries += [(p, f"qemu/{p.relative_to(qemu).as_posix()}") for p in sorted(qemu.rglob("*"))]
+    return entries + [(dist / name, name) for name in ARCHIVE_EXTRAS if (dist / name).is_file()]
+
+def _write_tar(output: Path, entries: list[tuple[Path, str]], epoch: Optional[int]) -> None:
+    def normalize(info: tarfile.TarInfo) -> tarfile.TarInfo:
+        if epoch is None: return info
+        info.mtime, info.uid, info.gid, info.uname, info.gname = epoch, 0, 0, "", ""
+        return info
+
+    # GzipFile direto para fixar também a data do cabeçalho gzip
+    with open(output, "wb") as raw, gzip.GzipFile(fileobj=raw, mode="wb", mtime=epoch) as gz, \
+            tarfile.open(fileobj=gz, mode="w", format=tarfile.PAX_FORMAT) as tar:
+        for path, name in entries: tar.add(path, arcname=name, recursive=False, filter=normalize)
+
+def _write_zip(output: Path, entries: list[tuple[Path, str]], epoch: Optional[int]) -> None:
+    with zipfile.ZipFile(output, "w", zipfile.ZIP_DEFLATED) as zf:
+        for path, name in entries:
+            st = path.stat()
+            moment = datetime.fromtimestamp(epoch if epoch is not None else st.st_mtime, timezone.utc)
+            # O formato zip não representa datas antes de 1980
+            info = zipfile.ZipInfo(name + "/" if path.is_dir() else name, max(moment.timetuple()[:6], (1980, 1, 1, 0, 0, 0)))
+            # Permissões Unix nos 16 bits altos (o unzip as restaura)
+            info.external_attr = (stat.S_IFMT(st.st_mode) | stat.S_IMODE(st.st_mode)) << 16
+            if path.is_dir():
+                info.external_attr |= 0x10  # MS-DOS: diretório
+                zf.writestr(info, b"")
+            else:
+                info.compress_type = zipfile.ZIP_DEFLATED
+                with open(path, "rb") as f: zf.writestr(info, f.read())
+
+def create_archive(dist: Path, fmt: str) -> Path:
+    """Gera dist/redstone.<fmt> a partir de um dist já montado."""
+    if fmt not in ARCHIVE_FORMATS: raise BuildError(f"Formato de arquivo desconhecido: {fmt}", "dist", ARCHIVE_FORMATS)
+    if not (dist / "qemu").is_dir(): raise BuildError(f"{dist / 'qemu'} não existe", "dist")
+    output = dist / f"{ARCHIVE_NAME}.{fmt}"
+    entries = archive_entries(dist)
+    try:
+        if fmt == "zip": _write_zip(output, entries, source_date_epoch())
+        else: _write_tar(output, entries, source_date_epoch())
+    except OSError as e:
+        output.unlink(missing_ok=True)
+        raise BuildError(f"Falha ao criar {output.name}", "dist", [str(e)])
+    return output
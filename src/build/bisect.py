@@ -0,0 +1,103 @@
+"""Anvil Build - Bisect de regressões de boot.
+
+Conduz 'git bisect' no workspace do SO: a cada passo recompila o sistema e roda
+o comando de teste do usuário, marcando o commit pelo código de saída
+(mesma convenção do 'git bisect run': 0 bom, 125 pular, 1-127 ruim).
+"""
+
+import asyncio
+import shutil
+import sys
+from pathlib import Path
+from typing import Optional
+
+from core.config import Config
+from core.errors import AnvilError
+from core.paths import Paths
+from core.logger import Logger, get_logger
+
+BISECT_SKIP = 125
+
+class Bisector:
+    """Executa a bisecção e restaura o checkout original ao final."""
+
+    def __init__(self, paths: Paths, config: Config, log: Optional[Logger] = None):
+        self.paths = paths
+        self.config = config
+        self.log = log or get_logger()
+        # Cópia do próprio Anvil: o checkout de commits antigos pode alterar anvil/src
+        self.snapshot = self.paths.anvil_state / "bisect" / "src"
+
+    async def _git(self, *args: str, check: bool = True) -> str:
+        p = await asyncio.create_subprocess_exec(
+            "git", *args, cwd=self.paths.root,
+            stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.STDOUT
+        )
+        out, _ = await p.communicate()
+        text = out.decode(errors="replace").strip()
+        if check and p.returncode != 0:
+            raise AnvilError(f"git {' '.join(args)} falhou", text)
+        return text
+
+    async def _shell(self, command: str) -> int:
+        p = await asyncio.create_subprocess_shell(command, cwd=self.paths.anvil)
+        return await p.wait()
+
+    def _take_snapshot(self) -> None:
+        if self.snapshot.exists(): shutil.rmtree(self.snapshot)
+        shutil.copytree(self.paths.anvil / "src", self.snapshot, ignore=shutil.ignore_patterns("__pycache__"))
+
+    def _clean(self) -> None:
+        for p in [self.paths.forge / "target", self.paths.ignite / "target", self.paths.dist]:
+            if p.exists(): shutil.rmtree(p, ignore_errors=True)
+
+    async def _build(self) -> bool:
+        # Cada passo compila com o mesmo anvil.toml do bisect (inclusive um --config)
+        config = ["--config", str(self.config.config_path)] if self.config.config_path else []
+        p = await asyncio.create_subprocess_exec(
+            sys.executable, str(self.snapshot / "main.py"), *config, "build", cwd=self.paths.anvil
+        )
+        return await p.wait() == 0
+
+    async def run(self, good: str, bad: str, test: str, build: bool = True, clean: bool = False) -> Optional[str]:
+        """Retorna o primeiro commit ruim (hash + assunto), ou None se a bisecção não concluir."""
+        if await self._git("status", "--porcelain", "--untracked-files=no"):
+            raise AnvilError("O workspace tem alterações não commitadas", "Faça commit ou stash antes do bisect")
+        original = await self._git("rev-parse", "--abbrev-ref", "HEAD")
+        if original == "HEAD": original = await self._git("rev-parse", "HEAD")
+
+        self._take_snapshot()
+        self.log.header(f"Bisect: {good} (bom) .. {bad} (ruim)")
+        try:
+            out = await self._git("bisect", "start", bad, good)
+            step = 0
+            while "is the first bad commit" not in out:
+                step += 1
+                commit = await self._git("log", "-1", "--format=%h %s")
+                self.log.info(f"Passo {step}: {commit}")
+
+                if clean: self._clean()
+                if build and not await self._build():
+                    self.log.warning("Build falhou, commit pulado")
+                    code = BISECT_SKIP
+                else:
+                    code = await self._shell(test)
+
+                if code > 127 or code < 0:
+                    raise AnvilError(f"Comando de teste abortou (código {code})", test)
+                verdict = "good" if code == 0 else "skip" if code == BISECT_SKIP else "bad"
+                self.log.step(f"{commit.split()[0]}: {verdict} (código {code})")
+                out = await self._git("bisect", verdict, check=False)
+                if "only skipped commits left" in out:
+                    self.log.warning("Restaram apenas commits pulados; não foi possível isolar o culpado")
+                    self.log.raw(out)
+                    return None
+
+            first_bad = out.split()[0]
+            result = await self._git("log", "-1", "--format=%h %s", first_bad)
+            self.log.success(f"Primeiro commit ruim: {result}")
+            return result
+        finally:
+            # Também em Ctrl+C: sempre volta ao checkout original
+            await self._git("bisect", "reset", original, check=False)
+            self.log.step(f"Checkout restaurado: {original}")
@@ -4,39 +4,189 @@ Este módulo organiza o diretório 'dist/qemu' que simula o disco rígido do QEM
 Copia Kernel, Bootloader e cria arquivos de configuração UEFI.
 """
 
+import asyncio
 import shutil
+import toml
+import struct
 from pathlib import Path
 from typing import Optional
 
 from core.config import Config
-from core.paths import Paths
-from core.errors import BuildError
+from core.paths import Paths, check_free_space, dir_size, format_size
+from core.errors import BuildError, ConfigError, ValidationError
 from core.logger import Logger, get_logger
+from core.events import get_events
+from core.toolchain import llvm_tool
+from core.checksum import sha256_file, write_sums
+from build.manifest import DistManifest, write_manifest
+
+STRIP_LEVELS = ["none", "debug", "all"]
+
+# Procurados em ordem: llvm-tools do sysroot (ou PATH), cargo-binutils e por fim binutils do sistema
+STRIP_TOOLS = ["llvm-strip", "rust-strip", "strip"]
+OBJCOPY_TOOLS = ["llvm-objcopy", "rust-objcopy", "objcopy"]
+
+def find_strip_tools() -> tuple[Optional[Path], Optional[Path]]:
+    """(strip, objcopy) disponíveis no host; None para o que não for encontrado."""
+    def first(names):
+        return next((p for p in map(llvm_tool, names) if p), None)
+    return first(STRIP_TOOLS), first(OBJCOPY_TOOLS)
+
+def needs_copy(src: Path, dest: Path) -> bool:
+    """
+    Se dest precisa ser (re)copiado de src. A cópia usa copy2, que preserva o mtime,
+    então mesmo tamanho e mesmo mtime significam que o binário de origem não mudou.
+    """
+    if not dest.is_file(): return True
+    s, d = src.stat(), dest.stat()
+    return s.st_size != d.st_size or s.st_mtime_ns != d.st_mtime_ns
+
+class ArtifactHashCache:
+    """
+    SHA-256 de cada binário copiado para o dist (cache/artifact-hashes.toml), por destino.
+    Um binário recompilado com o mesmo conteúdo muda de mtime mas não de hash, e não é
+    recopiado. O tamanho e o mtime da origem ficam junto para não refazer o hash de quem não mudou.
+    """
+
+    def __init__(self, path: Path):
+        self.path = path
+        try: self.entries: dict[str, dict] = toml.load(path)
+        except (OSError, ValueError): self.entries = {}
+
+    def _source_hash(self, rel: str, source: Path) -> str:
+        entry, st = self.entries.get(rel, {}), source.stat()
+        if entry.get("source") == str(source) and entry.get("size") == st.st_size and entry.get("mtime_ns") == st.st_mtime_ns:
+            return entry["sha256"]
+        return sha256_file(source)
+
+    def unchanged(self, rel: str, source: Path, dest: Path) -> bool:
+        """Se dest ainda é a cópia registrada e a origem tem o mesmo hash dela."""
+        entry = self.entries.get(rel)
+        if not entry or entry.get("source") != str(source) or not dest.is_file(): return False
+        # Alterado depois da cópia (ex: strip): o registro não vale mais
+        if dest.stat().st_size != entry.get("dest_size"): return False
+        sha = self._source_hash(rel, source)
+        if sha != entry["sha256"]: return False
+        self.store(rel, source, dest, sha)
+        return True
+
+    def store(self, rel: str, source: Path, dest: Path, sha: Optional[str] = None) -> None:
+        st = source.stat()
+        self.entries[rel] = {"source": str(source), "sha256": sha or self._source_hash(rel, source),
+                             "size": st.st_size, "mtime_ns": st.st_mtime_ns, "dest_size": dest.stat().st_size}
+
+    def save(self) -> None:
+        """Grava o cache, descartando entradas cuja origem não existe mais."""
+        self.entries = {k: v for k, v in self.entries.items() if Path(v.get("source", "")).is_file()}
+        self.path.parent.mkdir(parents=True, exist_ok=True)
+        with open(self.path, "w", encoding="utf-8") as f: toml.dump(dict(sorted(self.entries.items())), f)
+
+def copy_artifact(source: Path, dest: Path, rel: str, hashes: Optional[ArtifactHashCache] = None) -> bool:
+    """
+    Copia source para dest (rel é o caminho em dist/qemu). Com hashes (modo incremental) pula
+    a cópia se o tamanho e o mtime batem (needs_copy) ou se o hash é o do cache; True se copiou.
+    """
+    if hashes is not None:
+        if not needs_copy(source, dest):
+            # Cópia de um dist anterior ao cache: registra para as próximas comparações
+            if rel not in hashes.entries: hashes.store(rel, source, dest)
+            return False
+        if hashes.unchanged(rel, source, dest): return False
+    dest.parent.mkdir(parents=True, exist_ok=True)
+    shutil.copy2(source, dest)
+    if hashes is not None: hashes.store(rel, source, dest)
+    return True
+
+def _is_elf(path: Path) -> bool:
+    with open(path, "rb") as f: return f.read(4) == b"\x7fELF"
+
+# Artefatos de boot que um layout pode incluir (templates de [dist])
+BOOT_ARTIFACTS = ["bootloader", "kernel", "bios_kernel"]
+
+# Chaves reconhecidas pelo ignite.cfg: globais e da entrada de boot
+IGNITE_GLOBAL_KEYS = ["timeout", "default_entry", "serial", "quiet", "splash"]
+IGNITE_ENTRY_KEYS = ["protocol", "kernel_path", "cmdline", "module_path"]
+
+def missing_artifacts_error(missing: list[tuple[str, str]]) -> BuildError:
+    """Erro do 'dist --strict' listando cada artefato ausente e os comandos que o compilam."""
+    commands = list(dict.fromkeys(command for _, command in missing))
+    return BuildError(f"{len(missing)} artefato(s) ausente(s) no dist", "dist",
+                      [name for name, _ in missing] + [f"Compile com: {c}" for c in commands])
+
+# Limites do splash desenhado pelo Ignite via GOP (BMP sem compressão)
+SPLASH_MAX_SIZE = (1920, 1080)
+SPLASH_BPP = (24, 32)
 
 class DistBuilder:
     def __init__(self, paths: Paths, config: Config, log: Optional[Logger] = None):
         self.paths = paths
         self.config = config
         self.log = log or get_logger()
+        # (nome lógico, origem, destino em dist/qemu) de cada cópia, para o manifesto
+        self.copied: list[tuple[str, Path, str]] = []
+        # 'dist --incremental': só copia o que mudou desde o último dist (ver copy_artifact)
+        self.incremental = False
+        self.hashes: Optional[ArtifactHashCache] = None
+
+    def _record(self, name: str, source: Path, rel: str) -> None:
+        self.copied.append((name, source, rel))
+        get_events().emit("copy", source=source, dest=rel)
+
+    def _copy(self, name: str, source: Path, rel: str) -> bool:
+        """Copia source para dist/qemu/rel; False se o modo incremental manteve a cópia existente."""
+        if not copy_artifact(source, self.paths.dist_qemu / rel, rel, self.hashes):
+            self.copied.append((name, source, rel))
+            return False
+        self._record(name, source, rel)
+        return True
         
-    def prepare(self, profile: str = "release") -> bool:
+    def prepare(self, profile: str = "release", splash: Optional[Path] = None, layout: str = "uefi") -> bool:
         """
         Executa a preparação completa da pasta de distribuição.
-        Copia binários e configura o bootloader.
+        O layout escolhe quais artefatos de boot entram ([dist.layouts] no anvil.toml).
+        Copia binários e configura o bootloader (opcionalmente com uma imagem de splash).
         """
         self.log.header("Preparando Distribuição")
+        layouts = self.config.dist.layouts
+        if layout not in layouts:
+            raise ConfigError(f"Layout desconhecido: {layout}", f"Layouts em [dist.layouts]: {', '.join(layouts)}")
+        artifacts = layouts[layout]
+        unknown = set(artifacts) - set(BOOT_ARTIFACTS)
+        if unknown:
+            raise ConfigError(f"Artefato(s) desconhecido(s) em [dist.layouts].{layout}: {', '.join(sorted(unknown))}",
+                              f"Aceitos: {', '.join(BOOT_ARTIFACTS)}")
+        self.log.step(f"Layout: {layout} ({', '.join(artifacts)})")
+        if self.incremental: self.hashes = ArtifactHashCache(self.paths.artifact_hashes)
+        self._check_space(profile)
         self._create_structure()
         
-        if not self._copy_bootloader(profile):
-            raise BuildError("Bootloader é necessário", "dist")
-            
-        if not self._copy_kernel(profile):
-            raise BuildError("Kernel é necessário", "dist")
+        # Sem bootloader ou kernel o dist não boota: erro mesmo sem --strict, listando os dois
+        missing = []
+        if "bootloader" in artifacts and not self._copy_bootloader(profile):
+            missing.append(("bootloader", "anvil build bootloader"))
+        for kind in ("kernel", "bios_kernel"):
+            if kind in artifacts and not self._copy_kernel(profile, kind):
+                missing.append((kind, "anvil build kernel"))
+        if missing: raise missing_artifacts_error(missing)
             
-        self._create_ignite_cfg()
+        self._copy_drivers(profile)
+        if splash: self._copy_splash(splash)
+        # O ignite.cfg só faz sentido com o Ignite (UEFI) no disco
+        if "bootloader" in artifacts: self._create_ignite_cfg(profile, splash=splash is not None)
+        if self.hashes: self.hashes.save()
         self.log.success(f"dist/qemu pronto: {self.paths.dist_qemu}")
         return True
         
+    def _check_space(self, profile: str) -> None:
+        """Estima o tamanho do dist pelos binários de origem e confere o espaço livre."""
+        comps = self.config.components
+        sources = [self.paths.bootloader_binary(profile), self.paths.kernel_binary(profile)]
+        sources += [self.paths.service_binary(c.name, profile, base_path=self.paths.root / c.path, target=c.target)
+                    for c in [*comps.services, *comps.apps, *comps.drivers]]
+        # Cópia no dist + staging do initfs
+        required = 2 * sum(dir_size(s) for s in sources)
+        check_free_space(self.paths.dist, required, self.config.preflight.min_free_mb, self.log)
+
     def _create_structure(self) -> None:
         """Cria a árvore de diretórios necessária (EFI, boot, system...)."""
         self.log.info("Criando estrutura de diretórios...")
@@ -46,41 +196,232 @@ class DistBuilder:
         self.log.step("Estrutura criada: EFI/BOOT, boot, system/services")
         
     def _copy_bootloader(self, profile: str) -> bool:
-        """Copia ignite.efi para EFI/BOOT/BOOTX64.EFI."""
+        """Copia ignite.efi para o destino configurado (padrão: EFI/BOOT/BOOTX64.EFI)."""
         source = self.paths.bootloader_binary(profile)
-        dest = self.paths.dist_qemu / "EFI" / "BOOT" / "BOOTX64.EFI"
+        rel = self.config.artifact_dest("bootloader", profile)
         if not source.exists():
             self.log.error(f"Bootloader não encontrado: {source}")
             return False
-        shutil.copy2(source, dest)
-        self.log.step(f"Bootloader copiado para EFI/BOOT/BOOTX64.EFI")
+        if self._copy("bootloader", source, rel): self.log.step(f"Bootloader copiado para {rel}")
+        else: self.log.step(f"Bootloader inalterado ({rel})")
         return True
         
-    def _copy_kernel(self, profile: str) -> bool:
-        """Copia forge (kernel) para boot/kernel."""
+    def _copy_kernel(self, profile: str, kind: str = "kernel") -> bool:
+        """Copia forge (kernel) para o destino configurado (padrão: boot/kernel; BIOS: boot/forge)."""
         source = self.paths.kernel_binary(profile)
-        dest = self.paths.dist_qemu / "boot" / "kernel"
+        rel = self.config.artifact_dest(kind, profile)
         if not source.exists():
             self.log.error(f"Kernel não encontrado: {source}")
             return False
-        shutil.copy2(source, dest)
-        self.log.step(f"Kernel copiado para boot/kernel")
+        if self._copy(kind, source, rel): self.log.step(f"Kernel copiado para {rel}")
+        else: self.log.step(f"Kernel inalterado ({rel})")
         return True
         
-    def _create_ignite_cfg(self) -> None:
+    def _copy_drivers(self, profile: str) -> None:
+        """Copia os drivers já compilados (padrão: system/bin/<nome>); os não compilados ficam de fora."""
+        for drv in self.config.components.drivers:
+            source = self.paths.service_binary(drv.name, profile, base_path=self.paths.root / drv.path,
+                                               target=drv.target)
+            if not source.exists():
+                self.log.debug(f"Driver {drv.name} não compilado ({source}), ignorado")
+                continue
+            rel = self.config.artifact_dest("driver", profile, drv.name)
+            if self._copy(drv.name, source, rel): self.log.step(f"Driver {drv.name} copiado para {rel}")
+            else: self.log.step(f"Driver {drv.name} inalterado ({rel})")
+
+    def _check_splash(self, source: Path) -> None:
+        """Avisa se a imagem não for um BMP que o Ignite consiga desenhar."""
+        header = source.read_bytes()[:30]
+        if len(header) < 30 or header[:2] != b"BM":
+            self.log.warning(f"Splash não é um BMP: {source.name}")
+            return
+        width, height = struct.unpack_from("<ii", header, 18)
+        bpp, = struct.unpack_from("<H", header, 28)
+        if bpp not in SPLASH_BPP:
+            self.log.warning(f"Splash com {bpp} bpp; o Ignite espera {' ou '.join(map(str, SPLASH_BPP))} bpp")
+        if abs(width) > SPLASH_MAX_SIZE[0] or abs(height) > SPLASH_MAX_SIZE[1]:
+            self.log.warning(f"Splash {abs(width)}x{abs(height)} excede {SPLASH_MAX_SIZE[0]}x{SPLASH_MAX_SIZE[1]}")
+
+    def _copy_splash(self, source: Path) -> None:
+        """Copia a imagem de splash para boot/splash.bmp."""
+        if not source.is_file():
+            raise BuildError(f"Splash não encontrado: {source}", "dist")
+        self._check_splash(source)
+        shutil.copy2(source, self.paths.dist_qemu / "boot" / "splash.bmp")
+        self._record("splash", source, "boot/splash.bmp")
+        self.log.step("Splash copiado para boot/splash.bmp")
+
+    def _create_ignite_cfg(self, profile: str = "release", splash: bool = False) -> None:
         """Gera o arquivo de configuração ignite.cfg para o bootloader."""
-        cfg_content = """timeout: 10
+        kernel = self.config.artifact_dest("kernel", profile)
+        splash_line = "splash: boot():/boot/splash.bmp\n" if splash else ""
+        cfg_content = f"""timeout: 10
 default_entry: 1
 serial: true
 quiet: false
-
+{splash_line}
 # Default Entry
 /Redstone OS
     protocol: redstone
-    kernel_path: boot():/boot/kernel
+    kernel_path: boot():/{kernel}
     cmdline: verbose
     module_path: boot():/boot/initfs
 """
         dest = self.paths.dist_qemu / "EFI" / "BOOT" / "ignite.cfg"
         dest.write_text(cfg_content, encoding="utf-8")
         self.log.step("ignite.cfg criado")
+
+    def update_ignite_cfg(self, overrides: dict[str, str]) -> str:
+        """
+        Altera chaves do ignite.cfg já presente em dist/qemu e retorna o novo conteúdo.
+        Chaves globais ficam no topo; chaves de entrada (ex: cmdline) vão para a entrada padrão.
+        """
+        cfg = self.paths.dist_qemu / "EFI" / "BOOT" / "ignite.cfg"
+        if not cfg.exists():
+            raise ValidationError(f"ignite.cfg não encontrado: {cfg} (rode 'anvil dist' primeiro)", str(cfg))
+        unknown = [k for k in overrides if k not in IGNITE_GLOBAL_KEYS + IGNITE_ENTRY_KEYS]
+        if unknown:
+            raise ConfigError(f"Chave(s) desconhecida(s) no ignite.cfg: {', '.join(unknown)}",
+                              f"Aceitas: {', '.join(IGNITE_GLOBAL_KEYS + IGNITE_ENTRY_KEYS)}")
+
+        lines = cfg.read_text(encoding="utf-8").splitlines()
+        entry_starts = [i for i, l in enumerate(lines) if l.startswith("/")]
+        first_entry = entry_starts[0] if entry_starts else len(lines)
+
+        # Entrada padrão (default_entry é 1-based); o bloco vai até a próxima entrada
+        default = next((l.split(":", 1)[1].strip() for l in lines[:first_entry] if l.startswith("default_entry:")), "1")
+        index = min(max(int(default) - 1, 0), len(entry_starts) - 1) if default.isdigit() and entry_starts else 0
+        entry_begin = entry_starts[index] if entry_starts else None
+        entry_end = next((i for i in entry_starts if entry_begin is not None and i > entry_begin), len(lines))
+
+        for key, value in overrides.items():
+            if key in IGNITE_GLOBAL_KEYS:
+                found = next((i for i in range(first_entry) if lines[i].startswith(f"{key}:")), None)
+                if found is not None: lines[found] = f"{key}: {value}"
+                else:
+                    lines.insert(0, f"{key}: {value}")
+                    first_entry += 1
+                    entry_starts = [i + 1 for i in entry_starts]
+                    if entry_begin is not None: entry_begin += 1
+                    entry_end += 1
+            else:
+                if entry_begin is None:
+                    raise ConfigError(f"ignite.cfg não tem entrada de boot para receber '{key}'")
+                found = next((i for i in range(entry_begin + 1, entry_end) if lines[i].strip().startswith(f"{key}:")), None)
+                if found is not None: lines[found] = f"    {key}: {value}"
+                else:
+                    # Insere após a última linha indentada da entrada
+                    pos = entry_begin + 1
+                    while pos < entry_end and lines[pos].startswith((" ", "\t")): pos += 1
+                    lines.insert(pos, f"    {key}: {value}")
+                    entry_end += 1
+
+        content = "\n".join(lines) + "\n"
+        cfg.write_text(content, encoding="utf-8")
+        self.log.step(f"ignite.cfg atualizado: {', '.join(f'{k}={v}' for k, v in overrides.items())}")
+        return content
+
+    async def pack_squashfs(self) -> Path:
+        """
+        Empacota a árvore system/ em system.sfs (somente leitura) via mksquashfs,
+        substituindo os arquivos soltos.
+        """
+        system_dir = self.paths.dist_qemu / "system"
+        image = self.paths.dist_qemu / "system.sfs"
+        if not shutil.which("mksquashfs"):
+            raise BuildError("mksquashfs não encontrado (instale com: sudo apt install squashfs-tools)", "dist")
+        if not system_dir.exists():
+            raise BuildError(f"Diretório não encontrado: {system_dir}", "dist")
+
+        self.log.info("Empacotando system/ em system.sfs...")
+        p = await asyncio.create_subprocess_exec(
+            "mksquashfs", str(system_dir), str(image), "-noappend", "-quiet",
+            stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.STDOUT
+        )
+        out, _ = await p.communicate()
+        if p.returncode != 0:
+            raise BuildError("Falha ao criar system.sfs", "dist", out.decode(errors="replace").splitlines())
+
+        shutil.rmtree(system_dir)
+        self.copied.append(("system", system_dir, "system.sfs"))
+        self.log.step(f"system.sfs criado ({image.stat().st_size // 1024} KiB)")
+        return image
+
+    def write_manifest(self, profile: str, copied: list[tuple[str, Path, str]], build_version: str = "") -> Path:
+        """dist/manifest.json com as cópias deste builder e as de 'copied' (initramfs, serviços, apps)."""
+        manifest = DistManifest.collect(self.paths.dist_qemu, self.paths.root, profile, [*self.copied, *copied],
+                                        build_version)
+        output = write_manifest(manifest, self.paths.dist_manifest)
+        self.log.step(f"Manifesto: {output.name} ({len(manifest.entries)} artefato(s))")
+        return output
+
+    def write_checksums(self, profile: str = "release") -> Path:
+        """dist/SHA256SUMS com bootloader, kernel e initfs presentes em dist/qemu."""
+        rels = [self.config.artifact_dest(kind, profile) for kind in BOOT_ARTIFACTS] + ["boot/initfs"]
+        files = [f for f in dict.fromkeys(self.paths.dist_qemu / r for r in rels) if f.is_file()]
+        output = self.paths.dist / "SHA256SUMS"
+        write_sums(output, files, self.paths.dist)
+        self.log.step(f"SHA256SUMS: {len(files)} arquivo(s) (confira com 'cd dist && sha256sum -c SHA256SUMS')")
+        return output
+
+    def _strippable_artifacts(self, profile: str) -> list[Path]:
+        """Binários do dist: bootloader, kernel, serviços, apps e drivers."""
+        comps = self.config.components
+        rels = [self.config.artifact_dest("bootloader", profile), self.config.artifact_dest("kernel", profile),
+                self.config.artifact_dest("bios_kernel", profile)]
+        rels += [self.config.artifact_dest("service", profile, s.name) for s in comps.services]
+        rels += [self.config.artifact_dest("app", profile, a.name) for a in comps.apps]
+        rels += [self.config.artifact_dest("driver", profile, d.name) for d in comps.drivers]
+        return [a for a in dict.fromkeys(self.paths.dist_qemu / r for r in rels) if a.exists()]
+
+    async def _run_tool(self, *cmd) -> None:
+        p = await asyncio.create_subprocess_exec(
+            *map(str, cmd), stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.STDOUT
+        )
+        out, _ = await p.communicate()
+        if p.returncode != 0:
+            raise BuildError(f"Falha ao executar {Path(str(cmd[0])).name}", "dist", out.decode(errors="replace").splitlines())
+
+    async def strip_binary(self, artifact: Path, level: str, strip_tool: Path, objcopy: Optional[Path]) -> None:
+        """
+        Remove símbolos de um binário do dist.
+        - debug: remove apenas debug info, guardada em dist/debug/<caminho>.debug para simbolizar crashes
+        - all: remove todos os símbolos
+        """
+        if level == "debug":
+            rel = artifact.relative_to(self.paths.dist_qemu)
+            sidecar = self.paths.dist_debug / f"{rel}.debug"
+            sidecar.parent.mkdir(parents=True, exist_ok=True)
+            await self._run_tool(objcopy, "--only-keep-debug", artifact, sidecar)
+            await self._run_tool(strip_tool, "--strip-debug", artifact)
+            await self._run_tool(objcopy, f"--add-gnu-debuglink={sidecar}", artifact)
+        else:
+            await self._run_tool(strip_tool, "--strip-all", artifact)
+
+    async def strip(self, level: str = "none", profile: str = "release") -> None:
+        """
+        Remove símbolos dos binários ELF do dist (o bootloader é PE e fica como está) e mostra
+        o tamanho de cada binário antes e depois. Sem ferramenta de strip, só avisa.
+        """
+        if level not in STRIP_LEVELS:
+            raise BuildError(f"Nível de strip inválido: {level} (use {', '.join(STRIP_LEVELS)})", "dist")
+        artifacts = self._strippable_artifacts(profile)
+        strip_tool, objcopy = find_strip_tools() if level != "none" else (None, None)
+        if level != "none" and (not strip_tool or (level == "debug" and not objcopy)):
+            self.log.warning("Nenhuma ferramenta de strip encontrada; binários mantidos com símbolos")
+            self.log.step("Instale com: rustup component add llvm-tools-preview")
+            level = "none"
+        elif level != "none":
+            self.log.info(f"Removendo símbolos (nível: {level}, {strip_tool.name})...")
+
+        self.log.info("Tamanho dos binários:")
+        for artifact in artifacts:
+            rel = artifact.relative_to(self.paths.dist_qemu).as_posix()
+            before = artifact.stat().st_size
+            if level == "none" or not _is_elf(artifact):
+                self.log.step(f"{rel}: {format_size(before)}")
+                continue
+            await self.strip_binary(artifact, level, strip_tool, objcopy)
+            after = artifact.stat().st_size
+            saved = 100 * (before - after) / before if before else 0
+            self.log.step(f"{rel}: {format_size(before)} -> {format_size(after)} (-{saved:.0f}%)")
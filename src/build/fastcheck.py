@@ -0,0 +1,71 @@
+"""Anvil Build - Verificação rápida de builds sem alterações ('build --fast-check').
+
+Guarda uma impressão digital (caminho, tamanho e mtime) dos fontes de cada
+componente e, quando nada mudou desde o último build bem-sucedido, o cargo
+nem chega a ser executado.
+
+Limitações (o cargo continua sendo a referência):
+- só olha o diretório do crate, lib/, sdk/ e o Cargo.lock da raiz; dependências
+  de git/registry ou path deps fora dessas pastas não são vistas;
+- mudanças de toolchain só são notadas pela versão do rustc;
+- não confere os artefatos em target/, apenas que o diretório existe.
+"""
+
+import hashlib
+import json
+import os
+from pathlib import Path
+from typing import Optional
+
+# Diretórios ignorados na varredura
+SKIP_DIRS = {"target", ".git", ".anvil", "__pycache__"}
+
+def _walk(root: Path):
+    stack = [root]
+    while stack:
+        current = stack.pop()
+        try:
+            entries = list(os.scandir(current))
+        except OSError:
+            continue
+        for entry in entries:
+            if entry.is_dir(follow_symlinks=False):
+                if entry.name not in SKIP_DIRS: stack.append(entry.path)
+            elif entry.is_file(follow_symlinks=False):
+                yield entry
+
+def fingerprint(dirs: list[Path], files: list[Path], params: dict) -> str:
+    """Hash dos metadados dos fontes e dos parâmetros do build (perfil, features, flags...)."""
+    items = []
+    for d in dirs:
+        if not d.is_dir(): continue
+        for entry in _walk(d):
+            st = entry.stat(follow_symlinks=False)
+            items.append(f"{entry.path}\0{st.st_size}\0{st.st_mtime_ns}")
+    for f in files:
+        if f.is_file():
+            st = f.stat()
+            items.append(f"{f}\0{st.st_size}\0{st.st_mtime_ns}")
+    items.sort()
+    h = hashlib.sha256(json.dumps(params, sort_keys=True, default=str).encode())
+    for item in items: h.update(item.encode(errors="surrogateescape") + b"\n")
+    return h.hexdigest()
+
+class FastCheckCache:
+    """Impressões digitais do último build bem-sucedido de cada componente."""
+
+    def __init__(self, path: Path):
+        self.path = path
+        try:
+            self.entries: dict[str, str] = json.loads(path.read_text(encoding="utf-8"))
+        except (OSError, ValueError):
+            self.entries = {}
+
+    def matches(self, key: str, value: str) -> bool:
+        return self.entries.get(key) == value
+
+    def store(self, key: str, value: Optional[str]) -> None:
+        if value is None: self.entries.pop(key, None)
+        else: self.entries[key] = value
+        self.path.parent.mkdir(parents=True, exist_ok=True)
+        self.path.write_text(json.dumps(self.entries, indent=2, sort_keys=True), encoding="utf-8")
@@ -1,26 +1,71 @@
 """Anvil Build - Criação de Imagem de Disco."""
 
 import asyncio
+import math
 import time
 from pathlib import Path
 from typing import Optional
 
 from core.config import Config
-from core.paths import Paths
+from core.paths import Paths, check_free_space
 from core.errors import BuildError
 from core.logger import Logger, get_logger
+from core.reproducible import source_date_epoch
+
+# Bloco usado na estimativa de ocupação do FAT (arquivos arredondados para o cluster)
+FAT_CLUSTER = 4096
+# Menor volume aceito pelo FAT32 (mínimo de clusters), em MiB
+MIN_FAT32_MB = 33
 
 class ImageBuilder:
-    """Ferramenta para criar imagens VDI (VirtualBox) a partir da pasta dist."""
+    """
+    Ferramenta para criar imagens VDI (VirtualBox) a partir da pasta dist.
+    A pasta dist/qemu é apenas lida; só arquivos em dist/img são criados ou removidos.
+    """
     
     def __init__(self, paths: Paths, config: Config, log: Optional[Logger] = None):
         self.paths = paths
         self.config = config
         self.log = log or get_logger()
         
-    async def build_vdi(self, profile: str = "release") -> Path:
-        """Cria uma imagem .vdi contendo todo o diretório dist/qemu."""
+    def required_size_mb(self) -> int:
+        """Estimativa do espaço que dist/qemu ocupa no FAT (arquivos, diretórios e tabelas), em MiB."""
+        used = 0
+        for p in self.paths.dist_qemu.rglob("*"):
+            size = p.stat().st_size if p.is_file() else 0
+            used += max(1, math.ceil(size / FAT_CLUSTER)) * FAT_CLUSTER
+        # Setores reservados + duas cópias da FAT (4 bytes por cluster)
+        overhead = 1024 * 1024 + 2 * 4 * math.ceil(used / FAT_CLUSTER)
+        return math.ceil((used + overhead) / (1024 * 1024))
+
+    def resolve_size_mb(self, size_mb: Optional[int] = None, slack_mb: int = 16) -> int:
+        """
+        Tamanho da partição: valida o informado (erro se o conteúdo não couber)
+        ou calcula o necessário mais uma folga.
+        """
+        required = self.required_size_mb()
+        if size_mb is not None:
+            if size_mb < max(required, MIN_FAT32_MB):
+                raise BuildError(
+                    f"dist/qemu não cabe em {size_mb} MiB: são necessários pelo menos {max(required, MIN_FAT32_MB)} MiB "
+                    f"(use --esp-size {max(required, MIN_FAT32_MB)} ou maior)", "image"
+                )
+            return size_mb
+        return max(required + slack_mb, MIN_FAT32_MB)
+
+    async def build_vdi(self, profile: str = "release", keep_intermediate: bool = False,
+                        size_mb: Optional[int] = None, slack_mb: int = 16) -> Path:
+        """
+        Cria uma imagem .vdi contendo todo o diretório dist/qemu.
+        Com keep_intermediate=True a imagem RAW intermediária é preservada para inspeção.
+        Sem size_mb, a partição é dimensionada para o conteúdo mais slack_mb de folga.
+        """
         self.log.header("Gerando Imagem VDI (VirtualBox)")
+        # Verifica antes de escrever qualquer coisa: evita truncamento silencioso no FAT
+        img_size_mb = self.resolve_size_mb(size_mb, slack_mb)
+        # RAW e VDI coexistem durante a conversão
+        check_free_space(self.paths.dist_img, 2 * img_size_mb * 1024 * 1024,
+                         self.config.preflight.min_free_mb, self.log)
         
         img_dir = self.paths.root / "dist" / "img"
         img_dir.mkdir(parents=True, exist_ok=True)
@@ -30,11 +75,11 @@ class ImageBuilder:
         vdi_path = img_dir / vdi_name
         raw_path = img_dir / f"redstone_{timestamp}.raw"
         
-        self.log.info(f"Saída: {vdi_path}")
+        self.log.info(f"Saída: {vdi_path} ({img_size_mb} MiB)")
         
         try:
             # 1. Cria imagem FAT32 bruta (RAW)
-            if not await self._create_raw(raw_path):
+            if not await self._create_raw(raw_path, img_size_mb):
                 raise BuildError("Falha ao criar imagem RAW no WSL", "image")
             
             # 2. Converte RAW para VDI
@@ -42,13 +87,14 @@ class ImageBuilder:
                 raise BuildError("Falha na conversão para VDI", "image")
             
             # Limpa temporário
-            if raw_path.exists(): raw_path.unlink()
+            if keep_intermediate: self.log.step(f"RAW preservada: {raw_path}")
+            elif raw_path.exists(): raw_path.unlink()
             
             self.log.success(f"VDI gerada: {vdi_name}")
             return vdi_path
             
         except Exception as e:
-            if raw_path.exists(): raw_path.unlink()
+            if raw_path.exists() and not keep_intermediate: raw_path.unlink()
             self.log.error(f"Erro na geração da imagem: {e}")
             raise
             
@@ -72,19 +118,22 @@ class ImageBuilder:
             self.log.error(f"Erro WSL: {e}")
             return False
             
-    async def _create_raw(self, output: Path) -> bool:
+    async def _create_raw(self, output: Path, img_size_mb: int) -> bool:
         """Cria e formata imagem raw usando ferramentas Linux."""
-        img_size_mb = 128
         wsl_output = Paths.to_wsl(output)
         wsl_dist = Paths.to_wsl(self.paths.dist_qemu)
         
         # dd: cria arquivo vazio
         # mkfs.vfat: formata como FAT32
         # mcopy: copia arquivos para dentro da imagem FAT
+        # Modo reproduzível: volume ID fixo e datas preservadas (já normalizadas no dist)
+        reproducible = source_date_epoch() is not None
+        vol_id = "-i 52534f53 " if reproducible else ""
+        keep_mtime = "-m " if reproducible else ""
         cmd = (
             f"dd if=/dev/zero of='{wsl_output}' bs=1M count={img_size_mb} && "
-            f"mkfs.vfat -F 32 '{wsl_output}' && "
-            f"mcopy -i '{wsl_output}' -s '{wsl_dist}'/* ::/"
+            f"mkfs.vfat -F 32 {vol_id}'{wsl_output}' && "
+            f"mcopy -i '{wsl_output}' {keep_mtime}-s '{wsl_dist}'/* ::/"
         )
         return await self._run_wsl_logged(cmd)
         
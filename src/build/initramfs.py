@@ -1,21 +1,27 @@
 """Anvil Build - Gerador de InitRAMFS.
 
 Responsável por:
-1. Criar o arquivo initfs (TAR) contendo o Supervisor.
+1. Criar o arquivo initfs (TAR) contendo o Supervisor e os serviços 'core'
+   (os de 'programs' da receita ativa, que já chegam marcados na config).
 2. Copiar serviços e apps para as pastas apropriadas no 'disco' (dist/qemu).
 3. Gerar manifestos de serviços.
 """
 
 import asyncio
+import shlex
 import shutil
 from dataclasses import dataclass
 from pathlib import Path
 from typing import Optional
 
-from core.config import Config
+from core.config import Config, ServiceConfig
 from core.paths import Paths
 from core.errors import BuildError
 from core.logger import Logger, get_logger
+from core.events import get_events
+from core.progress import progress
+from core.reproducible import source_date_epoch
+from build.dist import ArtifactHashCache, copy_artifact, missing_artifacts_error
 
 @dataclass
 class InitramfsEntry:
@@ -31,31 +37,58 @@ class InitramfsBuilder:
         self.config = config
         self.log = log or get_logger()
         self.entries = []
+        # Cópias para dist/qemu (nome, origem, destino), como em DistBuilder.copied
+        self.copied: list[tuple[str, Path, str]] = []
+        # Como DistBuilder.incremental; o initfs em si é sempre regerado
+        self.incremental = False
+        # 'dist --strict': serviços e apps não compilados viram erro em vez de aviso
+        self.strict = False
+        # (nome, comando que compila) de cada binário esperado que não existia
+        self.missing: list[tuple[str, str]] = []
+        self.hashes: Optional[ArtifactHashCache] = None
         
     async def build(self, profile: str = "release") -> bool:
         """Executa processo de build do initramfs e deploy de componentes."""
         self.log.header("Construindo InitRAMFS e Serviços")
+        self.missing = []
         
         self._clean_staging()
         self._create_initfs_structure()
         
-        # O Supervisor é o único binário que VAI DENTRO do initfs (boot)
-        supervisor_path = self.paths.service_binary("supervisor", profile)
-        if not self._add_to_initfs("supervisor", supervisor_path):
-            raise BuildError("Supervisor é necessário", "initramfs")
+        # O Supervisor e os serviços marcados com 'core = true' VÃO DENTRO do initfs (boot)
+        for svc in self._core_services():
+            svc_path = self.paths.service_binary(svc.name, profile, base_path=self.paths.root / svc.path,
+                                                target=svc.target)
+            if self._add_to_initfs(svc.name, svc_path): continue
+            if svc.name == "supervisor":
+                raise BuildError("Supervisor é necessário", "initramfs", ["Compile com: anvil build services"])
+            self.missing.append((f"serviço core {svc.name}", "anvil build services"))
+            self.log.warning(f"Serviço core '{svc.name}' ficará fora do initfs")
             
         # Cria o pacote TAR do initfs
         output = self.paths.dist_qemu / "boot" / "initfs"
         output.parent.mkdir(parents=True, exist_ok=True)
-        if await self._create_tar(output) is None: return False
+        if await self._create_tar(output) is None:
+            raise BuildError("Falha ao criar o initfs", "initramfs", [str(output)])
+        self.copied.append(("initfs", self.paths.initramfs, "boot/initfs"))
         
         # Outros serviços e apps vão para o sistema de arquivos normal (/system/services)
+        if self.incremental: self.hashes = ArtifactHashCache(self.paths.artifact_hashes)
         await self._deploy_services(profile)
         await self._deploy_apps(profile)
-        self._create_manifest()
+        if self.hashes: self.hashes.save()
+        self._create_manifest(profile)
+        if self.strict and self.missing: raise missing_artifacts_error(self.missing)
         
         return True
         
+    def _core_services(self) -> list[ServiceConfig]:
+        """Serviços embutidos no initfs: o Supervisor (sempre) e os com 'core = true'."""
+        services = [s for s in self.config.components.services if s.core or s.name == "supervisor"]
+        if not any(s.name == "supervisor" for s in services):
+            services.insert(0, ServiceConfig(name="supervisor", path="services/supervisor"))
+        return services
+
     def _clean_staging(self):
         """Limpa diretório temporário do initramfs."""
         if self.paths.initramfs.exists(): shutil.rmtree(self.paths.initramfs)
@@ -75,29 +108,48 @@ class InitramfsBuilder:
         dest = f"system/core/{name}"
         dest_path = self.paths.initramfs / dest
         shutil.copy2(source, dest_path)
+        get_events().emit("copy", source=source, dest=f"initfs:/{dest}")
         self.log.step(f"initfs: /{dest}")
         return True
         
+    def _deploy(self, name: str, source: Path, rel: str) -> bool:
+        """Copia um binário para dist/qemu/rel; False se o modo incremental manteve a cópia existente."""
+        self.copied.append((name, source, rel))
+        if not copy_artifact(source, self.paths.dist_qemu / rel, rel, self.hashes): return False
+        get_events().emit("copy", source=source, dest=rel)
+        return True
+
+    def _deploy_all(self, label: str, items: list[tuple[str, Path, str]]) -> None:
+        """Faz o deploy de (nome, origem, destino) com barra de progresso; sem ela, uma linha por binário."""
+        if not items: return
+        with progress(self.log, label, len(items)) as bar:
+            for name, source, rel in items:
+                deployed = self._deploy(name, source, rel)
+                if not bar.visible: self.log.step(f"Deploy: {name}" if deployed else f"Inalterado: {name}")
+                bar.advance()
+
     async def _deploy_services(self, profile: str):
         """Copia serviços para dist/qemu/system/services."""
         self.log.info("Implantando serviços...")
         services_dir = self.paths.dist_qemu / "system" / "services"
         services_dir.mkdir(parents=True, exist_ok=True)
         
+        core = {s.name for s in self._core_services()}
+        found = []
         for svc in self.config.components.services:
-            if svc.name == "supervisor": continue # Já foi pro initfs
+            if svc.name in core: continue # Já foi pro initfs
             
-            svc_path = self.paths.service_binary(svc.name, profile, base_path=self.paths.root / svc.path)
+            svc_path = self.paths.service_binary(svc.name, profile, base_path=self.paths.root / svc.path,
+                                                target=svc.target)
             
             if not svc_path.exists():
+                self.missing.append((f"serviço {svc.name}", "anvil build services"))
                 self.log.warning(f"Serviço '{svc.name}' não achado")
                 continue
                 
-            # Estrutura: nome_servico/nome_servico.app
-            dest_dir = services_dir / svc.name
-            dest_dir.mkdir(parents=True, exist_ok=True)
-            shutil.copy2(svc_path, dest_dir / f"{svc.name}.app")
-            self.log.step(f"Deploy: {svc.name}")
+            # Estrutura padrão: system/services/nome_servico/nome_servico.app
+            found.append((svc.name, svc_path, self.config.artifact_dest("service", profile, svc.name)))
+        self._deploy_all("Serviços", found)
             
     async def _deploy_apps(self, profile: str):
         """Copia apps para dist/qemu/apps/system."""
@@ -105,31 +157,35 @@ class InitramfsBuilder:
         apps_dir = self.paths.dist_qemu / "apps" / "system"
         apps_dir.mkdir(parents=True, exist_ok=True)
         
+        found = []
         for app in self.config.components.apps:
-            app_path = self.paths.service_binary(app.name, profile, base_path=self.paths.root / app.path)
+            app_path = self.paths.service_binary(app.name, profile, base_path=self.paths.root / app.path,
+                                                target=app.target)
             
             if not app_path.exists():
+                self.missing.append((f"app {app.name}", "anvil build apps"))
                 self.log.warning(f"App '{app.name}' não achado")
                 continue
                 
-            dest_dir = apps_dir / app.name
-            dest_dir.mkdir(parents=True, exist_ok=True)
-            shutil.copy2(app_path, dest_dir / f"{app.name}.app")
-            self.log.step(f"Deploy: {app.name}")
+            found.append((app.name, app_path, self.config.artifact_dest("app", profile, app.name)))
+        self._deploy_all("Apps", found)
             
-    def _create_manifest(self):
+    def _create_manifest(self, profile: str = "release"):
         """Gera arquivo services.toml listando serviços para o Supervisor."""
         manifests_dir = self.paths.dist_qemu / "system" / "manifests" / "services"
         manifests_dir.mkdir(parents=True, exist_ok=True)
         
         lines = ["# RedstoneOS Services Manifest", ""]
-        for svc in self.config.components.services:
-            if svc.name == "supervisor": continue
+        core = {s.name for s in self._core_services()}
+        services = self.config.components.services
+        if source_date_epoch() is not None: services = sorted(services, key=lambda s: s.name)
+        for svc in services:
+            if svc.name in core: continue
             
             lines.extend([
                 "[[service]]",
                 f'name = "{svc.name}"',
-                f'path = "/system/services/{svc.name}/{svc.name}.app"',
+                f'path = "/{self.config.artifact_dest("service", profile, svc.name)}"',
                 'restart = "always"',
                 ""
             ])
@@ -138,19 +194,27 @@ class InitramfsBuilder:
         self.log.step("Manifesto de serviços criado")
 
     async def _create_tar(self, output: Path) -> Optional[int]:
-        """Usa 'tar' via WSL para criar o arquivo initfs com permissões corretas."""
+        """
+        Cria o arquivo initfs com 'tar', via WSL quando existe (hosts Windows) ou direto com
+        o tar do host (Linux, macOS, CI).
+        """
         self.log.info("Criando arquivo TAR initfs...")
-        
-        wsl_init = Paths.to_wsl(self.paths.initramfs)
-        wsl_out = Paths.to_wsl(output)
+        wsl = shutil.which("wsl")
+        source, dest = (Paths.to_wsl(self.paths.initramfs), Paths.to_wsl(output)) if wsl else \
+            (str(self.paths.initramfs), str(output))
         
         # -C muda o diretório antes de compactar, '.' pega tudo
-        cmd = f"tar -cf '{wsl_out}' -C '{wsl_init}' ."
+        args = ["tar", "-cf", dest, "-C", source, "."]
+        epoch = source_date_epoch()
+        if epoch is not None:
+            # Ordem, datas e donos fixos: mesmo conteúdo gera o mesmo TAR. O formato gnu
+            # não grava atime/ctime (o posix, padrão em algumas distros, grava)
+            args[1:1] = ["--format=gnu", "--sort=name", f"--mtime=@{epoch}", "--owner=0", "--group=0", "--numeric-owner"]
+        cmd = ["wsl", "bash", "-c", shlex.join(args)] if wsl else args
         
         try:
             p = await asyncio.create_subprocess_exec(
-                "wsl", "bash", "-c", cmd,
-                stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.PIPE
+                *cmd, stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.PIPE
             )
             _, err = await p.communicate()
             
@@ -160,5 +224,5 @@ class InitramfsBuilder:
                 
             return output.stat().st_size
         except Exception as e:
-            self.log.error(f"Erro ao chamar WSL: {e}")
+            self.log.error(f"Erro ao chamar {'WSL' if wsl else 'tar'}: {e}")
             return None
@@ -0,0 +1,204 @@
+"""Anvil Build - Criação de ISO bootável (UEFI).
+
+O firmware UEFI boota de uma imagem FAT apontada pela entrada El Torito
+(sem emulação). Essa imagem (efiboot.img) recebe todo o dist/qemu, pois o
+Ignite lê kernel e initfs de boot():/, que é o volume de onde ele foi carregado.
+O mesmo conteúdo também fica visível no sistema de arquivos ISO9660.
+
+ISO híbrida (--hybrid, apenas xorriso): além da entrada EFI, uma entrada BIOS
+para um estágio no-emulation já presente no dist (Limine ou ISOLINUX; o kernel
+vai em boot/forge com 'dist --layout hybrid'). Sequência exata de argumentos:
+
+    xorriso -as mkisofs -R -J -V REDSTONE -o <saída> -graft-points
+        -b <estágio BIOS> -no-emul-boot -boot-load-size 4 -boot-info-table
+        -c boot/boot.catalog
+        -eltorito-alt-boot
+        -e efiboot.img -no-emul-boot
+        [-isohybrid-mbr <isohdpfx.bin>]      (ISOLINUX, para bootar de pendrive)
+        /=<dist/qemu> /efiboot.img=<dist/img/efiboot.img>
+
+Com Limine, em seguida: limine bios-install <saída>.
+"""
+
+import asyncio
+import platform
+import shutil
+from dataclasses import dataclass
+from enum import Enum
+from pathlib import Path
+from typing import Optional
+
+from core.config import Config
+from core.paths import Paths, check_free_space
+from core.errors import BuildError, ValidationError
+from core.logger import Logger, get_logger
+from core.reproducible import source_date_epoch
+from core.checksum import write_sums
+from build.image import ImageBuilder
+
+ISO_VOLUME_ID = "REDSTONE"
+EFI_IMAGE_NAME = "efiboot.img"
+
+INSTALL_HINTS = [
+    "Debian/Ubuntu: sudo apt install xorriso mtools dosfstools",
+    "Fedora: sudo dnf install xorriso mtools dosfstools",
+    "Arch: sudo pacman -S libisoburn mtools dosfstools",
+    "macOS: brew install xorriso mtools dosfstools",
+    "Windows: oscdimg vem no Windows ADK (Deployment Tools)",
+]
+
+@dataclass
+class BiosStage:
+    """Estágio de boot BIOS procurado em dist/qemu (caminhos relativos à raiz do disco)."""
+    name: str
+    boot_image: str
+    isohybrid_mbr: Optional[str] = None  # MBR para ISO em pendrive (ISOLINUX)
+    install_cmd: Optional[str] = None    # Passo pós-xorriso (Limine)
+
+BIOS_STAGES = [
+    BiosStage("limine", "boot/limine/limine-bios-cd.bin", install_cmd="limine"),
+    BiosStage("isolinux", "boot/isolinux/isolinux.bin", isohybrid_mbr="boot/isolinux/isohdpfx.bin"),
+]
+
+class IsoTool(Enum):
+    XORRISO = "xorriso"
+    OSCDIMG = "oscdimg"
+    GENISOIMAGE = "genisoimage"
+
+@dataclass
+class FoundIsoTool:
+    kind: IsoTool
+    path: Path
+
+def find_iso_tool() -> FoundIsoTool:
+    """Ferramenta de ISO disponível: xorriso (Unix) ou oscdimg (Windows), genisoimage como alternativa."""
+    order = [IsoTool.XORRISO, IsoTool.OSCDIMG, IsoTool.GENISOIMAGE]
+    if platform.system() == "Windows": order = [IsoTool.OSCDIMG, IsoTool.XORRISO, IsoTool.GENISOIMAGE]
+    for kind in order:
+        found = shutil.which(kind.value)
+        if found: return FoundIsoTool(kind, Path(found))
+    raise BuildError("Nenhuma ferramenta de ISO encontrada (xorriso, oscdimg ou genisoimage)", "iso", INSTALL_HINTS)
+
+class IsoBuilder:
+    """Gera a ISO a partir de dist/qemu, que é apenas lido."""
+
+    def __init__(self, paths: Paths, config: Config, log: Optional[Logger] = None):
+        self.paths = paths
+        self.config = config
+        self.log = log or get_logger()
+
+    async def _run(self, *cmd) -> None:
+        self.log.debug(f"Comando: {' '.join(map(str, cmd))}")
+        p = await asyncio.create_subprocess_exec(
+            *map(str, cmd), stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.STDOUT
+        )
+        out, _ = await p.communicate()
+        if p.returncode != 0:
+            raise BuildError(f"Falha ao executar {Path(str(cmd[0])).name}", "iso", out.decode(errors="replace").splitlines())
+
+    async def _create_efi_image(self, output: Path) -> None:
+        """Imagem FAT com o conteúdo do dist para a entrada El Torito EFI."""
+        for tool in ("mkfs.vfat", "mcopy"):
+            if not shutil.which(tool):
+                raise BuildError(f"{tool} não encontrado (necessário para a imagem EFI da ISO)", "iso", INSTALL_HINTS)
+        size_mb = ImageBuilder(self.paths, self.config, self.log).resolve_size_mb()
+        with open(output, "wb") as f: f.truncate(size_mb * 1024 * 1024)
+        # Modo reproduzível: volume ID fixo e datas preservadas (já normalizadas no dist)
+        reproducible = source_date_epoch() is not None
+        await self._run("mkfs.vfat", "-F", "32", *(["-i", "52534f53"] if reproducible else []), output)
+        sources = sorted(self.paths.dist_qemu.iterdir())
+        await self._run("mcopy", "-i", output, *(["-m"] if reproducible else []), "-s", *sources, "::/")
+        self.log.step(f"Imagem EFI: {output.name} ({size_mb} MiB)")
+
+    def find_bios_stage(self) -> Optional[BiosStage]:
+        """Primeiro estágio BIOS presente no dist, ou None (com aviso dos caminhos esperados)."""
+        for stage in BIOS_STAGES:
+            if (self.paths.dist_qemu / stage.boot_image).is_file(): return stage
+        self.log.warning("Nenhum estágio BIOS no dist; a ISO vai bootar apenas por UEFI. Esperado um de:")
+        for stage in BIOS_STAGES: self.log.step(f"{stage.name}: {stage.boot_image}")
+        return None
+
+    def _hybrid_command(self, tool: FoundIsoTool, stage: BiosStage, efi_image: Path, output: Path) -> list:
+        """Sequência documentada no topo do módulo."""
+        mbr = []
+        if stage.isohybrid_mbr:
+            if (self.paths.dist_qemu / stage.isohybrid_mbr).is_file():
+                mbr = ["-isohybrid-mbr", self.paths.dist_qemu / stage.isohybrid_mbr]
+            else:
+                self.log.warning(f"{stage.isohybrid_mbr} ausente: a ISO boota de CD, mas não de pendrive em BIOS")
+        return [tool.path, "-as", "mkisofs", "-R", "-J", "-V", ISO_VOLUME_ID, "-o", output, "-graft-points",
+                "-b", stage.boot_image, "-no-emul-boot", "-boot-load-size", "4", "-boot-info-table",
+                "-c", "boot/boot.catalog",
+                "-eltorito-alt-boot",
+                "-e", EFI_IMAGE_NAME, "-no-emul-boot",
+                *mbr,
+                f"/={self.paths.dist_qemu}", f"/{EFI_IMAGE_NAME}={efi_image}"]
+
+    def _iso_command(self, tool: FoundIsoTool, efi_image: Path, output: Path) -> list:
+        dist = self.paths.dist_qemu
+        if tool.kind == IsoTool.OSCDIMG:
+            # -bootdata:1#pEF = uma entrada, plataforma EFI, sem emulação (e), imagem de boot (b)
+            staging = efi_image.parent / "iso-root"
+            return [tool.path, "-m", "-o", "-u2", "-udfver102", f"-l{ISO_VOLUME_ID}",
+                    f"-bootdata:1#pEF,e,b{efi_image}", staging, output]
+        if tool.kind == IsoTool.GENISOIMAGE:
+            return [tool.path, "-R", "-J", "-V", ISO_VOLUME_ID, "-o", output, "-graft-points",
+                    "-e", EFI_IMAGE_NAME, "-no-emul-boot", f"/={dist}", f"/{EFI_IMAGE_NAME}={efi_image}"]
+        return [tool.path, "-as", "mkisofs", "-R", "-J", "-V", ISO_VOLUME_ID, "-o", output, "-graft-points",
+                "-e", EFI_IMAGE_NAME, "-no-emul-boot", f"/={dist}", f"/{EFI_IMAGE_NAME}={efi_image}"]
+
+    async def build_iso(self, output: Optional[Path] = None, keep_intermediate: bool = False,
+                        hybrid: bool = False, checksum: bool = True) -> Path:
+        """
+        Cria a ISO (padrão: dist/redstone.iso). Com keep_intermediate preserva a efiboot.img em dist/img;
+        com hybrid adiciona a entrada BIOS, se houver um estágio BIOS no dist. Com checksum grava
+        <iso>.sha256 ao lado.
+        """
+        self.log.header("Gerando ISO (BIOS+UEFI)" if hybrid else "Gerando ISO (UEFI)")
+        bootloader = self.config.artifact_dest("bootloader")
+        if not (self.paths.dist_qemu / bootloader).exists():
+            raise ValidationError(f"{bootloader} não encontrado em dist/qemu; rode 'anvil dist' primeiro", str(self.paths.dist_qemu))
+        tool = find_iso_tool()
+        self.log.step(f"Ferramenta: {tool.kind.value} ({tool.path})")
+        stage = None
+        if hybrid:
+            if tool.kind != IsoTool.XORRISO:
+                raise BuildError(f"--hybrid requer xorriso (encontrado: {tool.kind.value})", "iso", INSTALL_HINTS[:4])
+            stage = self.find_bios_stage()
+            if stage: self.log.step(f"Estágio BIOS: {stage.name} ({stage.boot_image})")
+
+        output = output or self.paths.dist / "redstone.iso"
+        output.parent.mkdir(parents=True, exist_ok=True)
+        self.paths.dist_img.mkdir(parents=True, exist_ok=True)
+        efi_image = self.paths.dist_img / EFI_IMAGE_NAME
+        size_mb = ImageBuilder(self.paths, self.config, self.log).resolve_size_mb()
+        # Imagem EFI + ISO (que contém a imagem e o dist)
+        check_free_space(output.parent, 3 * size_mb * 1024 * 1024, self.config.preflight.min_free_mb, self.log)
+
+        staging = self.paths.dist_img / "iso-root"
+        try:
+            await self._create_efi_image(efi_image)
+            if tool.kind == IsoTool.OSCDIMG:
+                # oscdimg não tem graft-points: monta uma árvore com o dist e a imagem EFI
+                if staging.exists(): shutil.rmtree(staging)
+                shutil.copytree(self.paths.dist_qemu, staging)
+                shutil.copy2(efi_image, staging / EFI_IMAGE_NAME)
+            if stage:
+                await self._run(*self._hybrid_command(tool, stage, efi_image, output))
+                if stage.install_cmd:
+                    if shutil.which(stage.install_cmd): await self._run(stage.install_cmd, "bios-install", output)
+                    else: self.log.warning(f"'{stage.install_cmd}' não encontrado: rode '{stage.install_cmd} bios-install {output}' "
+                                           "para a ISO bootar de pendrive em BIOS")
+            else:
+                await self._run(*self._iso_command(tool, efi_image, output))
+        finally:
+            if staging.exists(): shutil.rmtree(staging)
+            if keep_intermediate: self.log.step(f"Imagem EFI preservada: {efi_image}")
+            elif efi_image.exists(): efi_image.unlink()
+
+        if checksum:
+            sums = output.with_name(output.name + ".sha256")
+            write_sums(sums, [output], output.parent)
+            self.log.step(f"SHA-256: {sums.name}")
+        self.log.success(f"ISO gerada: {output}")
+        return output
@@ -0,0 +1,71 @@
+"""Anvil Build - Manifesto do dist (dist/manifest.json).
+
+Descreve cada artefato copiado para dist/qemu: nome lógico, origem, destino,
+tamanho e SHA-256, com o perfil, a versão e a data do build no cabeçalho. Etapas
+seguintes (ISO, pendrive, ferramentas externas) podem ler o manifesto em vez
+de supor os caminhos de [dist].
+"""
+
+import json
+from dataclasses import asdict, dataclass, field
+from datetime import datetime, timezone
+from pathlib import Path
+from typing import Optional
+
+from core.checksum import sha256_file
+from core.errors import ValidationError
+from core.reproducible import source_date_epoch
+
+MANIFEST_VERSION = 1
+
+@dataclass
+class ManifestEntry:
+    """Artefato do dist; dest é relativo a dist/qemu, source ao projeto (se estiver dentro dele)."""
+    name: str
+    source: str
+    dest: str
+    size: int
+    sha256: str
+
+@dataclass
+class DistManifest:
+    profile: str
+    # ISO 8601 em UTC; no modo reproduzível, a data de SOURCE_DATE_EPOCH
+    created: str
+    entries: list[ManifestEntry] = field(default_factory=list)
+    version: int = MANIFEST_VERSION
+    # ANVIL_BUILD_VERSION gravado nos componentes; vazio em manifestos antigos
+    build_version: str = ""
+
+    @classmethod
+    def collect(cls, dist_root: Path, project_root: Path, profile: str,
+                copied: list[tuple[str, Path, str]], build_version: str = "") -> "DistManifest":
+        """Monta o manifesto a partir das cópias (nome, origem, destino) que ainda existem no dist."""
+        epoch = source_date_epoch()
+        moment = datetime.fromtimestamp(epoch, timezone.utc) if epoch is not None else datetime.now(timezone.utc)
+        entries = []
+        for name, source, rel in copied:
+            dest = dist_root / rel
+            # Ex: arquivos de system/ depois do 'dist --squashfs'
+            if not dest.is_file(): continue
+            source = Path(source)
+            shown = source.relative_to(project_root).as_posix() if source.is_relative_to(project_root) else str(source)
+            entries.append(ManifestEntry(name, shown, Path(rel).as_posix(), dest.stat().st_size, sha256_file(dest)))
+        return cls(profile, moment.strftime("%Y-%m-%dT%H:%M:%SZ"), sorted(entries, key=lambda e: e.dest),
+                   build_version=build_version)
+
+    def find(self, name: str) -> Optional[ManifestEntry]:
+        return next((e for e in self.entries if e.name == name), None)
+
+def write_manifest(manifest: DistManifest, output: Path) -> Path:
+    output.parent.mkdir(parents=True, exist_ok=True)
+    output.write_text(json.dumps(asdict(manifest), indent=2, ensure_ascii=False) + "\n", encoding="utf-8")
+    return output
+
+def read_manifest(path: Path) -> DistManifest:
+    try:
+        data = json.loads(path.read_text(encoding="utf-8"))
+        entries = [ManifestEntry(**e) for e in data.pop("entries", [])]
+        return DistManifest(**data, entries=entries)
+    except (OSError, ValueError, TypeError) as e:
+        raise ValidationError(f"Manifesto inválido: {path} ({e})", str(path))
@@ -0,0 +1,48 @@
+"""Anvil Build - Resumo de tempos do build.
+
+Cada invocação do cargo feita por run_cargo entra no relatório; o 'anvil build'
+imprime a tabela ao final para mostrar qual componente é o gargalo.
+"""
+
+from dataclasses import dataclass, field
+from typing import Optional
+
+from core.logger import Colors, Logger
+
+@dataclass
+class PackageTiming:
+    """Uma invocação do cargo."""
+    name: str
+    target: Optional[str]
+    profile: str
+    seconds: float
+    ok: bool
+    cached: bool = False  # Pulado pelo --fast-check
+
+@dataclass
+class BuildReport:
+    entries: list[PackageTiming] = field(default_factory=list)
+
+    def add(self, timing: PackageTiming) -> None:
+        self.entries.append(timing)
+
+    @property
+    def total(self) -> float:
+        return sum(e.seconds for e in self.entries)
+
+    def print(self, log: Logger) -> None:
+        if not self.entries or log.quiet: return
+        profiles = ", ".join(dict.fromkeys(e.profile for e in self.entries))
+        log.header(f"Resumo do Build ({profiles})")
+        name_w = max(len("Pacote"), *(len(e.name) for e in self.entries))
+        target_w = max(len("Target"), *(len(e.target or "padrão") for e in self.entries))
+        log.raw(f"  {Colors.BOLD}{'Pacote':<{name_w}}  {'Target':<{target_w}}  {'Tempo':>8}{Colors.RESET}")
+        slowest = max(self.entries, key=lambda e: e.seconds)
+        for e in self.entries:
+            status = f"{Colors.GREY}cache{Colors.RESET}" if e.cached else \
+                f"{Colors.GREEN}ok{Colors.RESET}" if e.ok else f"{Colors.RED}falhou{Colors.RESET}"
+            # O mais lento em amarelo
+            color = Colors.YELLOW if e is slowest and len(self.entries) > 1 else ""
+            log.raw(f"  {e.name:<{name_w}}  {e.target or 'padrão':<{target_w}}  "
+                    f"{color}{e.seconds:>7.1f}s{Colors.RESET}  {status}")
+        log.raw(f"  {Colors.BOLD}{'Total':<{name_w}}  {'':<{target_w}}  {self.total:>7.1f}s{Colors.RESET}")
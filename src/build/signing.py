@@ -0,0 +1,66 @@
+"""Anvil Build - Assinatura destacada do manifesto ('dist --sign-manifest').
+
+O manifest.json já traz o SHA-256 de cada artefato; assiná-lo cobre o dist
+inteiro. A assinatura usa 'ssh-keygen -Y' (chave ed25519 do OpenSSH, sem
+dependências extras) e fica ao lado, em manifest.json.sig:
+
+    ssh-keygen -Y sign -f <chave privada> -n anvil-dist manifest.json
+    ssh-keygen -Y verify -f <allowed_signers> -I <identidade> -n anvil-dist
+        -s manifest.json.sig < manifest.json
+
+O allowed_signers da verificação é montado a partir da chave pública, então
+quem recebe o dist só precisa do .pub.
+"""
+
+import shutil
+import subprocess
+import tempfile
+from pathlib import Path
+
+from core.errors import BuildError, ValidationError
+
+SIGNATURE_NAMESPACE = "anvil-dist"
+SIGNATURE_SUFFIX = ".sig"
+
+INSTALL_HINTS = [
+    "Debian/Ubuntu: sudo apt install openssh-client",
+    "Fedora: sudo dnf install openssh-clients",
+    "Gere uma chave: ssh-keygen -t ed25519 -f ~/.ssh/redstone-dist",
+]
+
+def signature_path(manifest: Path) -> Path:
+    return manifest.with_name(manifest.name + SIGNATURE_SUFFIX)
+
+def _ssh_keygen() -> str:
+    tool = shutil.which("ssh-keygen")
+    if not tool: raise BuildError("ssh-keygen não encontrado", "sign", INSTALL_HINTS)
+    return tool
+
+def sign_manifest(manifest: Path, key: Path) -> Path:
+    """Gera manifest.json.sig com a chave privada key; substitui uma assinatura anterior."""
+    if not key.is_file(): raise ValidationError(f"Chave de assinatura não encontrada: {key}", str(key))
+    tool = _ssh_keygen()
+    sig = signature_path(manifest)
+    # O ssh-keygen se recusa a sobrescrever o .sig
+    sig.unlink(missing_ok=True)
+    p = subprocess.run([tool, "-Y", "sign", "-f", str(key), "-n", SIGNATURE_NAMESPACE, str(manifest)],
+                       capture_output=True, text=True, stdin=subprocess.DEVNULL)
+    if p.returncode != 0 or not sig.is_file():
+        raise BuildError(f"Falha ao assinar {manifest.name}", "sign", (p.stderr or p.stdout).strip().splitlines())
+    return sig
+
+def verify_manifest(manifest: Path, public_key: Path, identity: str) -> tuple[bool, str]:
+    """(válida, mensagem do ssh-keygen) da assinatura de manifest contra a chave pública."""
+    sig = signature_path(manifest)
+    if not sig.is_file(): raise ValidationError(f"Assinatura não encontrada: {sig}", str(sig))
+    if not public_key.is_file(): raise ValidationError(f"Chave pública não encontrada: {public_key}", str(public_key))
+    tool = _ssh_keygen()
+    fields = public_key.read_text(encoding="utf-8").split()
+    if len(fields) < 2: raise ValidationError(f"Chave pública inválida: {public_key}", str(public_key))
+    with tempfile.TemporaryDirectory(prefix="anvil-sig-") as tmp:
+        signers = Path(tmp) / "allowed_signers"
+        signers.write_text(f'{identity} namespaces="{SIGNATURE_NAMESPACE}" {fields[0]} {fields[1]}\n', encoding="utf-8")
+        with open(manifest, "rb") as data:
+            p = subprocess.run([tool, "-Y", "verify", "-f", str(signers), "-I", identity, "-n", SIGNATURE_NAMESPACE,
+                                "-s", str(sig)], stdin=data, capture_output=True, text=True)
+    return p.returncode == 0, (p.stdout or p.stderr).strip()
@@ -0,0 +1,64 @@
+"""Anvil Build - Tamanho das seções dos binários ('anvil size').
+
+Usa llvm-size (llvm-tools-preview), rust-size (cargo-binutils) ou o size do
+binutils no formato SysV (-A), que lista cada seção com tamanho e endereço.
+Em binários PE (bootloader) a .rdata faz o papel da .rodata.
+"""
+
+import asyncio
+from dataclasses import dataclass, field
+from pathlib import Path
+from typing import Optional
+
+from core.errors import BuildError
+from core.logger import Colors, Logger
+from core.paths import format_size
+from core.toolchain import llvm_tool
+
+SECTIONS = [".text", ".rodata", ".data", ".bss"]
+SIZE_TOOLS = ["llvm-size", "rust-size", "size"]
+INSTALL_HINTS = ["rustup component add llvm-tools-preview", "cargo install cargo-binutils"]
+SECTION_ALIASES = {".rdata": ".rodata"}
+
+@dataclass
+class BinarySize:
+    name: str
+    path: Path
+    sections: dict[str, int] = field(default_factory=dict)
+
+    @property
+    def total(self) -> int:
+        return sum(self.sections.get(s, 0) for s in SECTIONS)
+
+def find_size_tool() -> Optional[Path]:
+    return next((p for p in map(llvm_tool, SIZE_TOOLS) if p), None)
+
+def parse_sysv(output: str) -> dict[str, int]:
+    """Soma o tamanho por seção (subseções como .text.boot entram em .text)."""
+    sections: dict[str, int] = {}
+    for line in output.splitlines():
+        parts = line.split()
+        if len(parts) < 2 or not parts[0].startswith(".") or not parts[1].isdigit(): continue
+        name = SECTION_ALIASES.get(parts[0], parts[0])
+        base = next((s for s in SECTIONS if name == s or name.startswith(s + ".")), name)
+        sections[base] = sections.get(base, 0) + int(parts[1])
+    return sections
+
+async def measure(tool: Path, name: str, path: Path) -> BinarySize:
+    p = await asyncio.create_subprocess_exec(
+        str(tool), "-A", str(path), stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.STDOUT
+    )
+    out, _ = await p.communicate()
+    if p.returncode != 0:
+        raise BuildError(f"Falha ao executar {tool.name} em {path.name}", "size", out.decode(errors="replace").splitlines())
+    return BinarySize(name, path, parse_sysv(out.decode(errors="replace")))
+
+def print_table(sizes: list[BinarySize], log: Logger) -> None:
+    """Tabela em bytes, uma linha por binário, com o total das seções e o tamanho do arquivo."""
+    name_w = max(len("Binário"), *(len(s.name) for s in sizes))
+    header = "".join(f"{s:>12}" for s in [*SECTIONS, "Total", "Arquivo"])
+    log.raw(f"  {Colors.BOLD}{'Binário':<{name_w}}{header}{Colors.RESET}")
+    for s in sizes:
+        cells = "".join(f"{s.sections.get(sec, 0):>12,}" for sec in SECTIONS)
+        log.raw(f"  {Colors.CYAN}{s.name:<{name_w}}{Colors.RESET}{cells}{s.total:>12,}"
+                f"{format_size(s.path.stat().st_size):>12}")
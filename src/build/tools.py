@@ -0,0 +1,65 @@
+"""Anvil Build - Ferramentas de Host.
+
+Descobre crates do workspace que não fazem parte do SO (ex: formatador de disco)
+para que sejam compilados para o triple do host e usados nas etapas de dist/imagem.
+"""
+
+import asyncio
+import json
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Optional
+
+from core.config import Config
+from core.paths import Paths
+from core.workspace import crate_name
+from core.logger import Logger, get_logger
+
+@dataclass
+class HostTool:
+    """Crate de ferramenta que roda no host."""
+    name: str
+    path: Path
+
+class HostToolsDiscovery:
+    """Lista as ferramentas de host via 'cargo metadata', excluindo os pacotes do SO."""
+
+    def __init__(self, paths: Paths, config: Config, log: Optional[Logger] = None):
+        self.paths = paths
+        self.config = config
+        self.log = log or get_logger()
+
+    def _os_packages(self) -> tuple[set[str], list[Path]]:
+        """Nomes e diretórios dos componentes conhecidos do SO."""
+        comps = self.config.components
+        dirs = [self.paths.root / comps.kernel.path, self.paths.root / comps.bootloader.path]
+        names = {crate_name(d) for d in dirs}
+        for item in [*comps.services, *comps.apps]:
+            names.add(item.name)
+            dirs.append(self.paths.root / item.path)
+        return names, [d.resolve() for d in dirs]
+
+    async def discover(self) -> list[HostTool]:
+        """Retorna os pacotes do workspace que não são componentes do SO."""
+        try:
+            p = await asyncio.create_subprocess_exec(
+                "cargo", "metadata", "--no-deps", "--format-version", "1",
+                cwd=self.paths.root, stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.PIPE
+            )
+            out, err = await p.communicate()
+        except Exception as e:
+            self.log.error(f"Erro ao chamar cargo metadata: {e}")
+            return []
+
+        if p.returncode != 0:
+            self.log.warning(f"cargo metadata falhou em {self.paths.root}: {err.decode(errors='replace').strip()}")
+            return []
+
+        names, dirs = self._os_packages()
+        tools = []
+        for pkg in json.loads(out).get("packages", []):
+            pkg_dir = Path(pkg["manifest_path"]).parent.resolve()
+            if pkg["name"] in names: continue
+            if any(pkg_dir == d or d in pkg_dir.parents for d in dirs): continue
+            tools.append(HostTool(pkg["name"], pkg_dir))
+        return tools
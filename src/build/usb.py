@@ -0,0 +1,354 @@
+"""Anvil Build - Gravação do dist em pendrive USB.
+
+Lista os discos removíveis do host para que o usuário escolha o destino e grava
+o dist/qemu nele: o disco recebe uma tabela GPT com uma única partição de sistema
+EFI (sgdisk -n1:0:0 -t1:ef00) formatada como FAT32, que o firmware UEFI boota
+por EFI/BOOT/BOOTX64.EFI. A gravação só existe no Linux.
+
+Partições de discos cujo nome termina em dígito (nvme0n1, mmcblk0) levam um 'p'
+antes do número: /dev/nvme0n1p1, mas /dev/sdb1.
+"""
+
+import hashlib
+import json
+import os
+import platform
+import plistlib
+import queue
+import re
+import shutil
+import subprocess
+import tempfile
+import threading
+import time
+from contextlib import contextmanager
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Optional
+
+from core.checksum import sha256_file
+from core.config import Config
+from core.errors import BuildError, ValidationError
+from core.paths import Paths, format_size
+from core.logger import Logger, get_logger
+from core.progress import progress
+
+USB_VOLUME_LABEL = "REDSTONE"
+COPY_CHUNK = 1024 * 1024
+# Abaixo disso a cópia é sequencial: as threads não compensam numa árvore pequena
+PIPELINE_THRESHOLD = 64 * 1024 * 1024
+# Blocos em trânsito em cada fila entre a leitura e as threads de gravação e de hash
+PIPELINE_DEPTH = 8
+# Espera pelo nó da partição nova em /dev (udev), em segundos
+PARTITION_WAIT = 10.0
+
+@dataclass
+class UsbDevice:
+    """Disco candidato a receber o dist."""
+    path: str
+    model: str
+    size: int  # bytes
+    removable: bool = True
+
+    def describe(self) -> str:
+        kind = "" if self.removable else " [NÃO REMOVÍVEL]"
+        return f"{self.path}  {self.model or '(sem modelo)'}  {format_size(self.size)}{kind}"
+
+def _read(path: Path) -> str:
+    try: return path.read_text().strip()
+    except OSError: return ""
+
+def _linux_devices() -> list[UsbDevice]:
+    devices = []
+    for block in sorted(Path("/sys/block").iterdir()):
+        # Loop, RAM e device-mapper não são discos físicos
+        if block.name.startswith(("loop", "ram", "dm-", "zram", "sr")): continue
+        sectors = _read(block / "size")
+        if not sectors.isdigit() or int(sectors) == 0: continue
+        model = " ".join(filter(None, [_read(block / "device" / "vendor"), _read(block / "device" / "model")]))
+        devices.append(UsbDevice(f"/dev/{block.name}", model, int(sectors) * 512, _read(block / "removable") == "1"))
+    return devices
+
+def _macos_devices() -> list[UsbDevice]:
+    out = subprocess.run(["diskutil", "list", "-plist", "physical"], capture_output=True, check=True).stdout
+    devices = []
+    for name in plistlib.loads(out).get("WholeDisks", []):
+        info = plistlib.loads(subprocess.run(["diskutil", "info", "-plist", name], capture_output=True, check=True).stdout)
+        removable = info.get("RemovableMediaOrExternalDevice", info.get("Removable", False))
+        devices.append(UsbDevice(f"/dev/{name}", info.get("MediaName", ""), info.get("TotalSize", 0), bool(removable)))
+    return devices
+
+def _windows_devices() -> list[UsbDevice]:
+    script = "Get-Disk | Select-Object Number,FriendlyName,Size,BusType | ConvertTo-Json"
+    out = subprocess.run(["powershell", "-NoProfile", "-Command", script], capture_output=True, check=True, text=True).stdout
+    data = json.loads(out or "[]")
+    if isinstance(data, dict): data = [data]
+    return [UsbDevice(f"\\\\.\\PhysicalDrive{d['Number']}", d.get("FriendlyName") or "", int(d.get("Size") or 0),
+                      d.get("BusType") in ("USB", 7, "SD", 12)) for d in data]
+
+def list_usb_devices(all_disks: bool = False) -> list[UsbDevice]:
+    """Discos do host; só os removíveis, a menos que all_disks seja True."""
+    system = platform.system()
+    try:
+        if system == "Linux": devices = _linux_devices()
+        elif system == "Darwin": devices = _macos_devices()
+        elif system == "Windows": devices = _windows_devices()
+        else: raise BuildError(f"Listagem de discos não suportada em {system}", "usb")
+    except (OSError, subprocess.CalledProcessError, ValueError) as e:
+        raise BuildError(f"Falha ao listar discos: {e}", "usb")
+    return devices if all_disks else [d for d in devices if d.removable]
+
+def partition_path(device: str, number: int) -> str:
+    """Nó da partição number do disco: /dev/sdb -> /dev/sdb1, /dev/nvme0n1 -> /dev/nvme0n1p1."""
+    return f"{device}{'p' if device[-1:].isdigit() else ''}{number}"
+
+def is_partition_of(source: str, device: str) -> bool:
+    """Se source é o próprio disco ou uma partição dele (/dev/sdb1 sim; /dev/sdba1 e /dev/nvme0n10 não)."""
+    suffix = r"p\d+" if device[-1:].isdigit() else r"\d+"
+    return source == device or re.fullmatch(re.escape(device) + suffix, source) is not None
+
+def _drop_cache(path: Path) -> None:
+    """Descarta o arquivo do page cache para a releitura vir do disco, não da memória."""
+    if not hasattr(os, "posix_fadvise"): return
+    fd = os.open(path, os.O_RDONLY)
+    try: os.posix_fadvise(fd, 0, 0, os.POSIX_FADV_DONTNEED)
+    finally: os.close(fd)
+
+def verify_copy(expected: dict[str, str], dst_root: Path, log: Optional[Logger] = None) -> None:
+    """
+    Relê cada arquivo em dst_root (fora do page cache) e compara o SHA-256 com o calculado
+    da origem durante a cópia (caminho relativo -> hash); BuildError em divergência.
+    """
+    log = log or get_logger()
+    os.sync()
+    problems = []
+    for rel, digest in expected.items():
+        dst = dst_root / rel
+        if not dst.is_file():
+            problems.append(f"{rel}: ausente no destino")
+            continue
+        _drop_cache(dst)
+        if sha256_file(dst) != digest: problems.append(f"{rel}: SHA-256 diferente")
+        else: log.debug(f"OK: {rel}")
+    if problems:
+        raise BuildError(f"Verificação falhou em {len(problems)} de {len(expected)} arquivo(s)", "usb", problems)
+    log.step(f"Verificados: {len(expected)} arquivo(s)")
+
+def throughput(size: int, seconds: float) -> str:
+    return f"{size / (1024 * 1024) / seconds:.1f} MB/s" if seconds > 0 else "-"
+
+class _Stage(threading.Thread):
+    """
+    Consumidor de uma fila limitada. Um erro fica em self.error para a thread que lê, e a
+    fila continua sendo esvaziada até o None final, para a leitura nunca travar num put.
+    """
+
+    def __init__(self, name: str, handle):
+        super().__init__(name=name, daemon=True)
+        self.queue: queue.Queue = queue.Queue(maxsize=PIPELINE_DEPTH)
+        self.handle = handle
+        self.error: Optional[BaseException] = None
+        self.cancelled = threading.Event()
+
+    def run(self) -> None:
+        while (item := self.queue.get()) is not None:
+            if self.error or self.cancelled.is_set(): continue
+            try: self.handle(*item)
+            except BaseException as e: self.error = e
+
+class UsbWriter:
+    """Formata o pendrive e copia o dist/qemu para ele."""
+
+    def __init__(self, paths: Paths, config: Config, log: Optional[Logger] = None):
+        self.paths = paths
+        self.config = config
+        self.log = log or get_logger()
+
+    def _run(self, *cmd) -> None:
+        self.log.debug(f"Comando: {' '.join(map(str, cmd))}")
+        p = subprocess.run([str(c) for c in cmd], capture_output=True, text=True)
+        if p.returncode != 0:
+            raise BuildError(f"Falha ao executar {Path(str(cmd[0])).name}", "usb", (p.stdout + p.stderr).splitlines())
+
+    def _unmount_partitions(self, device: UsbDevice) -> None:
+        """Desmonta partições do disco montadas automaticamente pelo desktop."""
+        try: mounts = Path("/proc/mounts").read_text().splitlines()
+        except OSError: return
+        for line in mounts:
+            source, target = line.split()[:2]
+            if is_partition_of(source, device.path):
+                self.log.step(f"Desmontando {source} ({target})")
+                self._run("umount", source)
+
+    @contextmanager
+    def _mounted(self, volume: str):
+        """Monta o volume num diretório temporário; desmonta e remove o diretório mesmo em erro."""
+        mountpoint = Path(tempfile.mkdtemp(prefix="anvil-usb-"))
+        mounted = False
+        try:
+            self._run("mount", volume, mountpoint)
+            mounted = True
+            yield mountpoint
+        finally:
+            if mounted:
+                subprocess.run(["sync"])
+                p = subprocess.run(["umount", str(mountpoint)], capture_output=True, text=True)
+                mounted = p.returncode != 0
+                if mounted:
+                    self.log.error(f"Falha ao desmontar {mountpoint}: {p.stderr.strip()}")
+                    self.log.step(f"Desmonte manualmente: sudo umount {mountpoint}")
+            if not mounted: mountpoint.rmdir()
+
+    def _copy_tree(self, dest: Path) -> tuple[int, dict[str, str]]:
+        """
+        Copia o dist/qemu para dest, com o progresso em bytes, calculando o SHA-256 da origem
+        na mesma leitura: o --verify só precisa reler o destino. Acima de PIPELINE_THRESHOLD a
+        gravação e o hash rodam em threads próprias. Retorna o total de bytes e o hash de cada
+        arquivo (caminho relativo -> hash).
+        """
+        files = sorted(p for p in self.paths.dist_qemu.rglob("*") if p.is_file())
+        total = sum(f.stat().st_size for f in files)
+        with progress(self.log, "Gravando", total, unit="bytes") as bar:
+            if total < PIPELINE_THRESHOLD: hashes = self._copy_sequential(files, dest, bar)
+            else: hashes = self._copy_pipeline(files, dest, bar)
+        return total, hashes
+
+    def _copy_sequential(self, files: list[Path], dest: Path, bar) -> dict[str, str]:
+        hashes = {}
+        for src in files:
+            rel = src.relative_to(self.paths.dist_qemu)
+            (dest / rel).parent.mkdir(parents=True, exist_ok=True)
+            digest = hashlib.sha256()
+            # Cópia só do conteúdo, em blocos: FAT não guarda permissões nem dono
+            with open(src, "rb") as fin, open(dest / rel, "wb") as fout:
+                while chunk := fin.read(COPY_CHUNK):
+                    fout.write(chunk)
+                    digest.update(chunk)
+                    bar.advance(len(chunk))
+            hashes[rel.as_posix()] = digest.hexdigest()
+            self.log.debug(f"{rel} ({format_size(src.stat().st_size)})")
+        return hashes
+
+    def _copy_pipeline(self, files: list[Path], dest: Path, bar) -> dict[str, str]:
+        """
+        Produtor/consumidor: esta thread lê os blocos e os entrega, por filas limitadas, a uma
+        thread que grava no pendrive e a outra que calcula o hash, então o pendrive lento não
+        espera pelo SHA-256. Um erro em qualquer ponto (ou um Ctrl-C) cancela as duas threads
+        e chega a quem chamou.
+        """
+        hashes, digests, out = {}, {}, {}
+
+        def write(kind: str, rel: str, data: bytes = b"") -> None:
+            if kind == "open":
+                (dest / rel).parent.mkdir(parents=True, exist_ok=True)
+                out["file"] = open(dest / rel, "wb")
+            elif kind == "data":
+                out["file"].write(data)
+                bar.advance(len(data))
+            else: out.pop("file").close()
+
+        def digest(kind: str, rel: str, data: bytes = b"") -> None:
+            if kind == "open": digests[rel] = hashlib.sha256()
+            elif kind == "data": digests[rel].update(data)
+            else: hashes[rel] = digests.pop(rel).hexdigest()
+
+        stages = [_Stage("usb-write", write), _Stage("usb-hash", digest)]
+        def feed(*item) -> None:
+            for stage in stages:
+                if stage.error: raise stage.error
+                stage.queue.put(item)
+
+        for stage in stages: stage.start()
+        try:
+            for src in files:
+                rel = src.relative_to(self.paths.dist_qemu).as_posix()
+                feed("open", rel)
+                with open(src, "rb") as fin:
+                    while chunk := fin.read(COPY_CHUNK): feed("data", rel, chunk)
+                feed("close", rel)
+                self.log.debug(f"{rel} ({format_size(src.stat().st_size)})")
+        except BaseException:
+            for stage in stages: stage.cancelled.set()
+            raise
+        finally:
+            for stage in stages: stage.queue.put(None)
+            for stage in stages: stage.join()
+            if "file" in out: out.pop("file").close()
+        for stage in stages:
+            if stage.error: raise stage.error
+        return hashes
+
+    def filesystem_type(self, path: str) -> str:
+        """Tipo do sistema de arquivos em path segundo o blkid ('' se não houver nenhum)."""
+        if not shutil.which("blkid"): raise BuildError("blkid não encontrado", "usb", ["Debian/Ubuntu: sudo apt install util-linux"])
+        p = subprocess.run(["blkid", "-o", "value", "-s", "TYPE", path], capture_output=True, text=True)
+        return p.stdout.strip()
+
+    def _partition(self, device: UsbDevice) -> str:
+        """Recria o disco com GPT e uma só partição EFI ocupando tudo; retorna o nó da partição."""
+        self._run("sgdisk", "--zap-all", device.path)
+        self._run("sgdisk", "-n1:0:0", "-t1:ef00", f"-c1:{USB_VOLUME_LABEL}", device.path)
+        # O kernel relê a tabela; o udev cria o nó da partição logo depois
+        subprocess.run(["partprobe", device.path], capture_output=True)
+        if shutil.which("udevadm"): subprocess.run(["udevadm", "settle"], capture_output=True)
+        partition = partition_path(device.path, 1)
+        deadline = time.monotonic() + PARTITION_WAIT
+        while not Path(partition).exists():
+            if time.monotonic() > deadline:
+                raise BuildError(f"{partition} não apareceu após criar a tabela de partições", "usb",
+                                 ["Remova e reconecte o pendrive e tente de novo"])
+            time.sleep(0.2)
+        self.log.step(f"Tabela GPT criada: {partition} (partição de sistema EFI)")
+        return partition
+
+    def _existing_volume(self, device: UsbDevice) -> str:
+        """
+        Volume FAT de um pendrive já preparado (--no-format): a primeira partição ou, nos
+        gravados antes da tabela GPT, o disco inteiro.
+        """
+        for volume in (partition_path(device.path, 1), device.path):
+            if not Path(volume).exists(): continue
+            fstype = self.filesystem_type(volume)
+            if fstype == "vfat": return volume
+            if volume != device.path and fstype:
+                raise ValidationError(f"{volume} não é FAT ({fstype}); rode sem --no-format para formatar", volume)
+        raise ValidationError(f"Nenhum sistema FAT em {device.path}; rode sem --no-format para formatar", device.path)
+
+    def write(self, device: UsbDevice, verify: bool = False, reformat: bool = True) -> None:
+        """
+        Particiona o disco (GPT, uma partição EFI), formata como FAT32 e copia o dist; apaga tudo
+        que havia nele. Sem reformat copia sobre o FAT que já existe, mantendo os outros arquivos.
+        Com verify relê e confere cada arquivo.
+        """
+        if platform.system() != "Linux":
+            raise BuildError(f"Gravação de pendrive não suportada em {platform.system()}", "usb",
+                             ["Use 'anvil iso' e uma ferramenta como Rufus ou balenaEtcher"])
+        if reformat:
+            for tool, package in (("mkfs.vfat", "dosfstools"), ("sgdisk", "gdisk")):
+                if not shutil.which(tool): raise BuildError(f"{tool} não encontrado", "usb", [f"Debian/Ubuntu: sudo apt install {package}"])
+        if os.geteuid() != 0:
+            raise ValidationError("Gravar no disco exige root; rode com sudo", device.path)
+        bootloader = self.config.artifact_dest("bootloader")
+        if not (self.paths.dist_qemu / bootloader).is_file():
+            raise ValidationError(f"{bootloader} não encontrado em dist/qemu; rode 'anvil dist' primeiro", str(self.paths.dist_qemu))
+
+        self.log.header(f"Gravando {device.path}")
+        self._unmount_partitions(device)
+        if reformat:
+            volume = self._partition(device)
+            self._run("mkfs.vfat", "-F", "32", "-n", USB_VOLUME_LABEL, volume)
+            self.log.step(f"Formatado como FAT32 ({USB_VOLUME_LABEL})")
+        else:
+            volume = self._existing_volume(device)
+            self.log.step(f"FAT existente em {volume} mantido (--no-format)")
+        with self._mounted(volume) as mountpoint:
+            started = time.monotonic()
+            total, hashes = self._copy_tree(mountpoint)
+            # O sync entra na conta: sem ele a taxa seria a do page cache, não a do pendrive
+            os.sync()
+            elapsed = time.monotonic() - started
+            if not (mountpoint / bootloader).is_file():
+                raise BuildError(f"{bootloader} ausente no pendrive após a cópia", "usb")
+            self.log.step(f"Copiado: {format_size(total)} em {elapsed:.1f}s ({throughput(total, elapsed)})")
+            if verify: verify_copy(hashes, mountpoint, self.log)
+        self.log.success(f"Pendrive pronto: {device.path}")
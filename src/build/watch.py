@@ -0,0 +1,54 @@
+"""Anvil Build - Rebuild contínuo ('anvil watch').
+
+Observa os fontes dos componentes por polling, com a mesma impressão digital
+(caminho, tamanho e mtime) do 'build --fast-check', então não depende de
+inotify nem de bibliotecas externas. Uma rajada de gravações (salvar vários
+arquivos, formatador do editor) vira um único rebuild: depois da primeira
+mudança, espera os fontes ficarem estáveis pelo tempo de debounce.
+"""
+
+import asyncio
+from pathlib import Path
+from typing import Optional
+
+from core.config import Config
+from core.paths import Paths
+from core.logger import Logger, get_logger
+from build.fastcheck import fingerprint
+
+POLL_INTERVAL = 0.5  # segundos
+DEFAULT_DEBOUNCE = 0.5
+
+class SourceWatcher:
+    """Detecta mudanças nos diretórios de um grupo de componentes (ou de todos)."""
+
+    def __init__(self, paths: Paths, config: Config, only: Optional[str] = None, log: Optional[Logger] = None):
+        self.paths = paths
+        self.config = config
+        self.log = log or get_logger()
+        self.dirs = self._dirs(only)
+        self.files = [self.paths.root / "Cargo.lock", self.paths.root / "Cargo.toml"]
+
+    def _dirs(self, only: Optional[str]) -> list[Path]:
+        comps = self.config.components
+        dirs = []
+        if only in (None, "kernel"): dirs.append(self.paths.forge)
+        if only in (None, "bootloader"): dirs.append(self.paths.ignite)
+        groups = {"services": comps.services, "apps": comps.apps, "drivers": comps.drivers}
+        for group, items in groups.items():
+            if only in (None, group): dirs += [self.paths.root / c.path for c in items]
+        # Bibliotecas compartilhadas afetam qualquer componente
+        return [*dict.fromkeys(dirs), self.paths.lib, self.paths.sdk]
+
+    def snapshot(self) -> str:
+        return fingerprint(self.dirs, self.files, {})
+
+    async def wait_for_change(self, previous: str, debounce: float = DEFAULT_DEBOUNCE) -> str:
+        """Bloqueia até os fontes mudarem e ficarem estáveis por debounce segundos; retorna a nova impressão."""
+        while (current := self.snapshot()) == previous:
+            await asyncio.sleep(POLL_INTERVAL)
+        while True:
+            await asyncio.sleep(debounce)
+            settled = self.snapshot()
+            if settled == current: return settled
+            current = settled
@@ -0,0 +1,17 @@
+"""Anvil Core - Somas SHA-256 dos artefatos (formato do sha256sum)."""
+
+import hashlib
+from pathlib import Path
+
+CHUNK = 1024 * 1024
+
+def sha256_file(path: Path) -> str:
+    h = hashlib.sha256()
+    with open(path, "rb") as f:
+        while chunk := f.read(CHUNK): h.update(chunk)
+    return h.hexdigest()
+
+def write_sums(output: Path, files: list[Path], base: Path) -> None:
+    """Grava '<hash>  <caminho relativo a base>' por arquivo; confira com 'sha256sum -c' a partir de base."""
+    lines = [f"{sha256_file(f)}  {f.relative_to(base).as_posix()}" for f in files]
+    output.write_text("\n".join(lines) + "\n", encoding="utf-8")
@@ -1,7 +1,7 @@
 """Anvil Core - Carregamento de configurações."""
 
 from __future__ import annotations
-from dataclasses import dataclass, field
+from dataclasses import dataclass, field, replace
 from pathlib import Path
 from typing import Any, Optional
 import toml
@@ -22,6 +22,12 @@ class KernelConfig:
     path: str = "forge"
     target: str = "x86_64-redstone"
     default_profile: str = "release"
+    # Nome do binário em target/<target>/<perfil>/ (forks que renomeiam o kernel)
+    binary: str = "forge"
+    # Features do cargo que ativam o harness de testes do kernel ('anvil test')
+    test_features: list[str] = field(default_factory=lambda: ["test"])
+    # Features que trocam o boot pelos microbenchmarks ('anvil bench')
+    bench_features: list[str] = field(default_factory=lambda: ["bench"])
 
 @dataclass
 class BootloaderConfig:
@@ -29,6 +35,7 @@ class BootloaderConfig:
     path: str = "ignite"
     target: str = "x86_64-unknown-uefi"
     default_profile: str = "release"
+    binary: str = "ignite.efi"
 
 @dataclass
 class AnalysisPattern:
@@ -54,6 +61,88 @@ class AppConfig:
     path: str
     target: str = "x86_64-unknown-none"
 
+@dataclass
+class DriverConfig:
+    """Configuração de um driver (crate em drivers/)."""
+    name: str
+    path: str
+    target: str = "x86_64-unknown-none"
+
+@dataclass
+class DistConfig:
+    """
+    Destino dos artefatos em dist/qemu, relativo à raiz do disco.
+    Aceitam os campos {name}, {profile} e {version} (versão do Cargo.toml do componente).
+    """
+    bootloader: str = "EFI/BOOT/BOOTX64.EFI"
+    kernel: str = "boot/kernel"
+    service: str = "system/services/{name}/{name}.app"
+    app: str = "apps/system/{name}/{name}.app"
+    driver: str = "system/bin/{name}"
+    # Kernel na posição esperada por um estágio de boot BIOS
+    bios_kernel: str = "boot/forge"
+    # Artefatos de boot copiados em cada layout de 'dist --layout' (chaves acima)
+    layouts: dict[str, list[str]] = field(default_factory=lambda: {
+        "uefi": ["bootloader", "kernel"],
+        "bios": ["bios_kernel"],
+        "hybrid": ["bootloader", "kernel", "bios_kernel"],
+    })
+    # Chave ed25519 do OpenSSH de 'dist --sign-manifest' e a pública de 'verify --check-signature'
+    # (padrão: signing_key + ".pub"), relativas à raiz do projeto
+    signing_key: str = ""
+    verify_key: str = ""
+    # Identidade gravada no allowed_signers da verificação
+    signing_identity: str = "redstone"
+
+def package_version(crate_dir: Path) -> str:
+    """Versão declarada no Cargo.toml do crate ('0.0.0' se indisponível)."""
+    try:
+        return toml.load(crate_dir / "Cargo.toml").get("package", {}).get("version", "0.0.0")
+    except Exception:
+        return "0.0.0"
+
+@dataclass
+class QemuConfig:
+    """Padrões do 'anvil run'/'anvil test' (as flags da linha de comando prevalecem)."""
+    memory_mb: int = 512
+    cpus: int = 1
+    # Textos procurados na serial por 'run --boot-timeout': boot concluído e pânico do kernel
+    boot_marker: str = "Redstone OS booted"
+    panic_marker: str = "KERNEL PANIC"
+
+@dataclass
+class PreflightConfig:
+    """Verificações feitas antes de operações pesadas (build/dist/imagem)."""
+    # Espaço livre mínimo no disco de dist/; abaixo disso apenas avisa
+    min_free_mb: int = 1024
+    # Confere nightly e rust-src (exigidos por -Zbuild-std) antes do 'anvil build'
+    toolchain: bool = True
+
+@dataclass
+class BuildStdConfig:
+    """-Zbuild-std para alvos bare-metal (specs customizadas e os listados em targets)."""
+    enabled: bool = True
+    crates: list[str] = field(default_factory=lambda: ["core", "alloc"])
+    # memcpy/memset da compiler_builtins, já que não há libc
+    features: list[str] = field(default_factory=lambda: ["compiler-builtins-mem"])
+    # Alvos embutidos no rustc que também compilam a core junto
+    targets: list[str] = field(default_factory=lambda: ["x86_64-unknown-none"])
+
+# Arquiteturas do '--arch'. O anvil.toml descreve x86_64; nas outras, os triples (e
+# nomes de spec JSON) x86_64-* viram <arch>-* e o arquivo de boot UEFI muda
+ARCHES = ["x86_64", "aarch64"]
+DEFAULT_ARCH = "x86_64"
+EFI_BOOT_FILES = {"x86_64": "BOOTX64.EFI", "aarch64": "BOOTAA64.EFI"}
+
+def arch_target(target: str, arch: str) -> str:
+    """Target de x86_64 equivalente em arch (ex: x86_64-unknown-none -> aarch64-unknown-none)."""
+    prefix = f"{DEFAULT_ARCH}-"
+    if arch == DEFAULT_ARCH: return target
+    path = Path(target)
+    if target.endswith(".json"):
+        return (path.with_name(arch + "-" + path.name[len(prefix):]) if path.name.startswith(prefix) else path).as_posix()
+    return arch + "-" + target[len(prefix):] if target.startswith(prefix) else target
+
 @dataclass
 class ComponentsConfig:
     """Agrupamento de todos os componentes do sistema."""
@@ -61,6 +150,8 @@ class ComponentsConfig:
     bootloader: BootloaderConfig = field(default_factory=BootloaderConfig)
     services: list[ServiceConfig] = field(default_factory=list)
     apps: list[AppConfig] = field(default_factory=list)
+    # Fora do build padrão; entram com 'build --with-drivers' ou 'build drivers'
+    drivers: list[DriverConfig] = field(default_factory=list)
 
 @dataclass
 class Config:
@@ -68,9 +159,51 @@ class Config:
     project_name: str = "RedstoneOS"
     project_root: Path = field(default_factory=Path)
     components: ComponentsConfig = field(default_factory=ComponentsConfig)
-    qemu: Any = field(default=None)
+    qemu: QemuConfig = field(default_factory=QemuConfig)
     analysis: AnalysisConfig = field(default_factory=AnalysisConfig)
+    dist: DistConfig = field(default_factory=DistConfig)
+    preflight: PreflightConfig = field(default_factory=PreflightConfig)
+    build_std: BuildStdConfig = field(default_factory=BuildStdConfig)
+    # Arquitetura do guest ('--arch'); targets e [dist] já convertidos por for_arch
+    arch: str = DEFAULT_ARCH
+    # anvil.toml de onde a config veio (None sem arquivo, só com os padrões embutidos)
+    config_path: Optional[Path] = None
+    
+    def artifact_dest(self, kind: str, profile: str = "release", name: Optional[str] = None) -> str:
+        """
+        Resolve o destino de um artefato ('bootloader', 'kernel', 'bios_kernel', 'service',
+        'app' ou 'driver') a partir do template configurado em [dist].
+        """
+        comps = self.components
+        if kind in ("bootloader", "kernel", "bios_kernel"):
+            role = "bootloader" if kind == "bootloader" else "kernel"
+            crate = getattr(comps, role).path
+            name = name or role
+        else:
+            items = {"service": comps.services, "app": comps.apps, "driver": comps.drivers}[kind]
+            crate = next((c.path for c in items if c.name == name), name)
+        template = getattr(self.dist, kind)
+        try:
+            return template.format(name=name, profile=profile, version=package_version(self.project_root / crate))
+        except (KeyError, IndexError) as e:
+            raise ConfigError(f"Campo inválido em [dist].{kind}: {template}", f"Campos aceitos: {{name}}, {{profile}}, {{version}} ({e})")
     
+    def for_arch(self, arch: str) -> Config:
+        """Config com os targets, o [build_std] targets e o destino do bootloader de arch."""
+        if arch not in ARCHES: raise ConfigError(f"Arquitetura desconhecida: {arch}", f"Aceitas: {', '.join(ARCHES)}")
+        if arch == self.arch: return self
+        def retarget(c): return replace(c, target=arch_target(c.target, arch))
+        comps = self.components
+        components = replace(
+            comps, kernel=retarget(comps.kernel), bootloader=retarget(comps.bootloader),
+            services=[retarget(s) for s in comps.services], apps=[retarget(a) for a in comps.apps],
+            drivers=[retarget(d) for d in comps.drivers],
+        )
+        # Só o nome de boot removível padrão; um destino customizado em [dist] fica como está
+        dist = replace(self.dist, bootloader=self.dist.bootloader.replace(EFI_BOOT_FILES[self.arch], EFI_BOOT_FILES[arch]))
+        build_std = replace(self.build_std, targets=[arch_target(t, arch) for t in self.build_std.targets])
+        return replace(self, components=components, dist=dist, build_std=build_std, arch=arch)
+
     @classmethod
     def from_dict(cls, data: dict[str, Any], config_path: Path) -> Config:
         """Cria objeto Config a partir de dicionário (toml)."""
@@ -79,28 +212,53 @@ class Config:
         project_root = (config_path.parent / root_str).resolve()
         
         comp_data = data.get("components", {})
+        if not {"kernel", "bootloader", "services", "apps", "drivers"} <= set(comp_data):
+            comp_data = {**discovered_components(project_root), **comp_data}
         kernel = KernelConfig(**comp_data.get("kernel", {}))
         bootloader = BootloaderConfig(**comp_data.get("bootloader", {}))
         
         services = [ServiceConfig(**s) for s in comp_data.get("services", [])]
         apps = [AppConfig(**a) for a in comp_data.get("apps", [])]
+        drivers = [DriverConfig(**d) for d in comp_data.get("drivers", [])]
         
-        components = ComponentsConfig(kernel=kernel, bootloader=bootloader, services=services, apps=apps)
+        components = ComponentsConfig(kernel=kernel, bootloader=bootloader, services=services, apps=apps, drivers=drivers)
         
-        qemu_data = data.get("qemu", {}).copy()
+        qemu = QemuConfig(**data.get("qemu", {}))
         
         analysis_data = data.get("analysis", {}).copy()
         patterns = [AnalysisPattern(**p) for p in analysis_data.pop("patterns", [])]
         analysis = AnalysisConfig(**analysis_data, patterns=patterns)
+        dist_data = data.get("dist", {}).copy()
+        layouts = {**DistConfig().layouts, **dist_data.pop("layouts", {})}
+        dist = DistConfig(**dist_data, layouts=layouts)
+        preflight = PreflightConfig(**data.get("preflight", {}))
+        build_std = BuildStdConfig(**data.get("build_std", {}))
         
         return cls(
             project_name=project_data.get("name", "RedstoneOS"),
             project_root=project_root,
             components=components,
-            qemu=None, # QEMU config removida/hardcoded
+            qemu=qemu,
             analysis=analysis,
+            dist=dist,
+            preflight=preflight,
+            build_std=build_std,
+            config_path=config_path if config_path.exists() else None,
         )
 
+def discovered_components(project_root: Path) -> dict[str, Any]:
+    """Seção [components] equivalente aos crates achados por convenção de diretórios."""
+    from core.workspace import discover
+    comp_data: dict[str, Any] = {"services": [], "apps": [], "drivers": []}
+    for c in discover(project_root):
+        entry = {"path": c.path, "target": c.target}
+        if c.role in ("kernel", "bootloader"): comp_data[c.role] = entry
+        elif c.role == "service": comp_data["services"].append({"name": c.name, **entry})
+        elif c.role == "app": comp_data["apps"].append({"name": c.name, **entry})
+        elif c.role == "driver": comp_data["drivers"].append({"name": c.name, **entry})
+    # Sem nada encontrado, mantém os padrões dos dataclasses
+    return {k: v for k, v in comp_data.items() if v}
+
 def find_config_file() -> Path:
     """Procura pelo arquivo anvil.toml em locais padrão."""
     search_paths = [
@@ -118,17 +276,22 @@ def load_config(config_path: Optional[Path] = None) -> Config:
     logger = get_logger()
     
     if config_path is None:
-        config_path = find_config_file()
+        try:
+            config_path = find_config_file()
+        except ConfigError:
+            # Sem anvil.toml: padrões embutidos e componentes descobertos nos diretórios convencionais
+            anvil_dir = Path(__file__).resolve().parents[2]
+            logger.warning("anvil.toml não encontrado; usando os padrões embutidos")
+            return Config.from_dict({}, anvil_dir / "anvil.toml")
     
     logger.info(f"Carregando config de: {config_path.absolute()}")
     
-    if not config_path.exists():
-        logger.error(f"Arquivo não encontrado: {config_path}")
-        raise ConfigError(f"Arquivo de config não achado: {config_path}")
+    # Quem chama reporta o erro; aqui só monta a mensagem com o caminho
+    if not config_path.is_file():
+        raise ConfigError(f"Arquivo de config não achado: {config_path.absolute()}")
     
     try:
         data = toml.load(config_path)
-        return Config.from_dict(data, config_path)
+        return Config.from_dict(data, config_path.absolute())
     except Exception as e:
-        logger.error(f"Erro ao carregar config: {e}")
-        raise ConfigError(f"Falha ao carregar config: {config_path}", str(e))
+        raise ConfigError(f"Falha ao carregar config: {config_path.absolute()}", str(e))
@@ -0,0 +1,178 @@
+"""Anvil Core - Variáveis de ambiente do build para o shell do usuário.
+
+Usado por 'anvil env --export': eval "$(anvil env --export)". Também detecta
+as ferramentas e os targets do rustup necessários ('anvil env') e instala o
+que falta no toolchain ('anvil bootstrap').
+"""
+
+import platform
+import shutil
+import subprocess
+from pathlib import Path
+from dataclasses import asdict, dataclass, field
+from typing import Optional
+
+from core.config import DEFAULT_ARCH, arch_target
+from core.toolchain import installed_components
+
+SHELLS = ["bash", "fish", "powershell"]
+
+def _quote(value: str, shell: str) -> str:
+    if shell == "powershell": return "'" + value.replace("'", "''") + "'"
+    if shell == "fish": return "'" + value.replace("\\", "\\\\").replace("'", "\\'") + "'"
+    return "'" + value.replace("'", "'\\''") + "'"
+
+def export_lines(variables: dict[str, str], path_prepend: list[str], shell: str = "bash") -> list[str]:
+    """Comandos que definem as variáveis e acrescentam diretórios no início do PATH."""
+    lines = []
+    for key, value in variables.items():
+        if shell == "fish": lines.append(f"set -gx {key} {_quote(value, shell)}")
+        elif shell == "powershell": lines.append(f"$env:{key} = {_quote(value, shell)}")
+        else: lines.append(f"export {key}={_quote(value, shell)}")
+    for directory in path_prepend:
+        if shell == "fish": lines.append(f"set -gx PATH {_quote(directory, shell)} $PATH")
+        elif shell == "powershell":
+            lines.append(f"$env:PATH = {_quote(directory, shell)} + [IO.Path]::PathSeparator + $env:PATH")
+        else: lines.append(f"export PATH={_quote(directory, shell)}:\"$PATH\"")
+    return lines
+
+# Targets do rustup usados pelo build do SO (de x86_64; outras arquiteturas passam por arch_target)
+REQUIRED_TARGETS = ["x86_64-unknown-uefi", "x86_64-unknown-none"]
+# rust-src para o -Zbuild-std; llvm-tools-preview para strip/size/objcopy do sysroot
+REQUIRED_COMPONENTS = ["rust-src", "llvm-tools-preview"]
+
+# (nome exibido, executáveis aceitos, flag de versão, obrigatório, dica de instalação)
+TOOLS = [
+    ("rustc", ["rustc"], "--version", True, "curl --proto '=https' --tlsv1.2 -sSf https://sh.rustup.rs | sh"),
+    ("cargo", ["cargo"], "--version", True, "curl --proto '=https' --tlsv1.2 -sSf https://sh.rustup.rs | sh"),
+    ("qemu-system-x86_64", ["qemu-system-x86_64"], "--version", True, "sudo apt install qemu-system-x86"),
+    ("xorriso/oscdimg", ["xorriso", "oscdimg"], "-version", False,
+     "sudo apt install xorriso (Windows: oscdimg do Windows ADK); usado por 'anvil iso'"),
+    ("mkfs.vfat", ["mkfs.vfat"], "--version", False, "sudo apt install dosfstools; usado por 'anvil iso' e 'anvil usb'"),
+    ("sgdisk", ["sgdisk"], "--version", False, "sudo apt install gdisk; usado por 'anvil usb' (tabela GPT)"),
+]
+# Pacote Debian do QEMU de cada arquitetura do guest
+QEMU_PACKAGES = {"x86_64": "qemu-system-x86", "aarch64": "qemu-system-arm"}
+
+def required_targets(arch: str = DEFAULT_ARCH) -> list[str]:
+    return [arch_target(t, arch) for t in REQUIRED_TARGETS]
+
+def arch_tools(arch: str = DEFAULT_ARCH) -> list[tuple]:
+    """TOOLS com o QEMU da arquitetura do guest no lugar do qemu-system-x86_64."""
+    qemu = f"qemu-system-{arch}"
+    tools = []
+    for name, executables, flag, required, hint in TOOLS:
+        if name == "qemu-system-x86_64": name, executables, hint = qemu, [qemu], f"sudo apt install {QEMU_PACKAGES[arch]}"
+        tools.append((name, executables, flag, required, hint))
+    return tools
+
+@dataclass
+class ToolStatus:
+    """Resultado da detecção de uma ferramenta."""
+    name: str
+    required: bool
+    hint: str
+    path: Optional[str] = None
+    version: Optional[str] = None
+
+    @property
+    def found(self) -> bool:
+        return self.path is not None
+
+def _version(path: str, flag: str) -> Optional[str]:
+    try:
+        p = subprocess.run([path, flag], capture_output=True, text=True, timeout=10)
+    except (OSError, subprocess.TimeoutExpired):
+        return None
+    return next((l.strip() for l in (p.stdout + p.stderr).splitlines() if l.strip()), None)
+
+def check_tools(arch: str = DEFAULT_ARCH) -> list[ToolStatus]:
+    """Detecta as ferramentas de TOOLS (com o QEMU de arch) e suas versões."""
+    statuses = []
+    for name, executables, flag, required, hint in arch_tools(arch):
+        status = ToolStatus(name, required, hint)
+        for exe in executables:
+            path = shutil.which(exe)
+            if path:
+                # oscdimg não tem flag de versão; basta existir
+                status.path, status.version = path, _version(path, flag) if exe != "oscdimg" else exe
+                break
+        statuses.append(status)
+    return statuses
+
+def installed_targets(cwd: Optional[Path] = None) -> Optional[list[str]]:
+    """Saída de 'rustup target list --installed' (do toolchain ativo em cwd); None sem rustup."""
+    try:
+        out = subprocess.run(["rustup", "target", "list", "--installed"], capture_output=True, text=True,
+                             check=True, timeout=30, cwd=cwd).stdout
+    except (OSError, subprocess.SubprocessError):
+        return None
+    return [l.strip() for l in out.splitlines() if l.strip()]
+
+@dataclass
+class EnvReport:
+    """Relatório de 'anvil env' (também em JSON com --json)."""
+    tools: list[ToolStatus] = field(default_factory=list)
+    # None: rustup ausente, então nenhum target pôde ser conferido
+    installed_targets: Optional[list[str]] = None
+    arch: str = DEFAULT_ARCH
+
+    @classmethod
+    def collect(cls, arch: str = DEFAULT_ARCH) -> "EnvReport":
+        return cls(check_tools(arch), installed_targets(), arch)
+
+    @property
+    def required_targets(self) -> list[str]:
+        return required_targets(self.arch)
+
+    @property
+    def missing_targets(self) -> list[str]:
+        return [t for t in self.required_targets if t not in (self.installed_targets or [])]
+
+    @property
+    def ok(self) -> bool:
+        return all(t.found for t in self.tools if t.required) and not self.missing_targets
+
+    def to_dict(self) -> dict:
+        return {
+            "ok": self.ok,
+            "tools": [{**asdict(t), "found": t.found} for t in self.tools],
+            "arch": self.arch,
+            "targets": {"required": self.required_targets, "installed": self.installed_targets or [],
+                        "missing": self.missing_targets, "rustup": self.installed_targets is not None},
+        }
+
+# Ferramentas fora do rustup: o bootstrap só mostra como instalar, por plataforma
+SYSTEM_PACKAGES = {
+    "Linux": ["Debian/Ubuntu: sudo apt install qemu-system-x86 ovmf xorriso dosfstools gdisk",
+              "Fedora: sudo dnf install qemu-system-x86 edk2-ovmf xorriso dosfstools gdisk",
+              "Arch: sudo pacman -S qemu-full edk2-ovmf libisoburn dosfstools gptfdisk"],
+    "Darwin": ["brew install qemu xorriso dosfstools"],
+    "Windows": ["winget install SoftwareFreedomConservancy.QEMU",
+                "oscdimg: Windows ADK (Deployment Tools)"],
+}
+
+@dataclass
+class BootstrapStep:
+    """Target ou componente do rustup; present diz se já está instalado."""
+    kind: str
+    name: str
+    present: bool
+
+    @property
+    def command(self) -> list[str]:
+        return ["rustup", self.kind, "add", self.name]
+
+def _component_present(name: str, installed: list[str]) -> bool:
+    # llvm-tools-preview aparece como llvm-tools nos toolchains recentes
+    return name in installed or name.removesuffix("-preview") in installed
+
+def bootstrap_plan(cwd: Path, arch: str = DEFAULT_ARCH) -> Optional[list[BootstrapStep]]:
+    """Targets (da arquitetura) e componentes necessários no toolchain ativo em cwd; None sem rustup."""
+    targets, components = installed_targets(cwd), installed_components(cwd)
+    if targets is None or components is None: return None
+    return ([BootstrapStep("target", t, t in targets) for t in required_targets(arch)] +
+            [BootstrapStep("component", c, _component_present(c, components)) for c in REQUIRED_COMPONENTS])
+
+def system_package_hints() -> list[str]:
+    return SYSTEM_PACKAGES.get(platform.system(), SYSTEM_PACKAGES["Linux"])
@@ -0,0 +1,53 @@
+"""Anvil Core - Fluxo de eventos de progresso para integração com IDEs.
+
+Ativado por '--event-stream [FD]'. Cada evento é uma linha JSON escrita no
+descritor informado (ou stderr), independente da saída normal do terminal.
+Com '--json' os eventos vão para o stdout e substituem a saída colorida, que
+fica reduzida aos erros no stderr.
+
+Esquema (todos os eventos têm "event" e "time", em segundos Unix):
+    {"event": "build-started", "command": "build"}
+    {"event": "package-started", "package": "Kernel", "path": "/.../forge", "profile": "release"}
+    {"event": "package-finished", "package": "Kernel", "ok": true, "duration_ms": 1234}
+    {"event": "copy", "source": "/.../forge", "dest": "boot/kernel"}
+    {"event": "env", "ok": false, "tools": [...], "targets": {...}, "variables": {...}, "path_prepend": [...]}
+    {"event": "done", "command": "build", "ok": true}
+    {"event": "error", "message": "..."}
+"""
+
+import json
+import os
+import sys
+import time
+from typing import Optional, TextIO
+
+class EventStream:
+    """Emissor de eventos NDJSON; inativo até 'open' ser chamado."""
+
+    def __init__(self):
+        self._out: Optional[TextIO] = None
+
+    def open(self, target: str) -> None:
+        """Abre o destino: 'stderr', 'stdout' ou o número de um descritor de arquivo já aberto."""
+        if target == "stderr":
+            self._out = sys.stderr
+        elif target == "stdout":
+            self._out = sys.stdout
+        else:
+            self._out = os.fdopen(int(target), "w", buffering=1, encoding="utf-8")
+
+    @property
+    def enabled(self) -> bool:
+        return self._out is not None
+
+    def emit(self, event: str, **fields) -> None:
+        if not self._out: return
+        record = {"event": event, "time": round(time.time(), 3), **fields}
+        self._out.write(json.dumps(record, default=str) + "\n")
+        self._out.flush()
+
+# Instância global
+_events = EventStream()
+
+def get_events() -> EventStream:
+    return _events
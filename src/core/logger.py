@@ -4,6 +4,7 @@ Este módulo fornece uma classe Logger básica para imprimir mensagens coloridas
 no terminal, substituindo a antiga dependência 'rich' por sequências de escape ANSI.
 """
 
+import os
 import sys
 from datetime import datetime
 from enum import Enum
@@ -16,6 +17,16 @@ class LogLevel(Enum):
     WARNING = "WARN"
     ERROR = "ERROR"
 
+# Mensagens abaixo do nível configurado são omitidas (SUCCESS conta como INFO)
+SEVERITY = {LogLevel.DEBUG: 0, LogLevel.INFO: 1, LogLevel.SUCCESS: 1, LogLevel.WARNING: 2, LogLevel.ERROR: 3}
+
+# Valores aceitos na variável ANVIL_LOG (as flags -q/-v prevalecem)
+LOG_ENV = "ANVIL_LOG"
+ENV_LEVELS = {
+    "debug": LogLevel.DEBUG, "verbose": LogLevel.DEBUG, "info": LogLevel.INFO,
+    "warn": LogLevel.WARNING, "warning": LogLevel.WARNING, "error": LogLevel.ERROR, "quiet": LogLevel.ERROR,
+}
+
 class Colors:
     """Definições de códigos de escape ANSI para cores."""
     RESET = "\033[0m"
@@ -29,37 +40,83 @@ class Colors:
     GREY = "\033[90m"
     BOLD = "\033[1m"
 
+# Modos de '--color'; NO_COLOR (não vazio) desliga as cores no modo auto
+COLOR_MODES = ["auto", "always", "never"]
+_ANSI = {name: value for name, value in vars(Colors).items() if name.isupper()}
+
+def set_color_mode(mode: str = "auto", stream=None) -> bool:
+    """Liga ou desliga as cores de Colors; em auto, só com o destino (padrão: stdout) num terminal."""
+    if mode == "always": enabled = True
+    elif mode == "never": enabled = False
+    else: enabled = not os.environ.get("NO_COLOR") and (stream or sys.stdout).isatty()
+    for name, value in _ANSI.items(): setattr(Colors, name, value if enabled else "")
+    return enabled
+
+def colors_enabled() -> bool:
+    return bool(Colors.RESET)
+
 class Logger:
     """
     Logger simples que escreve no stdout com cores.
-    Suporta níveis de log como info, success, warning, error e debug.
+    Suporta níveis de log como info, success, warning, error e debug; 'level' define
+    o mínimo exibido (ERROR com --quiet, DEBUG com --verbose).
     """
     
     def __init__(self, name="anvil", verbose=False):
         self.name = name
-        self.verbose = verbose
+        self.level = LogLevel.DEBUG if verbose else LogLevel.INFO
+        # Destino das mensagens (None = stdout); stderr quando o stdout é saída de dados
+        self.stream = None
+
+    @property
+    def verbose(self) -> bool:
+        """Comandos executados e operações de arquivo (nível DEBUG)."""
+        return self.level == LogLevel.DEBUG
+
+    @verbose.setter
+    def verbose(self, value: bool):
+        self.level = LogLevel.DEBUG if value else LogLevel.INFO
+
+    @property
+    def quiet(self) -> bool:
+        """Só erros (nível ERROR)."""
+        return SEVERITY[self.level] >= SEVERITY[LogLevel.ERROR]
+
+    def configure(self, verbose: bool = False, quiet: bool = False) -> None:
+        """Nível a partir das flags globais; sem nenhuma delas, de ANVIL_LOG (padrão: INFO)."""
+        if quiet: self.level = LogLevel.ERROR
+        elif verbose: self.level = LogLevel.DEBUG
+        else:
+            value = os.environ.get(LOG_ENV, "").strip().lower()
+            self.level = ENV_LEVELS.get(value, LogLevel.INFO)
+            if value and value not in ENV_LEVELS:
+                self.warning(f"{LOG_ENV}={value} desconhecido; use {', '.join(ENV_LEVELS)}")
+
+    def enabled(self, level: LogLevel) -> bool:
+        return SEVERITY[level] >= SEVERITY[self.level]
 
     def _print(self, level_color, box_char, message):
         """Método interno para formatar e imprimir a mensagem de log."""
         timestamp = datetime.now().strftime("%H:%M:%S")
         # Formato: HH:MM:SS [X] Mensagem
-        print(f"{Colors.GREY}{timestamp}{Colors.RESET} {level_color}{box_char}{Colors.RESET} {message}")
+        print(f"{Colors.GREY}{timestamp}{Colors.RESET} {level_color}{box_char}{Colors.RESET} {message}", file=self.stream)
 
     def header(self, title):
         """Imprime um cabeçalho de seção."""
-        print(f"\n{Colors.BOLD}{Colors.CYAN}=== {title} ==={Colors.RESET}")
+        if not self.enabled(LogLevel.INFO): return
+        print(f"\n{Colors.BOLD}{Colors.CYAN}=== {title} ==={Colors.RESET}", file=self.stream)
 
     def info(self, message):
         """Log de informação geral (Azul 'i')."""
-        self._print(Colors.BLUE, "i", message)
+        if self.enabled(LogLevel.INFO): self._print(Colors.BLUE, "i", message)
 
     def success(self, message):
         """Log de sucesso (Verde '✓')."""
-        self._print(Colors.GREEN, "✓", message)
+        if self.enabled(LogLevel.SUCCESS): self._print(Colors.GREEN, "✓", message)
 
     def warning(self, message):
         """Log de aviso (Amarelo '!')."""
-        self._print(Colors.YELLOW, "!", message)
+        if self.enabled(LogLevel.WARNING): self._print(Colors.YELLOW, "!", message)
 
     def error(self, message):
         """Log de erro (Vermelho 'x')."""
@@ -67,16 +124,17 @@ class Logger:
 
     def debug(self, message):
         """Log de debug (Cinza '?'), visível apenas se verbose=True."""
-        if self.verbose:
+        if self.enabled(LogLevel.DEBUG):
             self._print(Colors.GREY, "?", message)
 
     def step(self, message):
         """Log de passo de execução (seta cinza)."""
-        print(f"   {Colors.GREY}→ {message}{Colors.RESET}")
+        if not self.enabled(LogLevel.INFO): return
+        print(f"   {Colors.GREY}→ {message}{Colors.RESET}", file=self.stream)
 
     def raw(self, message):
-        """Imprime a mensagem exatamente como recebida, sem formatação extra."""
-        print(message, flush=True)
+        """Imprime a mensagem exatamente como recebida, sem formatação extra (também com --quiet)."""
+        print(message, flush=True, file=self.stream)
 
 # Instância global do logger
 _logger = None
@@ -1,9 +1,49 @@
 """Anvil Core - Gerenciamento de caminhos e utilitários de sistema de arquivos."""
 
 from __future__ import annotations
+import shutil
 from pathlib import Path
 from typing import Optional
 
+from core.errors import BuildError
+from core.config import ComponentsConfig
+from core.toolchain import target_dir_name
+
+def dir_size(path: Path) -> int:
+    """Tamanho total em bytes de um arquivo ou diretório (0 se não existir)."""
+    if path.is_file(): return path.stat().st_size
+    if not path.exists(): return 0
+    total = 0
+    for p in path.rglob("*"):
+        try:
+            if p.is_file() and not p.is_symlink(): total += p.stat().st_size
+        except OSError: pass
+    return total
+
+def format_size(size: int) -> str:
+    """Formata bytes em unidade legível (ex: 12.3 MiB)."""
+    value = float(size)
+    for unit in ("B", "KiB", "MiB", "GiB"):
+        if value < 1024 or unit == "GiB": break
+        value /= 1024
+    return f"{value:.0f} {unit}" if unit == "B" else f"{value:.1f} {unit}"
+
+def check_free_space(path: Path, required: int, min_free_mb: int, log) -> None:
+    """
+    Preflight de espaço em disco no sistema de arquivos de 'path': erro se não couber
+    o estimado, aviso se o espaço livre ficar abaixo do mínimo configurado.
+    """
+    probe = path
+    while not probe.exists() and probe != probe.parent: probe = probe.parent
+    free = shutil.disk_usage(probe).free
+    log.step(f"Espaço livre em {probe}: {format_size(free)} (estimado: {format_size(required)})")
+    if free < required:
+        raise BuildError(f"Espaço insuficiente em {probe}: {format_size(free)} livres, "
+                         f"{format_size(required)} necessários", "preflight")
+    if free - required < min_free_mb * 1024 * 1024:
+        log.warning(f"Pouco espaço em disco: restarão {format_size(free - required)} "
+                    f"(mínimo configurado: {min_free_mb} MiB)")
+
 class Paths:
     """
     Gerenciador centralizado de caminhos do projeto.
@@ -14,8 +54,10 @@ class Paths:
     - Localizar artefatos de build.
     """
     
-    def __init__(self, project_root: Path):
+    def __init__(self, project_root: Path, components: Optional[ComponentsConfig] = None):
         self._root = project_root.resolve()
+        # Caminhos, targets e binários do kernel/bootloader vêm do anvil.toml
+        self._components = components or ComponentsConfig()
         
     @classmethod
     def from_anvil_dir(cls) -> Paths:
@@ -32,10 +74,10 @@ class Paths:
     def anvil(self) -> Path: return self._root / "anvil"
     
     @property
-    def forge(self) -> Path: return self._root / "forge"  # Kernel
+    def forge(self) -> Path: return self._root / self._components.kernel.path  # Kernel
     
     @property
-    def ignite(self) -> Path: return self._root / "ignite"  # Bootloader
+    def ignite(self) -> Path: return self._root / self._components.bootloader.path  # Bootloader
     
     @property
     def services(self) -> Path: return self._root / "services"
@@ -49,31 +91,69 @@ class Paths:
     @property
     def sdk(self) -> Path: return self._root / "sdk"
     
+    @property
+    def recipes(self) -> Path: return self._root / "recipes"  # Receitas de build (<nome>.toml)
+    
     @property
     def dist(self) -> Path: return self._root / "dist"  # Saída build
     
+    @property
+    def cache(self) -> Path: return self._root / "cache"  # Downloads e caches do build ('clean --all')
+
+    @property
+    def artifact_hashes(self) -> Path: return self.cache / "artifact-hashes.toml"  # 'dist --incremental'
+    
     @property
     def dist_qemu(self) -> Path: return self.dist / "qemu"  # Raiz FS do QEMU
     
     @property
     def dist_img(self) -> Path: return self.dist / "img"  # Imagens VDI/Raw
     
+    @property
+    def dist_debug(self) -> Path: return self.dist / "debug"  # Símbolos separados (fora da imagem)
+
+    @property
+    def dist_manifest(self) -> Path: return self.dist / "manifest.json"  # Artefatos do último 'anvil dist'
+    
     @property
     def assets(self) -> Path: return self.anvil / "src" / "assets"
     
     @property
     def initramfs(self) -> Path: return self.assets / "initramfs"
     
+    @property
+    def templates(self) -> Path: return self.anvil / "templates"  # Modelos de 'anvil template new'
+    
+    # Estado local do Anvil (não versionado)
+    @property
+    def anvil_state(self) -> Path: return self.anvil / ".anvil"
+    
+    @property
+    def selection_file(self) -> Path: return self.anvil_state / "selection.toml"
+    
+    @property
+    def anvil_log_dir(self) -> Path: return self.anvil_state / "logs"
+
+    @property
+    def fast_check_file(self) -> Path: return self.anvil_state / "fast-check.json"
+    
     # Artefatos compilados
+    def target_dir(self, crate_dir: Path, target: str, profile: str = "release") -> Path:
+        """target/<triple ou nome da spec JSON>/<perfil> do crate."""
+        return crate_dir / "target" / target_dir_name(target) / profile
+
     def kernel_binary(self, profile: str = "release") -> Path:
-        return self.forge / "target" / "x86_64-redstone" / profile / "forge"
+        kernel = self._components.kernel
+        return self.target_dir(self.forge, kernel.target, profile) / kernel.binary
         
     def bootloader_binary(self, profile: str = "release") -> Path:
-        return self.ignite / "target" / "x86_64-unknown-uefi" / profile / "ignite.efi"
+        bootloader = self._components.bootloader
+        return self.target_dir(self.ignite, bootloader.target, profile) / bootloader.binary
         
-    def service_binary(self, name: str, profile: str = "release", base_path: Optional[Path] = None) -> Path:
+    def service_binary(self, name: str, profile: str = "release", base_path: Optional[Path] = None,
+                       target: str = "x86_64-unknown-none") -> Path:
         base = base_path or (self.services / name)
-        return base / "target" / "x86_64-unknown-none" / profile / name
+        return self.target_dir(base, target, profile) / name
 
     # UEFI / BIOS
     @property
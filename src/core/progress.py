@@ -0,0 +1,66 @@
+"""Anvil Core - Barra de progresso no terminal.
+
+Uma linha redesenhada com '\\r', em arquivos ou bytes. Só aparece num terminal
+e no nível normal de log: com --quiet fica escondida, e com --verbose o log
+por arquivo (debug) substitui a barra, já que as duas se misturariam.
+"""
+
+import sys
+import time
+from typing import Optional
+
+from core.logger import Colors, Logger
+from core.paths import format_size
+
+BAR_WIDTH = 30
+REDRAW_INTERVAL = 0.1  # segundos
+
+class Progress:
+    """Contador com total conhecido; invisível, só soma (ex: fora de um terminal)."""
+
+    def __init__(self, label: str, total: int, unit: str = "files", visible: bool = True, stream=None):
+        self.label = label
+        self.total = max(total, 0)
+        self.unit = unit
+        self.visible = visible
+        self.stream = stream or sys.stdout
+        self.done = 0
+        self._drawn = 0.0
+
+    def __enter__(self) -> "Progress":
+        self._draw()
+        return self
+
+    def __exit__(self, *exc) -> None:
+        self.finish()
+
+    def _amount(self, value: int) -> str:
+        return format_size(value) if self.unit == "bytes" else str(value)
+
+    def _draw(self) -> None:
+        if not self.visible: return
+        ratio = min(self.done / self.total, 1.0) if self.total else 1.0
+        filled = int(ratio * BAR_WIDTH)
+        bar = "#" * filled + "-" * (BAR_WIDTH - filled)
+        total = f"{self._amount(self.done)}/{self._amount(self.total)}"
+        self.stream.write(f"\r   {Colors.GREY}{self.label}{Colors.RESET} [{bar}] {total} {ratio:4.0%}\033[K")
+        self.stream.flush()
+        self._drawn = time.monotonic()
+
+    def advance(self, amount: int = 1) -> None:
+        self.done += amount
+        # Redesenha no máximo a cada REDRAW_INTERVAL (cópias em blocos pequenos)
+        if self.done >= self.total or time.monotonic() - self._drawn >= REDRAW_INTERVAL: self._draw()
+
+    def finish(self) -> None:
+        if not self.visible: return
+        self._draw()
+        self.stream.write("\n")
+        self.stream.flush()
+        self.visible = False
+
+def progress(log: Logger, label: str, total: int, unit: str = "files") -> Progress:
+    """Barra configurada pelo logger: escondida com --quiet, com --verbose ou fora de um terminal."""
+    stream = log.stream or sys.stdout
+    visible = not log.quiet and not log.verbose and stream.isatty()
+    return Progress(label, total, unit, visible, stream)
@@ -0,0 +1,278 @@
+"""Anvil Core - Receitas de build (recipes/<nome>.toml na raiz do projeto).
+
+Uma receita escolhe quais componentes entram no sistema e com qual perfil:
+
+    description = "Sistema mínimo de boot"
+    profile = "release"
+    packages = ["bootloader", "kernel", "init"]
+
+    [targets]
+    init = "x86_64-unknown-none"
+
+Os pacotes são nomes de componentes do anvil.toml ('kernel', 'bootloader' ou o
+nome de um serviço/app/driver); [targets] troca o target de um pacote.
+
+A tabela opcional drivers = { include = [...], exclude = [...] } escolhe os
+drivers de drivers/ compilados e copiados: include (padrão: todos) menos
+exclude. Sem ela valem só os drivers listados em packages.
+
+Com programs = ["supervisor", "init", ...] a receita escolhe os serviços que
+vão dentro do initfs (system/core), no lugar dos marcados com 'core = true' no
+anvil.toml; o Supervisor entra sempre.
+
+Com extends = "minimal" a receita parte de outra: os pacotes se somam (sem
+repetição), [targets] se mescla e os demais campos da filha (inclusive a
+tabela drivers, inteira) prevalecem.
+"""
+
+from __future__ import annotations
+from dataclasses import dataclass, field, replace
+from pathlib import Path
+from typing import Optional
+import toml
+
+from core.config import Config, arch_target
+from core.errors import ConfigError
+from core.toolchain import builtin_targets, is_target_spec
+
+REQUIRED_FIELDS = ["description", "packages"]
+# Limite da cadeia de extends
+MAX_EXTENDS_DEPTH = 8
+
+# Receitas compiladas no Anvil: carregadas quando recipes/<nome>.toml não existe,
+# para que o fluxo sem configuração continue funcionando
+BUILTIN_RECIPE_DATA = {
+    "minimal": {
+        "description": "Bootloader, kernel e Supervisor, sem features extras",
+        "packages": ["bootloader", "kernel", "supervisor"],
+        "profile": "release",
+    },
+}
+
+def _read(recipes_dir: Path, name: str) -> tuple[dict, Optional[Path]]:
+    """Dados de recipes/<name>.toml; sem o arquivo, a receita embutida (caminho None)."""
+    path = recipes_dir / f"{name}.toml"
+    if not path.is_file() and name in BUILTIN_RECIPE_DATA: return dict(BUILTIN_RECIPE_DATA[name]), None
+    if not path.is_file(): raise ConfigError(f"Receita '{name}' não encontrada", f"Esperado: {path}")
+    try:
+        return toml.load(path), path
+    except Exception as e:
+        raise ConfigError(f"Falha ao ler a receita '{name}': {path}", str(e))
+
+def _resolve(recipes_dir: Path, name: str, chain: list[str]) -> dict:
+    """Dados da receita com a cadeia de extends já mesclada."""
+    chain = [*chain, name]
+    if name in chain[:-1]: raise ConfigError(f"Ciclo em extends: {' -> '.join(chain)}")
+    if len(chain) > MAX_EXTENDS_DEPTH:
+        raise ConfigError(f"Cadeia de extends passa de {MAX_EXTENDS_DEPTH} níveis: {' -> '.join(chain)}")
+    data, path = _read(recipes_dir, name)
+    # Pacotes de uma embutida (inclusive herdados): sem o componente no workspace, só ficam de fora
+    if path is None: data["optional_packages"] = list(data.get("packages", []))
+    else: data.pop("optional_packages", None)
+    parent_name = data.pop("extends", None)
+    if parent_name is None: return data
+    if not isinstance(parent_name, str): raise ConfigError(f"Receita '{name}': 'extends' deve ser o nome de uma receita")
+    parent = _resolve(recipes_dir, parent_name, chain)
+    merged = {**parent, **data}
+    if "packages" in parent or "packages" in data:
+        merged["packages"] = list(dict.fromkeys([*parent.get("packages", []), *data.get("packages", [])]))
+    merged["targets"] = {**parent.get("targets", {}), **data.get("targets", {})}
+    # Um pacote que a filha lista por conta própria volta a ser obrigatório
+    own = data.get("packages", [])
+    merged["optional_packages"] = list(dict.fromkeys(
+        [*(p for p in parent.get("optional_packages", []) if p not in own), *data.get("optional_packages", [])]))
+    # O nome nunca é herdado
+    if "name" not in data: merged.pop("name", None)
+    return merged
+
+@dataclass
+class RecipeSpec:
+    """Receita já validada."""
+    name: str
+    description: str
+    packages: list[str]
+    profile: str = "release"
+    targets: dict[str, str] = field(default_factory=dict)
+    extends: Optional[str] = None
+    # drivers = { include, exclude }; include None = todos os drivers de drivers/
+    drivers_include: Optional[list[str]] = None
+    drivers_exclude: list[str] = field(default_factory=list)
+    has_driver_lists: bool = False
+    # Serviços do initfs; None = os com 'core = true' no anvil.toml
+    programs: Optional[list[str]] = None
+    # Compilada no Anvil (BUILTIN_RECIPE_DATA), sem arquivo em recipes/
+    builtin: bool = False
+    # Vindos de uma embutida: ignorados se o workspace não tiver o componente
+    optional_packages: list[str] = field(default_factory=list)
+
+    @classmethod
+    def from_dict(cls, name: str, data: dict, path: Optional[Path]) -> RecipeSpec:
+        missing = [f for f in REQUIRED_FIELDS if f not in data]
+        if missing: raise ConfigError(f"Receita '{name}' sem o(s) campo(s) obrigatório(s): {', '.join(missing)}", str(path))
+        packages, targets = data["packages"], data.get("targets", {})
+        if not isinstance(packages, list) or not all(isinstance(p, str) for p in packages):
+            raise ConfigError(f"Receita '{name}': 'packages' deve ser uma lista de nomes", str(path))
+        if not isinstance(targets, dict):
+            raise ConfigError(f"Receita '{name}': [targets] deve mapear pacote = \"triple\"", str(path))
+        include, exclude = _driver_lists(name, data.get("drivers"), path)
+        programs = data.get("programs")
+        if programs is not None and (not isinstance(programs, list) or not all(isinstance(p, str) for p in programs)):
+            raise ConfigError(f"Receita '{name}': 'programs' deve ser uma lista de nomes", str(path))
+        return cls(
+            name=data.get("name", name),
+            description=str(data["description"]),
+            packages=list(packages),
+            profile=str(data.get("profile", "release")),
+            targets={str(k): str(v) for k, v in targets.items()},
+            extends=data.get("extends"),
+            drivers_include=include,
+            drivers_exclude=exclude,
+            has_driver_lists="drivers" in data,
+            programs=None if programs is None else list(programs),
+            builtin=path is None,
+            optional_packages=[str(p) for p in data.get("optional_packages", [])],
+        )
+
+    @classmethod
+    def load(cls, recipes_dir: Path, name: str) -> RecipeSpec:
+        """Carrega recipes/<name>.toml (ou a embutida de mesmo nome), resolvendo a cadeia de extends."""
+        data, path = _read(recipes_dir, name)
+        merged = _resolve(recipes_dir, name, [])
+        if "extends" in data: merged["extends"] = data["extends"]
+        return cls.from_dict(name, merged, path)
+
+    def workspace_packages(self, config: Config) -> list[str]:
+        """Pacotes da receita, sem os opcionais (de uma embutida) que o workspace não tem."""
+        comps = config.components
+        available = {"kernel", "bootloader", *(c.name for c in [*comps.services, *comps.apps, *comps.drivers])}
+        return [p for p in self.packages if p in available or p not in self.optional_packages]
+
+    def selected_drivers(self, available: list[str]) -> list[str]:
+        """Drivers da receita entre os disponíveis: a tabela drivers ou, sem ela, os de packages."""
+        if not self.has_driver_lists: return [d for d in available if d in self.packages]
+        base = available if self.drivers_include is None else self.drivers_include
+        chosen = {*base, *(p for p in self.packages if p in available)} - set(self.drivers_exclude)
+        return [d for d in available if d in chosen]
+
+    def apply(self, config: Config) -> Config:
+        """
+        Config restrita aos pacotes da receita, com os targets de [targets] aplicados.
+        Falha antes de qualquer build se algum pacote não existir no workspace.
+        """
+        comps = config.components
+        available = ["kernel", "bootloader", *(c.name for c in [*comps.services, *comps.apps, *comps.drivers])]
+        # A embutida não conhece o workspace: um pacote dela ausente (ex: sem services/supervisor) só fica de fora
+        packages = self.workspace_packages(config)
+        unknown = [p for p in [*packages, *self.targets] if p not in available]
+        if unknown:
+            raise ConfigError(f"Receita '{self.name}': pacote(s) inexistente(s) no workspace: {', '.join(dict.fromkeys(unknown))}",
+                              f"Disponíveis: {', '.join(available)}")
+        driver_names = [d.name for d in comps.drivers]
+        unknown = [d for d in [*(self.drivers_include or []), *self.drivers_exclude] if d not in driver_names]
+        if unknown:
+            raise ConfigError(f"Receita '{self.name}': driver(s) inexistente(s) em drivers/: {', '.join(dict.fromkeys(unknown))}",
+                              f"Disponíveis: {', '.join(driver_names) or '(nenhum)'}")
+        drivers = self.selected_drivers(driver_names)
+        # Os triples da receita, como os do anvil.toml, são de x86_64 e seguem o '--arch'
+        targets = {name: arch_target(t, config.arch) for name, t in self.targets.items()}
+        def retarget(c): return replace(c, target=targets[c.name]) if c.name in targets else c
+        # programs substitui o 'core = true' do anvil.toml na escolha do initfs
+        def initfs(s): return s if self.programs is None else replace(s, core=s.name in self.programs)
+        kernel, bootloader = comps.kernel, comps.bootloader
+        if "kernel" in targets: kernel = replace(kernel, target=targets["kernel"])
+        if "bootloader" in targets: bootloader = replace(bootloader, target=targets["bootloader"])
+        components = replace(
+            comps, kernel=kernel, bootloader=bootloader,
+            services=[initfs(retarget(s)) for s in comps.services if s.name in packages],
+            apps=[retarget(a) for a in comps.apps if a.name in packages],
+            drivers=[retarget(d) for d in comps.drivers if d.name in drivers],
+        )
+        return replace(config, components=components)
+
+    def missing_programs(self, config: Config) -> list[str]:
+        """Nomes de programs que não são serviços da config já restrita por apply (ficam fora do initfs)."""
+        services = {s.name for s in config.components.services} | {"supervisor"}
+        return [p for p in dict.fromkeys(self.programs or []) if p not in services]
+
+def _driver_lists(name: str, table, path: Path) -> tuple[Optional[list[str]], list[str]]:
+    """(include, exclude) da tabela drivers; include None quando omitido."""
+    if table is None: return None, []
+    if not isinstance(table, dict) or set(table) - {"include", "exclude"}:
+        raise ConfigError(f"Receita '{name}': 'drivers' deve ser {{ include = [...], exclude = [...] }}", str(path))
+    lists = []
+    for key in ("include", "exclude"):
+        value = table.get(key)
+        if value is not None and (not isinstance(value, list) or not all(isinstance(d, str) for d in value)):
+            raise ConfigError(f"Receita '{name}': 'drivers.{key}' deve ser uma lista de nomes", str(path))
+        lists.append(None if value is None else list(value))
+    return lists[0], lists[1] or []
+
+def recipe_names(recipes_dir: Path) -> list[str]:
+    """Nomes de recipes/*.toml mais os das embutidas sem arquivo (funciona sem o diretório)."""
+    names = [p.stem for p in sorted(recipes_dir.glob("*.toml"))] if recipes_dir.is_dir() else []
+    return [*names, *(n for n in BUILTIN_RECIPE_DATA if n not in names)]
+
+def list_recipes(recipes_dir: Path) -> tuple[list[RecipeSpec], list[ConfigError]]:
+    """
+    Receitas de recipes/*.toml mais as embutidas sem arquivo de mesmo nome, em ordem
+    alfabética, e os erros das que não puderam ser lidas.
+    """
+    recipes, errors = [], []
+    for name in recipe_names(recipes_dir):
+        try: recipes.append(RecipeSpec.load(recipes_dir, name))
+        except ConfigError as e: errors.append(e)
+    recipes.sort(key=lambda r: r.name)
+    return recipes, errors
+
+def _packages(config: Config) -> dict[str, str]:
+    """Componente do anvil.toml -> diretório do crate (relativo à raiz)."""
+    comps = config.components
+    return {"kernel": comps.kernel.path, "bootloader": comps.bootloader.path,
+            **{c.name: c.path for c in [*comps.services, *comps.apps, *comps.drivers]}}
+
+def _configured_targets(config: Config) -> set[str]:
+    comps = config.components
+    return {comps.kernel.target, comps.bootloader.target, *(c.target for c in [*comps.services, *comps.apps, *comps.drivers])}
+
+def validate_recipe(recipes_dir: Path, name: str, config: Config, project_root: Path) -> list[str]:
+    """
+    Todos os problemas de uma receita ('recipe validate'), sem parar no primeiro: leitura
+    e cadeia de extends, pacotes sem componente ou sem Cargo.toml, drivers fora de drivers/,
+    programs que não são serviços da receita e targets desconhecidos. Com extends quebrado, ainda confere os pacotes e targets da
+    própria receita.
+    """
+    problems, drivers, programs = [], [], []
+    try:
+        recipe = RecipeSpec.load(recipes_dir, name)
+        packages, targets = recipe.workspace_packages(config), recipe.targets
+        drivers = [*(recipe.drivers_include or []), *recipe.drivers_exclude]
+        programs = recipe.programs or []
+    except ConfigError as e:
+        problems.append(e.message + (f" ({e.details})" if e.details else ""))
+        try: data, _ = _read(recipes_dir, name)
+        except ConfigError: return problems
+        packages = [p for p in data.get("packages", []) if isinstance(p, str)] if isinstance(data.get("packages"), list) else []
+        targets = {str(k): str(v) for k, v in data["targets"].items()} if isinstance(data.get("targets"), dict) else {}
+
+    available = _packages(config)
+    for package in packages:
+        if package not in available: problems.append(f"pacote '{package}' não existe no anvil.toml")
+        elif not (project_root / available[package] / "Cargo.toml").is_file():
+            problems.append(f"pacote '{package}': manifesto não encontrado ({available[package]}/Cargo.toml)")
+    driver_names = {d.name for d in config.components.drivers}
+    for driver in dict.fromkeys(drivers):
+        if driver not in driver_names: problems.append(f"driver '{driver}' não existe em drivers/")
+    service_names = {s.name for s in config.components.services} | {"supervisor"}
+    for program in dict.fromkeys(programs):
+        if program not in service_names: problems.append(f"programa '{program}' não é um serviço do anvil.toml")
+        elif program != "supervisor" and program not in packages: problems.append(f"programa '{program}' não está em packages")
+
+    # Triples do rustc mais os já usados no anvil.toml (specs customizadas como x86_64-redstone)
+    builtin = builtin_targets()
+    known = _configured_targets(config) | (builtin or set())
+    for package, triple in targets.items():
+        if package not in packages: problems.append(f"[targets] '{package}' não está em packages")
+        if is_target_spec(triple):
+            if not (project_root / triple).is_file(): problems.append(f"spec '{triple}' de '{package}' não encontrada")
+        elif builtin is not None and triple not in known: problems.append(f"target '{triple}' de '{package}' desconhecido")
+    return problems
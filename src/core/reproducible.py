@@ -0,0 +1,36 @@
+"""Anvil Core - Suporte a builds reproduzíveis.
+
+O modo reproduzível é sinalizado pela variável SOURCE_DATE_EPOCH, que também é
+respeitada pelas ferramentas externas (mksquashfs, tar, rustc...).
+"""
+
+import os
+import subprocess
+from pathlib import Path
+from typing import Optional
+
+def source_date_epoch() -> Optional[int]:
+    """Timestamp fixo do build, ou None fora do modo reproduzível."""
+    value = os.environ.get("SOURCE_DATE_EPOCH")
+    return int(value) if value and value.isdigit() else None
+
+def enable(root: Path) -> int:
+    """
+    Ativa o modo reproduzível: mantém SOURCE_DATE_EPOCH se já definido, senão usa
+    a data do último commit do projeto (ou 0 fora de um repositório git).
+    """
+    epoch = source_date_epoch()
+    if epoch is None:
+        try:
+            out = subprocess.run(["git", "log", "-1", "--format=%ct"], cwd=root,
+                                 capture_output=True, text=True, check=True).stdout.strip()
+            epoch = int(out)
+        except Exception:
+            epoch = 0
+        os.environ["SOURCE_DATE_EPOCH"] = str(epoch)
+    return epoch
+
+def normalize_mtimes(path: Path, epoch: int) -> None:
+    """Fixa a data de modificação de toda a árvore (as cópias preservam a data da origem)."""
+    for p in [path, *path.rglob("*")]:
+        os.utime(p, (epoch, epoch), follow_symlinks=False)
@@ -0,0 +1,45 @@
+"""Anvil Core - Seleção ativa: receita e features por componente.
+
+Gerada pelo 'anvil configure' e consumida pelos builds seguintes. A receita vale
+para 'build' e 'dist' sem '--recipe'; as chaves de features são os caminhos dos
+crates relativos à raiz do projeto (ex: "forge", "services/input").
+"""
+
+from __future__ import annotations
+from dataclasses import dataclass, field
+from pathlib import Path
+from typing import Optional
+import toml
+
+from core.errors import ConfigError
+
+@dataclass
+class Selection:
+    """Receita ativa e features de cargo escolhidas para cada componente."""
+    features: dict[str, list[str]] = field(default_factory=dict)
+    # Nome em recipes/ (ou embutida); None = todos os componentes
+    recipe: Optional[str] = None
+
+    @classmethod
+    def load(cls, path: Path) -> Selection:
+        """Carrega a seleção salva; retorna seleção vazia se o arquivo não existir."""
+        if not path.exists(): return cls()
+        try:
+            data = toml.load(path)
+        except Exception as e:
+            raise ConfigError(f"Falha ao carregar seleção: {path}", str(e))
+        recipe = data.get("recipe")
+        return cls(features={k: list(v) for k, v in data.get("features", {}).items()},
+                   recipe=str(recipe) if recipe else None)
+
+    def save(self, path: Path) -> None:
+        """Grava a seleção, omitindo componentes sem features ativas."""
+        path.parent.mkdir(parents=True, exist_ok=True)
+        data = {"features": {k: sorted(v) for k, v in self.features.items() if v}}
+        if self.recipe: data = {"recipe": self.recipe, **data}
+        with open(path, "w", encoding="utf-8") as f:
+            f.write("# Gerado por 'anvil configure' - não editar manualmente\n")
+            toml.dump(data, f)
+
+    def features_for(self, key: str) -> list[str]:
+        return self.features.get(key, [])
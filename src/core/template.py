@@ -0,0 +1,110 @@
+"""Anvil Core - Modelos de crate para 'anvil template new'.
+
+Cada modelo é uma árvore em anvil/templates/<tipo>/ copiada para o diretório
+do workspace correspondente ao tipo, trocando nos conteúdos e nos nomes de
+arquivo os campos {{name}}, {{name_snake}} e {{name_pascal}}.
+
+Um template.toml opcional na raiz do modelo (não copiado) descreve o modelo:
+
+    description = "Driver PCI com probe e IRQ"
+    destination = "drivers"   # obrigatório para tipos fora de TEMPLATE_DESTS
+"""
+
+from __future__ import annotations
+import re
+import shutil
+from dataclasses import dataclass
+from pathlib import Path
+from typing import Optional
+import toml
+
+from core.errors import ConfigError, ValidationError
+
+# Tipo -> diretório do workspace que recebe o crate (mesmas convenções de core.workspace)
+TEMPLATE_DESTS = {"driver": "drivers", "service": "services", "app": "apps", "lib": "lib"}
+
+# Descrições usadas quando o modelo não tem template.toml
+BUILTIN_DESCRIPTIONS = {
+    "driver": "Driver de dispositivo (no_std, binário)",
+    "service": "Serviço de sistema iniciado pelo supervisor",
+    "app": "Aplicativo de usuário",
+    "lib": "Biblioteca no_std compartilhada",
+}
+
+METADATA_FILE = "template.toml"
+
+NAME_PATTERN = re.compile(r"^[a-z][a-z0-9]*([-_][a-z0-9]+)*$")
+
+def name_variants(name: str) -> dict[str, str]:
+    """Valores dos campos: 'net-card' -> net-card, net_card, NetCard."""
+    parts = re.split(r"[-_]", name)
+    return {"name": name, "name_snake": "_".join(parts), "name_pascal": "".join(p.capitalize() for p in parts)}
+
+def substitute(text: str, variants: dict[str, str]) -> str:
+    for key, value in variants.items(): text = text.replace("{{" + key + "}}", value)
+    return text
+
+@dataclass
+class Template:
+    """Modelo encontrado em disco."""
+    kind: str
+    source: Path
+    description: str = ""
+    dest_dir: Optional[str] = None
+
+    @classmethod
+    def from_dir(cls, source: Path) -> Template:
+        """Lê o template.toml, se houver; sem ele valem as descrições embutidas."""
+        meta = {}
+        if (source / METADATA_FILE).is_file():
+            try:
+                meta = toml.load(source / METADATA_FILE)
+            except Exception as e:
+                raise ConfigError(f"Falha ao ler {source / METADATA_FILE}", str(e))
+        kind = source.name
+        return cls(kind, source, str(meta.get("description", BUILTIN_DESCRIPTIONS.get(kind, ""))),
+                   meta.get("destination", TEMPLATE_DESTS.get(kind)))
+
+    @classmethod
+    def find(cls, templates_dir: Path, kind: str) -> Template:
+        source = templates_dir / kind
+        if not source.is_dir():
+            available = [t.kind for t in list_templates(templates_dir)]
+            raise ConfigError(f"Modelo '{kind}' não encontrado em {templates_dir}",
+                              f"Disponíveis: {', '.join(available) or '(nenhum)'}")
+        return cls.from_dir(source)
+
+    def destination(self, root: Path, name: str) -> Path:
+        if not self.dest_dir:
+            raise ConfigError(f"Modelo '{self.kind}' sem diretório de destino",
+                              f"Defina destination em {self.source / METADATA_FILE}")
+        return root / self.dest_dir / name
+
+    def create(self, root: Path, name: str) -> Path:
+        """Gera o crate sob a raiz do projeto e retorna o diretório criado."""
+        if not NAME_PATTERN.match(name):
+            raise ConfigError(f"Nome de crate inválido: {name}", "Use minúsculas, dígitos e '-' ou '_' (ex: net-card)")
+        dest = self.destination(root, name)
+        if dest.exists(): raise ValidationError(f"Destino já existe: {dest}", str(dest))
+        variants = name_variants(name)
+        try:
+            for src in sorted(self.source.rglob("*")):
+                if not src.is_file() or src == self.source / METADATA_FILE: continue
+                rel = Path(substitute(src.relative_to(self.source).as_posix(), variants))
+                target = dest / rel
+                target.parent.mkdir(parents=True, exist_ok=True)
+                try:
+                    target.write_text(substitute(src.read_text(encoding="utf-8"), variants), encoding="utf-8")
+                except UnicodeDecodeError:
+                    # Binários (ícones, fontes...) vão sem substituição
+                    shutil.copyfile(src, target)
+        except BaseException:
+            # Não deixa um crate pela metade
+            shutil.rmtree(dest, ignore_errors=True)
+            raise
+        return dest
+
+def list_templates(templates_dir: Path) -> list[Template]:
+    """Subdiretórios de templates_dir, em ordem alfabética."""
+    if not templates_dir.is_dir(): return []
+    return [Template.from_dir(p) for p in sorted(templates_dir.iterdir()) if p.is_dir()]
@@ -0,0 +1,178 @@
+"""Anvil Core - Localização de ferramentas do toolchain Rust e pré-requisitos do build."""
+
+import shutil
+import subprocess
+from functools import lru_cache
+from pathlib import Path
+from typing import Optional
+import toml
+
+from core.config import BuildStdConfig
+
+@lru_cache(maxsize=None)
+def rustc_info() -> dict[str, str]:
+    """Campos de 'rustc -vV' (host, release...). Vazio se o rustc não estiver disponível."""
+    try:
+        out = subprocess.run(["rustc", "-vV"], capture_output=True, text=True, check=True).stdout
+    except Exception:
+        return {}
+    info = {}
+    for line in out.splitlines():
+        key, sep, value = line.partition(": ")
+        if sep: info[key] = value.strip()
+    return info
+
+def host_triple() -> Optional[str]:
+    return rustc_info().get("host")
+
+@lru_cache(maxsize=None)
+def sysroot() -> Optional[Path]:
+    try:
+        out = subprocess.run(["rustc", "--print", "sysroot"], capture_output=True, text=True, check=True).stdout
+    except Exception:
+        return None
+    return Path(out.strip())
+
+def llvm_tool(name: str) -> Optional[Path]:
+    """
+    Procura uma ferramenta LLVM (llvm-strip, llvm-objcopy...) no sysroot do rustc
+    (componente llvm-tools-preview) e depois no PATH.
+    """
+    root, host = sysroot(), host_triple()
+    if root and host:
+        candidate = root / "lib" / "rustlib" / host / "bin" / name
+        for path in (candidate, candidate.with_suffix(".exe")):
+            if path.exists(): return path
+    found = shutil.which(name)
+    return Path(found) if found else None
+
+# Verificações antes do build ('anvil build'): alvos fora da lista do rustc (ex: o
+# x86_64-redstone do kernel, uma spec JSON) e os de [build_std] targets compilam com
+# -Zbuild-std, que exige o código-fonte da std (rust-src) e um toolchain nightly
+RUST_SRC = "rust-src"
+
+@lru_cache(maxsize=None)
+def builtin_targets() -> Optional[frozenset[str]]:
+    """Alvos embutidos no rustc ('rustc --print target-list'); None sem rustc."""
+    try:
+        out = subprocess.run(["rustc", "--print", "target-list"], capture_output=True, text=True, check=True).stdout
+    except Exception:
+        return None
+    return frozenset(l.strip() for l in out.splitlines() if l.strip())
+
+def _rustup(*args: str, cwd: Path) -> Optional[str]:
+    """Saída de um comando do rustup no diretório cwd (o toolchain ativo depende dele); None sem rustup."""
+    try:
+        return subprocess.run(["rustup", *args], cwd=cwd, capture_output=True, text=True, check=True, timeout=30).stdout
+    except (OSError, subprocess.SubprocessError):
+        return None
+
+def active_toolchain(cwd: Path) -> Optional[str]:
+    """Nome do toolchain ativo em cwd (ex: nightly-x86_64-unknown-linux-gnu)."""
+    out = _rustup("show", "active-toolchain", cwd=cwd)
+    return out.split()[0] if out and out.split() else None
+
+def installed_components(cwd: Path) -> Optional[list[str]]:
+    """Componentes do toolchain ativo em cwd, sem o sufixo do host (rust-src, llvm-tools...)."""
+    out = _rustup("component", "list", "--installed", cwd=cwd)
+    if out is None: return None
+    host = host_triple()
+    return [l.strip().removesuffix(f"-{host}") if host else l.strip() for l in out.splitlines() if l.strip()]
+
+def pinned_channel(project_root: Path) -> Optional[str]:
+    """Canal fixado em rust-toolchain.toml (ou no formato antigo, rust-toolchain)."""
+    toml_file, legacy = project_root / "rust-toolchain.toml", project_root / "rust-toolchain"
+    try:
+        if toml_file.is_file(): return toml.load(toml_file).get("toolchain", {}).get("channel")
+        if legacy.is_file():
+            text = legacy.read_text().strip()
+            if not text.startswith("["): return text or None
+            return toml.loads(text).get("toolchain", {}).get("channel")
+    except (OSError, ValueError):
+        return None
+    return None
+
+def cargo_configs(path: Path, project_root: Path) -> list[dict]:
+    """Os .cargo/config(.toml) de path até a raiz do projeto, do mais próximo ao mais distante."""
+    configs = []
+    for base in [path, *path.parents]:
+        for name in ("config.toml", "config"):
+            config_file = base / ".cargo" / name
+            try:
+                if config_file.is_file(): configs.append(toml.load(config_file))
+            except (OSError, ValueError):
+                pass
+        if base == project_root or project_root not in base.parents: break
+    return configs
+
+def cargo_config_build_std(path: Path, project_root: Path) -> bool:
+    """Se algum .cargo/config(.toml) entre path e a raiz do projeto já pede [unstable] build-std."""
+    return any("build-std" in c.get("unstable", {}) for c in cargo_configs(path, project_root))
+
+def cargo_config_target(path: Path, project_root: Path) -> Optional[str]:
+    """[build] target do .cargo/config mais próximo (o kernel é compilado sem --target)."""
+    return next((c["build"]["target"] for c in cargo_configs(path, project_root)
+                 if isinstance(c.get("build", {}).get("target"), str)), None)
+
+def is_target_spec(target: Optional[str]) -> bool:
+    """Target dado como caminho de uma spec JSON, em vez de um triple."""
+    return target is not None and target.endswith(".json")
+
+def resolve_target(target: Optional[str], project_root: Path) -> Optional[str]:
+    """Triple como está; spec JSON como caminho absoluto (relativo à raiz do projeto), para o --target do cargo."""
+    if not is_target_spec(target): return target
+    return str((project_root / target).resolve())
+
+def target_dir_name(target: str) -> str:
+    """Diretório em target/ onde o cargo grava os binários: o triple, ou o nome da spec sem .json."""
+    return Path(target).stem if is_target_spec(target) else target
+
+def _bare_metal(target: Optional[str], build_std: Optional[BuildStdConfig]) -> bool:
+    """Spec customizada (fora da lista do rustc) ou alvo listado em [build_std] targets."""
+    if target is None: return False
+    if is_target_spec(target): return True
+    builtin = builtin_targets()
+    if builtin is not None and target not in builtin: return True
+    return build_std is not None and build_std.enabled and target in build_std.targets
+
+def needs_build_std(path: Path, target: Optional[str], project_root: Path,
+                    build_std: Optional[BuildStdConfig] = None) -> bool:
+    """Se o crate compila a core junto: alvo bare-metal ou build-std já no .cargo/config."""
+    target = target or cargo_config_target(path, project_root)
+    return _bare_metal(target, build_std) or cargo_config_build_std(path, project_root)
+
+def build_std_args(path: Path, target: Optional[str], project_root: Path, build_std: BuildStdConfig) -> list[str]:
+    """Flags '-Z build-std' para o cargo; vazio fora de alvos bare-metal ou se o .cargo/config já as define."""
+    if not build_std.enabled or not build_std.crates or cargo_config_build_std(path, project_root): return []
+    if not _bare_metal(target or cargo_config_target(path, project_root), build_std): return []
+    args = ["-Z", f"build-std={','.join(build_std.crates)}"]
+    if build_std.features: args += ["-Z", f"build-std-features={','.join(build_std.features)}"]
+    return args
+
+def check_build_prerequisites(project_root: Path, crates: list[tuple[Path, Optional[str]]],
+                              build_std: Optional[BuildStdConfig] = None) -> list[tuple[str, str]]:
+    """
+    Problemas do toolchain que fariam o cargo falhar com uma mensagem confusa,
+    cada um com o comando que resolve: (problema, correção). Vazio se estiver
+    tudo certo ou se não houver rustup para conferir.
+    """
+    toolchain = active_toolchain(project_root)
+    if toolchain is None: return []
+    problems = []
+    channel = pinned_channel(project_root)
+    build_std = [str(target or path.name) for path, target in crates if needs_build_std(path, target, project_root, build_std)]
+    # Toolchain em que o rust-src deve ser instalado: o que vai ficar ativo depois da correção
+    wanted = toolchain
+    if channel and channel.startswith("nightly") and not toolchain.startswith("nightly"):
+        problems.append((f"O projeto fixa o canal {channel}, mas o toolchain ativo é {toolchain}",
+                         f"rustup toolchain install {channel} (e remova RUSTUP_TOOLCHAIN/'rustup override')"))
+        wanted = channel
+    elif build_std and not toolchain.startswith("nightly"):
+        problems.append((f"-Zbuild-std ({', '.join(dict.fromkeys(build_std))}) exige nightly; toolchain ativo: {toolchain}",
+                         "rustup override set nightly"))
+        wanted = "nightly"
+    components = installed_components(project_root) if wanted == toolchain else []
+    if build_std and RUST_SRC not in (components if components is not None else [RUST_SRC]):
+        problems.append((f"Componente {RUST_SRC} ausente (necessário para -Zbuild-std)",
+                         f"rustup component add {RUST_SRC} --toolchain {wanted}"))
+    return problems
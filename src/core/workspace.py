@@ -0,0 +1,116 @@
+"""Anvil Core - Descoberta de componentes por convenção de diretórios.
+
+Permite usar o Anvil num workspace sem seção [components] no anvil.toml,
+desde que os crates sigam a estrutura padrão do RedstoneOS.
+"""
+
+import fnmatch
+import re
+from dataclasses import dataclass
+from pathlib import Path
+import toml
+
+# Papel -> (diretórios de um único crate, diretórios que agrupam vários crates, target)
+CONVENTIONS = {
+    "kernel": (["forge", "kernel"], [], "x86_64-redstone"),
+    "bootloader": (["ignite", "bootloader"], [], "x86_64-unknown-uefi"),
+    "service": (["firefly/compositor", "firefly/shell"], ["services", "userspace"], "x86_64-unknown-none"),
+    "app": ([], ["firefly/apps", "apps"], "x86_64-unknown-none"),
+    "driver": ([], ["drivers"], "x86_64-unknown-none"),
+}
+
+@dataclass
+class DiscoveredComponent:
+    """Crate encontrado e o papel que ele cumpre no sistema."""
+    role: str
+    name: str
+    path: str  # Relativo à raiz do projeto
+    target: str
+
+def crate_name(crate_dir: Path) -> str:
+    """Nome do pacote no Cargo.toml (ou o nome do diretório)."""
+    try:
+        return toml.load(crate_dir / "Cargo.toml")["package"]["name"]
+    except Exception:
+        return crate_dir.name
+
+def discover(root: Path) -> list[DiscoveredComponent]:
+    """Procura crates nos diretórios convencionais e retorna cada um com seu papel."""
+    found = []
+    for role, (singles, groups, target) in CONVENTIONS.items():
+        dirs = [root / d for d in singles]
+        for group in groups:
+            if (root / group).is_dir(): dirs.extend(sorted(p for p in (root / group).iterdir() if p.is_dir()))
+        for crate in dirs:
+            if not (crate / "Cargo.toml").exists(): continue
+            # Kernel e bootloader: vale o primeiro diretório encontrado
+            if singles and not groups and any(c.role == role for c in found): continue
+            found.append(DiscoveredComponent(role, crate_name(crate), crate.relative_to(root).as_posix(), target))
+    return found
+
+def _array_end(text: str, start: int) -> int:
+    """Índice do ']' que fecha o array aberto em text[start] (ignora strings e comentários)."""
+    depth, i = 0, start
+    while i < len(text):
+        ch = text[i]
+        if ch in "\"'":
+            quote = ch
+            i += 1
+            while i < len(text) and text[i] != quote:
+                if text[i] == "\\" and quote == '"': i += 1
+                i += 1
+        elif ch == "#":
+            while i < len(text) and text[i] != "\n": i += 1
+        elif ch == "[": depth += 1
+        elif ch == "]":
+            depth -= 1
+            if depth == 0: return i
+        i += 1
+    return -1
+
+def add_workspace_member(manifest: Path, member: str) -> bool:
+    """
+    Acrescenta member ao [workspace] members do Cargo.toml, editando o texto para
+    preservar formatação e comentários. Retorna False se o array não for encontrado.
+    """
+    text = manifest.read_text(encoding="utf-8")
+    try:
+        members = toml.loads(text).get("workspace", {}).get("members")
+    except Exception:
+        return False
+    if not isinstance(members, list): return False
+    # Já coberto por um item literal ou por um glob (ex: "drivers/*")
+    if any(fnmatch.fnmatchcase(member, m) for m in members if isinstance(m, str)): return True
+
+    section = re.search(r"^\[workspace\][ \t]*(#.*)?$", text, re.MULTILINE)
+    if not section: return False
+    next_section = re.search(r"^\[", text[section.end():], re.MULTILINE)
+    limit = section.end() + next_section.start() if next_section else len(text)
+    key = re.compile(r"^[ \t]*members[ \t]*=[ \t]*\[", re.MULTILINE).search(text, section.end(), limit)
+    if not key: return False
+    open_at = key.end() - 1
+    close_at = _array_end(text, open_at)
+    if close_at < 0: return False
+
+    body = text[open_at + 1:close_at]
+    entry = f'"{member}"'
+    if "\n" in body:
+        # Um item por linha: segue a indentação do último item
+        lines = body.rstrip().splitlines()
+        last = next((l for l in reversed(lines) if l.strip() and not l.strip().startswith("#")), "")
+        indent = re.match(r"[ \t]*", last).group() if last else "    "
+        head = body.rstrip()
+        # Vírgula após o último item (antes de um eventual comentário na mesma linha)
+        tail_code = re.sub(r"#.*$", "", head.splitlines()[-1]).rstrip()
+        if tail_code and not tail_code.endswith((",", "[")):
+            last_line = head.splitlines()[-1]
+            code = last_line[:len(tail_code)]
+            head = head[:len(head) - len(last_line)] + code + "," + last_line[len(tail_code):]
+        new_body = f"{head}\n{indent}{entry},\n"
+    else:
+        stripped = body.strip()
+        if not stripped: new_body = entry
+        elif stripped.endswith(","): new_body = f"{body.rstrip()} {entry}"
+        else: new_body = f"{body.rstrip()}, {entry}"
+    manifest.write_text(text[:open_at + 1] + new_body + text[close_at:], encoding="utf-8")
+    return True
@@ -1,8 +1,17 @@
+import argparse
 import asyncio
+import collections
+import dataclasses
+import json
+import os
+import re
 import sys
 import shutil
+import subprocess
 import time
+import webbrowser
 from pathlib import Path
+from typing import Optional
 
 # Captura de tecla compatível com Linux/Unix
 import tty
@@ -11,20 +20,58 @@ import termios
 # Adiciona o diretório 'src' ao path do python para permitir imports relativos
 sys.path.append(str(Path(__file__).parent))
 
-from core.config import load_config
-from core.paths import Paths
-from core.logger import get_logger, Colors
+from core.config import ARCHES, DEFAULT_ARCH, load_config
+from core.paths import Paths, dir_size, format_size
+from core.logger import COLOR_MODES, get_logger, set_color_mode, Colors
+from core.errors import AnvilError, BuildError, ConfigError, RunError, ValidationError
+from core.checksum import sha256_file, write_sums
+from core.toolchain import build_std_args, builtin_targets, is_target_spec, resolve_target, check_build_prerequisites, llvm_tool, host_triple, rustc_info, sysroot
+from core.environment import (SHELLS, TOOLS, EnvReport, bootstrap_plan, check_tools, export_lines,
+                              system_package_hints)
+from core.selection import Selection
+from core.recipe import BUILTIN_RECIPE_DATA, RecipeSpec, list_recipes, recipe_names, validate_recipe
+from core.template import TEMPLATE_DESTS, Template, list_templates
+from core.workspace import add_workspace_member, crate_name
+from core.events import get_events
+from core import reproducible
+import toml
 
 # Imports dos módulos de construção e execução
-from build.dist import DistBuilder
+from build.dist import DistBuilder, STRIP_LEVELS
+from build.archive import ARCHIVE_FORMATS, create_archive
+from build.manifest import read_manifest
+from build.signing import sign_manifest, signature_path, verify_manifest
 from build.initramfs import InitramfsBuilder
 from build.image import ImageBuilder
+from build.iso import IsoBuilder
+from build.usb import UsbDevice, UsbWriter, list_usb_devices
+from build.tools import HostToolsDiscovery
+from build.bisect import Bisector
+from build.fastcheck import FastCheckCache, fingerprint
+from build.report import BuildReport, PackageTiming
+from build import size as binsize
+from build.watch import DEFAULT_DEBOUNCE, SourceWatcher
+from runner import bench
 
 from runner.monitor import QemuMonitor
+from runner.qemu import (QemuOptions, QemuRunner, create_disk, find_ovmf, host_arch, hw_accel_possible, parse_new_disk,
+                         ACCELERATORS, BOOTLOADER_BANNER, DEFAULT_TAP, DISPLAYS, GDB_DEFAULT_PORT, GDB_TIMEOUT_ACTIONS, NET_MODES,
+                         TEST_EXIT_PORT, TEST_SUCCESS_CODE)
 from runner.serial import PipeListener, SerialColorizer
 from runner.streams import StreamSource
 
 logger = get_logger()
+events = get_events()
+
+# Últimas linhas da serial no resumo do 'test --json'
+TEST_SERIAL_TAIL = 50
+
+# Componentes simultâneos de 'build --parallel' (o cargo já paraleliza dentro de cada crate)
+DEFAULT_PARALLEL_BUILDS = 3
+
+# Linhas de progresso do cargo (verbo alinhado à direita), omitidas sem --verbose
+CARGO_STATUS_LINE = re.compile(r"^\s*(Compiling|Checking|Documenting|Finished|Fresh|Downloading|Downloaded|Updating|"
+                               r"Locking|Adding|Blocking|Building|Running|Packaging|Removed|Scraping)\s")
 
 class AnvilCLI:
     """
@@ -32,61 +79,205 @@ class AnvilCLI:
     Gerencia as chamadas para os builders e runners.
     """
     
-    def __init__(self):
-        self.config = load_config()
-        self.paths = Paths(self.config.project_root)
+    def __init__(self, config_path: Optional[Path] = None):
+        # None: procura o anvil.toml nos locais padrão
+        self.config = load_config(config_path)
+        self.paths = Paths(self.config.project_root, self.config.components)
+        # Ativado por '--reproducible': cargo com --locked e datas fixas no dist
+        self.reproducible = False
+        # Versão exposta aos componentes como ANVIL_BUILD_VERSION (padrão: git describe)
+        self.version_string: str = None
+        # Relatórios HTML do 'cargo build --timings' (ativado por 'build --timings')
+        self.timings = False
+        self.timing_reports: list[tuple[str, Path]] = []
+        # -Ctarget-cpu das ferramentas de host ('build tools --target-cpu'); nunca vale para o SO
+        self.tools_target_cpu = None
+        # Pula o cargo quando os fontes não mudaram ('build --fast-check')
+        self.fast_check = False
+        # Drivers entram no build completo só com 'build --with-drivers'
+        self.with_drivers = False
+        # Componentes compilados ao mesmo tempo ('build --jobs'); 1 = em sequência
+        self.jobs = 1
+        # Tempo de cada invocação do cargo, resumido ao final do 'anvil build'
+        self.report = BuildReport()
+        # Receita ativa ('build/dist --recipe'); self.config fica restrita aos pacotes dela
+        self.recipe: Optional[RecipeSpec] = None
+        # MonitorResult do último run_qemu (lido pelo 'test --json')
+        self.qemu_result = None
 
-    async def run_cargo(self, name: str, path: Path, target: str = None, profile: str = "release") -> bool:
-        """Executa 'cargo build' para um componente específico."""
-        logger.info(f"Construindo {name}...")
+    async def run_cargo(self, name: str, path: Path, target: str = None, profile: str = "release",
+                        rustc_args: list = None, rustflags: list = None, features: list = None,
+                        subcommand: str = "build", cargo_args: list = None, tool_args: list = None) -> bool:
+        """
+        Executa 'cargo build' (ou outro subcomando, como 'check') para um componente específico.
+        Com rustc_args usa 'cargo rustc', repassando as flags só ao crate final
+        (sem substituir as rustflags do .cargo/config.toml como RUSTFLAGS faria).
+        rustflags vão em RUSTFLAGS e atingem também as dependências.
+        cargo_args vão ao subcomando; tool_args, depois de '--' (ex: clippy -D warnings).
+        """
+        logger.info(f"{'Conferindo' if subcommand == 'check' else 'Construindo'} {name}...")
+        events.emit("package-started", package=name, path=path, profile=profile)
+        started = time.time()
         
-        cmd = ["cargo", "build"]
+        cmd = ["cargo", "rustc" if rustc_args else subcommand]
         if profile == "release": cmd.append("--release")
         elif profile != "debug": cmd.extend(["--profile", profile])
         
-        if target: cmd.extend(["--target", target])
+        # Kernel: sem --target vale o .cargo/config.toml do forge (ver kernel_target)
+        if target is None and path == self.paths.forge: target = self.kernel_target()
+        if is_target_spec(target) and not (self.paths.root / target).is_file():
+            raise ConfigError(f"Spec de target não encontrada: {target}", f"Caminhos de spec são relativos a {self.paths.root}")
+        if target: cmd.extend(["--target", resolve_target(target, self.paths.root)])
+        cmd.extend(build_std_args(path, target, self.paths.root, self.config.build_std))
+        if cargo_args: cmd.extend(cargo_args)
+        # Builds em paralelo dividem as CPUs para não disputar além do que a máquina tem
+        if self.jobs > 1: cmd.extend(["-j", str(max(1, (os.cpu_count() or 1) // self.jobs))])
+        if self.timings: cmd.append("--timings")
+        if self.reproducible: cmd.append("--locked")
+        
+        # Features escolhidas via 'anvil configure'
+        features = [*self.selection().features_for(self._component_key(path)), *(features or [])]
+        if features: cmd.extend(["--features", ",".join(dict.fromkeys(features))])
+        if rustc_args or tool_args: cmd.extend(["--", *(rustc_args or []), *(tool_args or [])])
+        
+        env = os.environ.copy()
+        env["ANVIL_BUILD_VERSION"] = await self.build_version()
+        if rustflags: env["RUSTFLAGS"] = " ".join(filter(None, [env.get("RUSTFLAGS"), *rustflags]))
+
+        fast_key = fast_value = None
+        if self.fast_check:
+            fast_key = f"{self._component_key(path)}:{target or 'host'}:{profile}"
+            fast_value = fingerprint(
+                [path, self.paths.lib, self.paths.sdk], [self.paths.root / "Cargo.lock"],
+                {"cmd": cmd, "rustflags": env.get("RUSTFLAGS"), "version": env["ANVIL_BUILD_VERSION"],
+                 "rustc": rustc_info().get("release")},
+            )
+            has_target = any((base / "target").is_dir() for base in [path, *path.parents]
+                             if base == self.paths.root or self.paths.root in base.parents)
+            cache = FastCheckCache(self.paths.fast_check_file)
+            if has_target and cache.matches(fast_key, fast_value):
+                logger.success(f"{name} sem alterações (fast-check), cargo não executado")
+                events.emit("package-finished", package=name, ok=True, cached=True,
+                            duration_ms=int((time.time() - started) * 1000))
+                self.report.add(PackageTiming(name, target, profile, time.time() - started, True, cached=True))
+                return True
         
         try:
             process = await asyncio.create_subprocess_exec(
-                *cmd, cwd=path, stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.STDOUT
+                *cmd, cwd=path, env=env, stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.STDOUT
             )
             
-            # Lê a saída em tempo real
+            # Lê a saída em tempo real; com --quiet guarda e só mostra se o cargo falhar
+            held = []
             while True:
                 line = await process.stdout.readline()
                 if not line: break
                 decoded = line.decode("utf-8", errors="replace").rstrip()
-                if decoded:
+                # Sem -v, só avisos e erros: as linhas de status (Compiling, Finished...) ficam de fora
+                if decoded and (logger.verbose or not CARGO_STATUS_LINE.match(decoded)):
                      # Imprime saída do cargo em cinza para não poluir
-                    print(f"{Colors.GREY}  | {decoded}{Colors.RESET}")
+                    prefix = f"{name} " if self.jobs > 1 else ""
+                    text = f"{Colors.GREY}  {prefix}| {decoded}{Colors.RESET}"
+                    if logger.quiet: held.append(text)
+                    else: print(text, flush=True, file=logger.stream)
             
             await process.wait()
+            if process.returncode != 0:
+                for text in held: print(text, flush=True, file=logger.stream)
+            events.emit("package-finished", package=name, ok=process.returncode == 0,
+                        duration_ms=int((time.time() - started) * 1000))
+            self.report.add(PackageTiming(name, target, profile, time.time() - started, process.returncode == 0))
+            if fast_key: FastCheckCache(self.paths.fast_check_file).store(
+                fast_key, fast_value if process.returncode == 0 else None)
             if process.returncode == 0:
                 logger.success(f"{name} pronto!")
+                if self.timings:
+                    # O target/ pode ser do próprio crate ou do workspace que o contém
+                    for base in [path, *path.parents]:
+                        report = base / "target" / "cargo-timings" / "cargo-timing.html"
+                        if report.exists():
+                            self.timing_reports.append((name, report))
+                            break
+                        if base == self.paths.root: break
                 return True
                 
             logger.error(f"Erro em {name}!")
             return False
         except Exception as e:
             logger.error(f"Exceção ao rodar cargo: {e}")
+            events.emit("package-finished", package=name, ok=False, duration_ms=int((time.time() - started) * 1000))
+            self.report.add(PackageTiming(name, target, profile, time.time() - started, False))
             return False
 
-    async def build_release(self):
+    async def build_version(self) -> str:
+        """Versão do build: --version-string ou 'git describe' do projeto ('unknown' fora do git)."""
+        if self.version_string is None:
+            try:
+                p = await asyncio.create_subprocess_exec(
+                    "git", "describe", "--always", "--dirty", "--tags", cwd=self.paths.root,
+                    stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.DEVNULL
+                )
+                out, _ = await p.communicate()
+                self.version_string = out.decode().strip() if p.returncode == 0 else "unknown"
+            except Exception:
+                self.version_string = "unknown"
+        return self.version_string
+
+    def selection(self) -> Selection:
+        return Selection.load(self.paths.selection_file)
+
+    def _component_key(self, path: Path) -> str:
+        """Chave do componente na seleção: caminho relativo à raiz do projeto."""
+        try: return path.resolve().relative_to(self.paths.root).as_posix()
+        except ValueError: return path.as_posix()
+
+    def use_arch(self, arch: str) -> None:
+        """Troca a arquitetura do guest ('--arch'): targets, destino do bootloader e caminhos dos artefatos."""
+        self.config = self.config.for_arch(arch)
+        self.paths = Paths(self.config.project_root, self.config.components)
+        if arch != DEFAULT_ARCH: logger.info(f"Arquitetura: {arch}")
+
+    def check_cross_arch(self):
+        """Avisa quando o host não tem a arquitetura do guest: 'anvil run' precisará de emulação (TCG)."""
+        if not hw_accel_possible(self.config.arch):
+            logger.warning(f"Host {host_arch()} ≠ alvo {self.config.arch}: sem aceleração por hardware, "
+                           "'anvil run' usará emulação TCG (lenta)")
+
+    async def build_components(self, profile: str, kernel_profile: str = None) -> bool:
+        """
+        Kernel, bootloader, serviços e apps. Em sequência, para no primeiro erro; com self.jobs > 1
+        compila até jobs componentes ao mesmo tempo e falha no fim listando todos os erros.
+        Falhas viram BuildError, para o comando sair com código diferente de zero.
+        """
+        comps = self.config.components
+        jobs = [("Kernel", self.paths.forge, None, kernel_profile or profile),
+                ("Bootloader", self.paths.ignite, comps.bootloader.target, profile)]
+        jobs += [(c.name, self.paths.root / c.path, c.target, profile) for c in [*comps.services, *comps.apps]]
+        if self.with_drivers: jobs += [(d.name, self.paths.root / d.path, d.target, profile) for d in comps.drivers]
+        if self.jobs <= 1:
+            for name, path, target, prof in jobs:
+                if not await self.run_cargo(name, path, target=target, profile=prof):
+                    raise BuildError(f"Build falhou em {name}", "build", [name])
+            return True
+
+        logger.info(f"Compilando até {self.jobs} componentes em paralelo")
+        slots = asyncio.Semaphore(self.jobs)
+        async def build(name, path, target, prof):
+            async with slots: return await self.run_cargo(name, path, target=target, profile=prof)
+        results = await asyncio.gather(*(build(*job) for job in jobs))
+        failed = [job[0] for job, ok in zip(jobs, results) if not ok]
+        if failed: raise BuildError(f"Build falhou em {len(failed)} de {len(jobs)} componente(s)", "build", failed)
+        return True
+
+    async def build_release(self) -> bool:
         """Compila tudo em modo Release."""
         logger.header("Build Total (Release)")
-        
-        if not await self.run_cargo("Kernel", self.paths.forge, profile="release"): return
-        if not await self.run_cargo("Bootloader", self.paths.ignite, target="x86_64-unknown-uefi", profile="release"): return
-        
-        for svc in self.config.components.services:
-            if not await self.run_cargo(svc.name, self.paths.root / svc.path, target=svc.target, profile="release"): return
-            
-        for app in self.config.components.apps:
-            if not await self.run_cargo(app.name, self.paths.root / app.path, target=app.target, profile="release"): return
+        if not await self.build_components("release"): return False
             
         DistBuilder(self.paths, self.config).prepare(profile="release")
         await InitramfsBuilder(self.paths, self.config).build(profile="release")
         logger.success("Build Release concluída!")
+        return True
 
     async def build_clean_release(self):
         """
@@ -94,23 +285,14 @@ class AnvilCLI:
         Isso gera um kernel mais limpo para produção.
         """
         logger.header("Build Limpa (Zero Tracer)")
-        
-        if not await self.run_cargo("Kernel", self.paths.forge, profile="clean-release"): return
-        if not await self.run_cargo("Bootloader", self.paths.ignite, target="x86_64-unknown-uefi", profile="release"): return
-        
-        for svc in self.config.components.services:
-            if not await self.run_cargo(svc.name, self.paths.root / svc.path, target=svc.target, profile="release"): return
-        
-        for app in self.config.components.apps:
-            if not await self.run_cargo(app.name, self.paths.root / app.path, target=app.target, profile="release"): return
+        if not await self.build_components("release", kernel_profile="clean-release"): return
         
         logger.info("Implantando artefatos limpos...")
         # Copia o kernel clean-release para o lugar do release para ser pego pelo DistBuilder
         try:
             shutil.copy2(self.paths.kernel_binary("clean-release"), self.paths.kernel_binary("release"))
-        except Exception as e:
-            logger.error(f"Falha ao copiar kernel clean: {e}")
-            return
+        except OSError as e:
+            raise BuildError(f"Falha ao copiar kernel clean: {e}", "build")
 
         DistBuilder(self.paths, self.config).prepare(profile="release")
         await InitramfsBuilder(self.paths, self.config).build(profile="release")
@@ -119,53 +301,977 @@ class AnvilCLI:
     async def build_opt_release(self):
         """Compila tudo em modo Otimizado (opt-release)."""
         logger.header("Build Otimizada")
-        
-        if not await self.run_cargo("Kernel", self.paths.forge, profile="opt-release"): return
-        if not await self.run_cargo("Bootloader", self.paths.ignite, target="x86_64-unknown-uefi", profile="opt-release"): return
-        
-        for svc in self.config.components.services:
-            if not await self.run_cargo(svc.name, self.paths.root / svc.path, target=svc.target, profile="opt-release"): return
-            
-        for app in self.config.components.apps:
-            if not await self.run_cargo(app.name, self.paths.root / app.path, target=app.target, profile="opt-release"): return
+        if not await self.build_components("opt-release"): return
             
         DistBuilder(self.paths, self.config).prepare(profile="opt-release")
         await InitramfsBuilder(self.paths, self.config).build(profile="opt-release")
         logger.success("Build Otimizada concluída!")
 
-    # Atalhos para compilar componentes individuais
-    async def build_kernel(self): await self.run_cargo("Kernel", self.paths.forge)
-    async def build_bootloader(self): await self.run_cargo("Bootloader", self.paths.ignite, target="x86_64-unknown-uefi")
+    def use_recipe(self, name: str) -> RecipeSpec:
+        """Carrega a receita e restringe a config aos pacotes dela (valida antes de qualquer build)."""
+        recipe = RecipeSpec.load(self.paths.recipes, name)
+        self.config = recipe.apply(self.config)
+        self.recipe = recipe
+        logger.info(f"Receita: {recipe.name} ({', '.join(recipe.workspace_packages(self.config))})")
+        for program in recipe.missing_programs(self.config):
+            logger.warning(f"Programa '{program}' da receita não é um serviço dela; ficará fora do initfs")
+        return recipe
+
+    async def build_recipe(self, profile: str):
+        """Compila os pacotes da receita ativa, na ordem dela, depois os drivers escolhidos por ela, e monta o dist."""
+        logger.header(f"Build da Receita {self.recipe.name} ({profile})")
+        comps = self.config.components
+        crates = {c.name: c for c in [*comps.services, *comps.apps, *comps.drivers]}
+        # A config já vem restrita aos drivers da receita (packages ou a tabela drivers)
+        packages = self.recipe.workspace_packages(self.config)
+        extra = [d.name for d in comps.drivers if d.name not in packages]
+        for package in [*packages, *extra]:
+            if package == "kernel":
+                target = comps.kernel.target if "kernel" in self.recipe.targets else None
+                ok = await self.run_cargo("Kernel", self.paths.forge, target=target, profile=profile)
+            elif package == "bootloader":
+                ok = await self.run_cargo("Bootloader", self.paths.ignite, target=comps.bootloader.target, profile=profile)
+            else:
+                crate = crates[package]
+                ok = await self.run_cargo(crate.name, self.paths.root / crate.path, target=crate.target, profile=profile)
+            if not ok: raise BuildError(f"Build da receita {self.recipe.name} falhou em {package}", "recipe", [package])
+
+        DistBuilder(self.paths, self.config).prepare(profile=profile)
+        await InitramfsBuilder(self.paths, self.config).build(profile=profile)
+        logger.success(f"Build da receita {self.recipe.name} concluída!")
+
+    def component_jobs(self, only: str = None) -> list[tuple[str, Path, Optional[str]]]:
+        """(nome, diretório, target) de cada componente, com o mesmo target do build; only filtra um grupo."""
+        comps = self.config.components
+        jobs = []
+        # Kernel sem --target: vale o do .cargo/config.toml do forge (ou a spec JSON do anvil.toml, ou o --arch)
+        if only in (None, "kernel"): jobs.append(("Kernel", self.paths.forge, self.kernel_target()))
+        if only in (None, "bootloader"): jobs.append(("Bootloader", self.paths.ignite, comps.bootloader.target))
+        if only in (None, "services"): jobs += [(s.name, self.paths.root / s.path, s.target) for s in comps.services]
+        if only in (None, "apps"): jobs += [(a.name, self.paths.root / a.path, a.target) for a in comps.apps]
+        if only in (None, "drivers"): jobs += [(d.name, self.paths.root / d.path, d.target) for d in comps.drivers]
+        return jobs
+
+    async def changed_jobs(self, jobs: list[tuple[str, Path, Optional[str]]], since: str) -> list[tuple[str, Path, Optional[str]]]:
+        """
+        Só os componentes com arquivos alterados desde o ref since: commits (since...HEAD), mudanças
+        não commitadas e arquivos novos. Fora de um repositório git ou com ref inválido, avisa e
+        devolve todos.
+        """
+        changed = set()
+        for args in (["diff", "--name-only", "--relative", f"{since}...HEAD"], ["diff", "--name-only", "--relative", "HEAD"],
+                     ["ls-files", "--others", "--exclude-standard"]):
+            try:
+                p = await asyncio.create_subprocess_exec("git", *args, cwd=self.paths.root,
+                                                         stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.PIPE)
+                out, err = await p.communicate()
+            except OSError as e: out, err, code = b"", str(e).encode(), 1
+            else: code = p.returncode
+            if code != 0:
+                reason = err.decode(errors="replace").strip().splitlines()
+                logger.warning(f"--since {since}: git falhou ({reason[0] if reason else 'sem detalhes'}); conferindo a árvore toda")
+                return jobs
+            changed |= {(self.paths.root / line).resolve() for line in out.decode().splitlines() if line}
+        selected = [job for job in jobs if any(f.is_relative_to(job[1].resolve()) for f in changed)]
+        logger.step(f"Alterados desde {since}: {', '.join(name for name, _, _ in selected) or '(nenhum componente)'}")
+        return selected
+
+    def kernel_target(self) -> Optional[str]:
+        """
+        Target explícito do kernel: uma spec JSON do anvil.toml ou, com '--arch', o triple da
+        arquitetura. Senão None, e o cargo usa o do .cargo/config.toml do forge.
+        """
+        target = self.config.components.kernel.target
+        return target if is_target_spec(target) or self.config.arch != DEFAULT_ARCH else None
+
+    def check_prerequisites(self, only: str = None) -> None:
+        """Aborta antes do cargo se faltar rust-src ou nightly para os componentes do build, com o comando que resolve."""
+        if not self.config.preflight.toolchain: return
+        crates = [(path, target) for _, path, target in self.component_jobs(only)]
+        problems = check_build_prerequisites(self.paths.root, crates, self.config.build_std)
+        if problems:
+            raise BuildError("Pré-requisitos do toolchain ausentes", "rustup", [f"{p} -> {fix}" for p, fix in problems])
+
+    async def run_per_component(self, label: str, subcommand: str, only: str = None,
+                                cargo_args: list = None, tool_args: list = None, since: str = None):
+        """
+        Roda um subcomando do cargo em cada componente (com since, só nos alterados desde
+        esse ref). Segue mesmo após uma falha, para mostrar todos os erros de uma vez, e
+        falha no fim listando os componentes.
+        """
+        logger.header(label)
+        jobs = self.component_jobs(only)
+        if since:
+            jobs = await self.changed_jobs(jobs, since)
+            if not jobs:
+                logger.success(f"{label}: nada alterado desde {since}")
+                return
+        failed = []
+        for name, path, target in jobs:
+            if not await self.run_cargo(name, path, target=target, profile="debug", subcommand=subcommand,
+                                        cargo_args=cargo_args, tool_args=tool_args):
+                failed.append(name)
+        if failed: raise BuildError(f"{label} falhou em {len(failed)} de {len(jobs)} componente(s)", subcommand, failed)
+        logger.success(f"{label} OK ({len(jobs)} componentes)")
+
+    async def check(self, only: str = None):
+        """'cargo check' de cada componente no target do build."""
+        await self.run_per_component("Check", "check", only)
+
+    async def clippy(self, only: str = None, fix: bool = False, deny_warnings: bool = False, since: str = None):
+        """'cargo clippy' de cada componente (ou só dos alterados desde since); deny_warnings acrescenta '-- -D warnings'."""
+        await self.run_per_component("Clippy", "clippy", only, cargo_args=["--fix"] if fix else None,
+                                     tool_args=["-D", "warnings"] if deny_warnings else None, since=since)
+
+    def _doc_index(self) -> Optional[Path]:
+        """index.html da documentação do kernel, em target/doc ou target/<triple>/doc."""
+        name = crate_name(self.paths.forge).replace("-", "_")
+        for base in [self.paths.forge, *self.paths.forge.parents]:
+            candidates = [base / "target" / "doc" / name / "index.html",
+                          *sorted((base / "target").glob(f"*/doc/{name}/index.html"))]
+            found = next((c for c in candidates if c.is_file()), None)
+            if found or base == self.paths.root: return found
+        return None
+
+    async def doc(self, only: str = None, open_docs: bool = False, private: bool = False):
+        """'cargo doc --no-deps' de cada componente no seu target; open_docs abre a do kernel no navegador."""
+        await self.run_per_component("Documentação", "doc", only,
+                                     cargo_args=["--no-deps", *(["--document-private-items"] if private else [])])
+        index = self._doc_index()
+        if not index:
+            logger.warning("Índice da documentação do kernel não encontrado")
+            return
+        logger.info(f"Documentação: {index}")
+        if open_docs: webbrowser.open(index.as_uri())
+
+    async def size(self, only: str = None, profile: str = "release"):
+        """Tamanho das seções (.text, .rodata, .data, .bss) dos binários já compilados; padrão: kernel e bootloader."""
+        tool = binsize.find_size_tool()
+        if not tool: raise BuildError("llvm-size/rust-size/size não encontrados", "size", binsize.INSTALL_HINTS)
+        comps = self.config.components
+        binaries = []
+        if only in (None, "kernel"): binaries.append(("Kernel", self.paths.kernel_binary(profile)))
+        if only in (None, "bootloader"): binaries.append(("Bootloader", self.paths.bootloader_binary(profile)))
+        groups = {"services": comps.services, "apps": comps.apps, "drivers": comps.drivers}
+        for item in groups.get(only, []):
+            binary = self.paths.service_binary(item.name, profile, base_path=self.paths.root / item.path, target=item.target)
+            binaries.append((item.name, binary))
+
+        logger.header(f"Tamanho dos binários ({profile})")
+        logger.step(f"Ferramenta: {tool.name}")
+        sizes = []
+        for name, path in binaries:
+            if not path.is_file():
+                logger.warning(f"{name} não compilado: {path}")
+                continue
+            try: sizes.append(await binsize.measure(tool, name, path))
+            except BuildError as e: logger.warning(f"{name}: {e.errors[0] if e.errors else e}")
+        if not sizes: raise BuildError(f"Nenhum binário do perfil {profile} encontrado; rode 'anvil build' primeiro", "size")
+        binsize.print_table(sizes, logger)
+
+    async def fmt(self, check: bool = False, since: str = None):
+        """
+        'cargo fmt --all' no workspace da raiz (ou em cada componente, sem workspace). Com since,
+        'cargo fmt' só nos diretórios dos componentes alterados desde esse ref.
+        """
+        logger.header("Verificando formatação" if check else "Formatando")
+        dirs = [self.paths.root] if (self.paths.root / "Cargo.toml").exists() else [p for _, p, _ in self.component_jobs()]
+        if since:
+            jobs = self.component_jobs()
+            changed = await self.changed_jobs(jobs, since)
+            if not changed:
+                logger.success(f"Formatação: nada alterado desde {since}")
+                return
+            # Árvore toda quando o git falhou (changed_jobs devolve todos)
+            if changed is not jobs: dirs = [p for _, p, _ in changed]
+        failed = []
+        for path in dirs:
+            # No diretório de um crate, sem --all o cargo fmt formata só aquele pacote
+            cmd = ["cargo", "fmt", *(["--all"] if path == self.paths.root or not since else []), *(["--check"] if check else [])]
+            logger.debug(f"Comando: {' '.join(cmd)} (em {path})")
+            p = await asyncio.create_subprocess_exec(*cmd, cwd=path)
+            if await p.wait() != 0: failed.append(path.relative_to(self.paths.root).as_posix() or ".")
+        if failed:
+            message = "Arquivos fora do padrão do rustfmt" if check else "cargo fmt falhou"
+            raise BuildError(message, "fmt", failed)
+        logger.success("Formatação OK" if check else "Formatado")
+
+    # Atalhos para compilar componentes individuais; serviços e apps seguem após uma falha e listam todas no fim
+    async def build_kernel(self):
+        if not await self.run_cargo("Kernel", self.paths.forge): raise BuildError("Build falhou em Kernel", "build", ["Kernel"])
+    async def build_bootloader(self):
+        if not await self.run_cargo("Bootloader", self.paths.ignite, target=self.config.components.bootloader.target):
+            raise BuildError("Build falhou em Bootloader", "build", ["Bootloader"])
     async def build_services(self):
-        for svc in self.config.components.services: await self.run_cargo(svc.name, self.paths.root / svc.path, target=svc.target)
+        services = self.config.components.services
+        failed = [s.name for s in services if not await self.run_cargo(s.name, self.paths.root / s.path, target=s.target)]
+        if failed: raise BuildError(f"Build falhou em {len(failed)} de {len(services)} serviço(s)", "services", failed)
     async def build_apps(self):
-        for app in self.config.components.apps: await self.run_cargo(app.name, self.paths.root / app.path, target=app.target)
+        apps = self.config.components.apps
+        failed = [a.name for a in apps if not await self.run_cargo(a.name, self.paths.root / a.path, target=a.target)]
+        if failed: raise BuildError(f"Build falhou em {len(failed)} de {len(apps)} app(s)", "apps", failed)
+
+    async def build_drivers(self, only: str = None):
+        """Compila os drivers de drivers/ (ou só o driver 'only'), informando cada um."""
+        drivers = self.config.components.drivers
+        if only:
+            drivers = [d for d in drivers if d.name == only]
+            if not drivers:
+                raise ConfigError(f"Driver não encontrado: {only}",
+                                  f"Disponíveis: {', '.join(d.name for d in self.config.components.drivers) or '(nenhum)'}")
+        if not drivers:
+            logger.warning(f"Nenhum driver encontrado em {self.paths.root / 'drivers'}")
+            return
+        logger.header("Drivers")
+        failed = [d.name for d in drivers if not await self.run_cargo(d.name, self.paths.root / d.path, target=d.target)]
+        if failed: raise BuildError(f"Build falhou em {len(failed)} de {len(drivers)} driver(s)", "drivers", failed)
+
+    async def dist(self, profile: str = "release", squashfs: bool = False, strip: str = "none", splash: Path = None,
+                   layout: str = "uefi", checksum: bool = True, incremental: bool = False, strict: bool = False,
+                   archive: Optional[str] = None, sign: bool = False):
+        """
+        Monta dist/qemu a partir dos binários já compilados; incremental só copia os que mudaram.
+        Com strict, serviços e apps não compilados são erro (bootloader e kernel sempre são).
+        Com sign, assina o manifest.json com a chave de [dist].signing_key (manifest.json.sig).
+        Com archive (tar.gz ou zip), empacota o dist pronto em dist/redstone.<archive>.
+        """
+        key = self.signing_key() if sign else None
+        builder = DistBuilder(self.paths, self.config)
+        builder.incremental = incremental
+        builder.prepare(profile=profile, splash=splash, layout=layout)
+        initramfs = InitramfsBuilder(self.paths, self.config)
+        initramfs.incremental = incremental
+        initramfs.strict = strict
+        await initramfs.build(profile=profile)
+        await builder.strip(strip, profile=profile)
+        epoch = reproducible.source_date_epoch()
+        if self.reproducible and epoch is not None: reproducible.normalize_mtimes(self.paths.dist_qemu, epoch)
+        if squashfs: await builder.pack_squashfs()
+        # Depois do strip e do squashfs: tamanhos e hashes são os do dist final
+        manifest = builder.write_manifest(profile, initramfs.copied, await self.build_version())
+        if key: logger.step(f"Assinatura: {sign_manifest(manifest, key).name}")
+        else: signature_path(manifest).unlink(missing_ok=True)  # Não assinaria o manifesto novo
+        if checksum: builder.write_checksums(profile)
+        else: (self.paths.dist / "SHA256SUMS").unlink(missing_ok=True)  # Não deixa somas de um dist anterior
+        if archive:
+            output = create_archive(self.paths.dist, archive)
+            logger.step(f"Arquivo: {output.name} ({format_size(output.stat().st_size)})")
+            sums = output.with_name(output.name + ".sha256")
+            if checksum:
+                write_sums(sums, [output], output.parent)
+                logger.step(f"SHA-256: {sums.name}")
+            else: sums.unlink(missing_ok=True)
+        logger.success("Distribuição pronta!")
+
+    def signing_key(self) -> Path:
+        """Chave privada de [dist].signing_key (relativa à raiz); conferida antes de montar o dist."""
+        if not self.config.dist.signing_key:
+            raise ConfigError("--sign-manifest exige signing_key em [dist] no anvil.toml",
+                              "Ex: signing_key = \"keys/redstone-dist\" (ssh-keygen -t ed25519 -f keys/redstone-dist)")
+        key = self.paths.root / self.config.dist.signing_key
+        if not key.is_file(): raise ConfigError(f"Chave de assinatura não encontrada: {key}", "[dist].signing_key")
+        return key
+
+    def verify(self, check_signature: bool = False, public_key: Optional[Path] = None) -> int:
+        """
+        Confere o dist contra o manifest.json: cada artefato existe em dist/qemu com o tamanho e
+        o SHA-256 registrados. Com check_signature, valida também o manifest.json.sig com a chave
+        pública (padrão: [dist].verify_key ou signing_key + '.pub'). Retorna 1 se algo não bater.
+        """
+        logger.header("Verificação do Dist")
+        manifest_path = self.paths.dist_manifest
+        if not manifest_path.is_file():
+            raise ValidationError(f"{manifest_path} não existe; rode 'anvil dist' primeiro", str(manifest_path))
+        manifest = read_manifest(manifest_path)
+        problems = []
+        for entry in manifest.entries:
+            path = self.paths.dist_qemu / entry.dest
+            if not path.is_file(): problems.append(f"{entry.dest}: ausente")
+            elif path.stat().st_size != entry.size or sha256_file(path) != entry.sha256: problems.append(f"{entry.dest}: SHA-256 diferente")
+        if problems:
+            for problem in problems: logger.error(problem)
+        else: logger.success(f"{len(manifest.entries)} artefato(s) conferem com {manifest_path.name}")
+
+        if check_signature:
+            if public_key is None:
+                dist = self.config.dist
+                if not dist.verify_key and not dist.signing_key:
+                    raise ConfigError("--check-signature exige --key ou verify_key/signing_key em [dist]")
+                public_key = self.paths.root / (dist.verify_key or dist.signing_key + ".pub")
+            ok, message = verify_manifest(manifest_path, public_key, self.config.dist.signing_identity)
+            if ok: logger.success(f"Assinatura válida ({public_key.name})")
+            else:
+                problems.append("assinatura")
+                logger.error(f"Assinatura inválida para {public_key}")
+            if message: logger.step(message)
+        return 1 if problems else 0
+
+    async def watch(self, only: str = None, run: bool = False, debounce: float = DEFAULT_DEBOUNCE):
+        """
+        Refaz o build release (e o dist) a cada mudança nos fontes; only restringe quais componentes
+        disparam o rebuild. Com run, reinicia o QEMU após cada build bem-sucedido. Sai com Ctrl+C.
+        """
+        watcher = SourceWatcher(self.paths, self.config, only)
+        logger.header("Watch")
+        for d in watcher.dirs:
+            if d.is_dir(): logger.step(f"Observando {d.relative_to(self.paths.root)}")
+        qemu, cycle = None, 0
+        state = watcher.snapshot()
+        try:
+            while True:
+                cycle += 1
+                logger.raw(f"\n{Colors.BOLD}{Colors.YELLOW}{'─' * 20} Ciclo {cycle} ({time.strftime('%H:%M:%S')}) "
+                           f"{'─' * 20}{Colors.RESET}")
+                if qemu and not qemu.done():
+                    qemu.cancel()
+                    await asyncio.gather(qemu, return_exceptions=True)
+                self.report = BuildReport()
+                try: ok = await self.build_release()
+                except AnvilError as e:
+                    logger.error(str(e))
+                    ok = False
+                self.report.print(logger)
+                if not ok: logger.error("Build falhou; corrija e salve para tentar de novo")
+                elif run:
+                    options = QemuOptions(memory_mb=self.config.qemu.memory_mb, cpus=self.config.qemu.cpus)
+                    qemu = asyncio.create_task(self.run_qemu(options=options, interactive=False))
+                logger.info("Aguardando alterações (Ctrl+C encerra)...")
+                state = await watcher.wait_for_change(state, debounce)
+        finally:
+            if qemu and not qemu.done():
+                qemu.cancel()
+                await asyncio.gather(qemu, return_exceptions=True)
+
+    async def test(self, profile: str = "debug", timeout: int = 300, as_json: bool = False) -> int:
+        """
+        Compila o kernel com as features de teste, monta um dist mínimo (bootloader,
+        kernel e ignite.cfg) e boota headless; o resultado vem do isa-debug-exit.
+        Com as_json imprime no stdout o resumo da execução (o log vai para o stderr).
+        """
+        logger.header("Testes do Kernel")
+        await self._build_test(profile)
+        return await self._run_test(profile, timeout, as_json)
+
+    async def _build_test(self, profile: str) -> None:
+        features = self.config.components.kernel.test_features
+        if not await self.run_cargo("Kernel (testes)", self.paths.forge, profile=profile, features=features):
+            raise BuildError("Falha no build de testes do kernel", "test")
+        if not await self.run_cargo("Bootloader", self.paths.ignite, target=self.config.components.bootloader.target,
+                                    profile=profile):
+            raise BuildError("Falha no build do bootloader", "test")
+        DistBuilder(self.paths, self.config).prepare(profile=profile)
+
+    async def _run_test(self, profile: str, timeout: int, as_json: bool = False) -> int:
+        options = QemuOptions(profile=profile, test=True, test_timeout=timeout, display=False,
+                              memory_mb=self.config.qemu.memory_mb, cpus=self.config.qemu.cpus)
+        tail = collections.deque(maxlen=TEST_SERIAL_TAIL)
+        code = await self.run_qemu(options=options, interactive=False,
+                                   on_serial=lambda e: tail.append(e.line) if e.source == StreamSource.SERIAL else None)
+        if as_json: print(json.dumps(self._test_summary(code, profile, list(tail)), indent=2))
+        return code
+
+    async def test_watch(self, profile: str = "debug", timeout: int = 300, debounce: float = DEFAULT_DEBOUNCE):
+        """
+        'test --watch': refaz o build de testes e reinicia o QEMU a cada mudança nos fontes do
+        kernel (e das bibliotecas), encerrando antes o teste ainda em execução. Cada ciclo
+        termina numa linha de passou/falhou com o placar acumulado. Sai com Ctrl+C.
+        """
+        watcher = SourceWatcher(self.paths, self.config, "kernel")
+        logger.header("Testes do Kernel (watch)")
+        for d in watcher.dirs:
+            if d.is_dir(): logger.step(f"Observando {d.relative_to(self.paths.root)}")
+        tally = {"passed": 0, "failed": 0}
+        run, cycle = None, 0
+
+        def report(number: int, ok: bool, detail: str = "") -> None:
+            tally["passed" if ok else "failed"] += 1
+            mark = f"{Colors.GREEN}PASSOU" if ok else f"{Colors.RED}FALHOU"
+            logger.raw(f"{Colors.BOLD}Ciclo {number}: {mark}{Colors.RESET}{detail} "
+                       f"({tally['passed']} passaram, {tally['failed']} falharam)")
+
+        def finished(task: asyncio.Task, number: int) -> None:
+            if task.cancelled(): return
+            error = task.exception()
+            report(number, error is None and task.result() == 0, f" ({error})" if error else "")
+            logger.info("Aguardando alterações (Ctrl+C encerra)...")
+
+        state = watcher.snapshot()
+        try:
+            while True:
+                cycle += 1
+                logger.raw(f"\n{Colors.BOLD}{Colors.YELLOW}{'─' * 20} Ciclo {cycle} ({time.strftime('%H:%M:%S')}) "
+                           f"{'─' * 20}{Colors.RESET}")
+                if run and not run.done():
+                    logger.warning("Fontes alterados durante o teste; encerrando o QEMU anterior")
+                    run.cancel()
+                    await asyncio.gather(run, return_exceptions=True)
+                try: await self._build_test(profile)
+                except AnvilError as e:
+                    logger.error(str(e))
+                    report(cycle, False, " (build)")
+                    logger.info("Aguardando alterações (Ctrl+C encerra)...")
+                else:
+                    run = asyncio.create_task(self._run_test(profile, timeout))
+                    run.add_done_callback(lambda task, number=cycle: finished(task, number))
+                state = await watcher.wait_for_change(state, debounce)
+        finally:
+            if run and not run.done():
+                run.cancel()
+                await asyncio.gather(run, return_exceptions=True)
+            logger.info(f"Placar: {tally['passed']} passaram, {tally['failed']} falharam")
+
+    def _test_summary(self, code: int, profile: str, tail: list[str]) -> dict:
+        """Resumo do 'test --json': um binário (o kernel de testes) mais o status geral."""
+        result = self.qemu_result
+        status = "passed" if code == 0 else "timeout" if result.timed_out else "failed"
+        binary = {
+            "name": "kernel", "status": status, "duration_ms": result.runtime_ms,
+            "exit_code": result.exit_code, "timed_out": result.timed_out, "crashed": result.crashed,
+            "serial_tail": tail,
+        }
+        return {"status": status, "profile": profile, "exit_code": code, "tests": [binary]}
+
+    async def bench(self, timeout: int = 300, output: Optional[Path] = None, baseline: Optional[Path] = None,
+                    threshold: float = bench.DEFAULT_THRESHOLD) -> int:
+        """
+        Compila o kernel em release com as features de benchmark, boota headless e lê as
+        linhas 'BENCH <nome> <nanos>' da serial. Com baseline, retorna 1 se algum
+        benchmark ficou mais de threshold% mais lento.
+        """
+        logger.header("Benchmarks do Kernel")
+        # Caminhos relativos partem da raiz do projeto (o run.sh entra em anvil/)
+        output, baseline = (p if p is None or p.is_absolute() else self.paths.root / p for p in (output, baseline))
+        # Lida antes do build: uma baseline inválida não deve custar um boot inteiro
+        previous = bench.read_baseline(baseline) if baseline else None
+        features = self.config.components.kernel.bench_features
+        if not await self.run_cargo("Kernel (bench)", self.paths.forge, profile="release", features=features):
+            raise BuildError("Falha no build de benchmarks do kernel", "bench")
+        if not await self.run_cargo("Bootloader", self.paths.ignite, target=self.config.components.bootloader.target,
+                                    profile="release"):
+            raise BuildError("Falha no build do bootloader", "bench")
+        DistBuilder(self.paths, self.config).prepare(profile="release")
+        options = QemuOptions(profile="release", test=True, test_timeout=timeout, display=False,
+                              memory_mb=self.config.qemu.memory_mb, cpus=self.config.qemu.cpus)
+        collector = bench.BenchCollector()
+        code = await self.run_qemu(options=options, interactive=False, on_serial=collector.on_entry)
+        results = list(collector.results.values())
+        if not results:
+            raise BuildError("Nenhuma linha BENCH na serial", "bench",
+                             ["O kernel com as features de bench precisa imprimir 'BENCH <nome> <nanos>' e sair pelo isa-debug-exit"])
+
+        rows = bench.compare(results, previous)
+        logger.header("Resultados")
+        bench.print_table(rows, threshold, logger)
+        if output: logger.success(f"Resultados: {bench.write_results(results, output)}")
+        if previous:
+            for name in sorted(set(previous) - set(collector.results)): logger.warning(f"{name}: presente só na baseline")
+            regressions = [r for r in rows if r.regressed(threshold)]
+            if regressions:
+                logger.error(f"{len(regressions)} regressão(ões) acima de {threshold:g}%: {', '.join(r.result.name for r in regressions)}")
+                return 1
+            logger.success(f"Nenhuma regressão acima de {threshold:g}% em relação a {baseline}")
+        return code
+
+    async def build_pgo(self, timeout: int = 120):
+        """
+        Build do kernel guiada por perfil (PGO), em dois estágios:
+        1. Kernel instrumentado (-Cprofile-generate) roda no QEMU pelo workload do boot
+        2. Os .profraw são mesclados com llvm-profdata e o kernel é recompilado com -Cprofile-use
+
+        Pré-requisitos: 'rustup component add llvm-tools-preview' (llvm-profdata) e um kernel
+        que, ao final do workload, grave seus contadores em /pgo/*.profraw no disco FAT
+        (dist/qemu é montado com fat:rw) e desligue a VM.
+        """
+        logger.header("Build PGO (Kernel)")
+        profdata_tool = llvm_tool("llvm-profdata")
+        if not profdata_tool:
+            raise BuildError("llvm-profdata não encontrado (instale com: rustup component add llvm-tools-preview)", "pgo")
+
+        pgo_dir = self.paths.anvil_state / "pgo"
+        raw_dir = self.paths.dist_qemu / "pgo"
+        merged = pgo_dir / "merged.profdata"
+        for d in (pgo_dir, raw_dir):
+            if d.exists(): shutil.rmtree(d)
+            d.mkdir(parents=True)
+
+        logger.info("Estágio 1/2: kernel instrumentado")
+        if not await self.run_cargo("Kernel (instrumentado)", self.paths.forge, rustc_args=[f"-Cprofile-generate={raw_dir}"]):
+            raise BuildError("Falha no build instrumentado", "pgo")
+        await self.dist()
+
+        logger.info(f"Executando workload no QEMU (até {timeout}s)...")
+        monitor = QemuMonitor(self.paths, self.config, stop_on_exception=True, show_serial=True)
+        await monitor.run_monitored(timeout=timeout)
 
-    async def create_vdi(self):
+        profiles = sorted(raw_dir.glob("*.profraw"))
+        if not profiles:
+            raise BuildError(f"Nenhum .profraw gerado em {raw_dir}", "pgo",
+                             ["O kernel instrumentado precisa gravar os contadores em /pgo no disco FAT"])
+
+        p = await asyncio.create_subprocess_exec(
+            str(profdata_tool), "merge", "-o", str(merged), *map(str, profiles),
+            stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.STDOUT
+        )
+        out, _ = await p.communicate()
+        if p.returncode != 0:
+            raise BuildError("Falha no llvm-profdata merge", "pgo", out.decode(errors="replace").splitlines())
+        logger.step(f"{len(profiles)} perfil(is) mesclado(s) em {merged}")
+
+        logger.info("Estágio 2/2: kernel otimizado com o perfil")
+        if not await self.run_cargo("Kernel (PGO)", self.paths.forge, rustc_args=[f"-Cprofile-use={merged}"]):
+            raise BuildError("Falha no build com -Cprofile-use", "pgo")
+        shutil.rmtree(raw_dir, ignore_errors=True)
+        await self.dist()
+        logger.success("Build PGO concluída!")
+
+    def report_timings(self, open_reports: bool = False):
+        """Lista os relatórios de tempo gerados pelo cargo e, opcionalmente, abre no navegador."""
+        if not self.timing_reports:
+            logger.warning("Nenhum relatório de timings foi gerado.")
+            return
+
+        logger.header("Relatórios de Timings")
+        for name, report in self.timing_reports:
+            logger.step(f"{name}: {report}")
+            if open_reports: webbrowser.open(report.as_uri())
+
+    async def build_tools(self):
+        """Compila as ferramentas de host (crates fora do SO) para o triple do host."""
+        logger.header("Ferramentas de Host")
+        tools = await HostToolsDiscovery(self.paths, self.config).discover()
+        if not tools:
+            logger.warning("Nenhuma ferramenta de host encontrada no workspace.")
+            return
+
+        built = []
+        for tool in tools:
+            # Sem --target: compila para o host, não para um alvo bare-metal
+            rustflags = [f"-Ctarget-cpu={self.tools_target_cpu}"] if self.tools_target_cpu else None
+            if await self.run_cargo(tool.name, tool.path, rustflags=rustflags): built.append(tool.name)
+
+        logger.info(f"Ferramentas compiladas ({len(built)}/{len(tools)}): {', '.join(built) or 'nenhuma'}")
+        failed = [t.name for t in tools if t.name not in built]
+        if failed: raise BuildError(f"Build falhou em {len(failed)} de {len(tools)} ferramenta(s)", "tools", failed)
+
+    @staticmethod
+    def _configure_input(prompt: str) -> str:
+        """input() do configure: fim da entrada ou Ctrl-C cancelam sem salvar a seleção."""
+        try: return input(prompt).strip()
+        except (EOFError, KeyboardInterrupt):
+            print()
+            raise ConfigError("Configuração cancelada; nada foi salvo")
+
+    def _choose_recipe(self, current: Optional[str]) -> Optional[str]:
+        """Menu da receita ativa: ENTER mantém a atual, 0 volta a todos os componentes."""
+        recipes, _ = list_recipes(self.paths.recipes)
+        print(f"\n{Colors.BOLD}Receita{Colors.RESET} {Colors.GREY}(atual: {current or 'nenhuma'}){Colors.RESET}")
+        print(f" {Colors.CYAN}[0]{Colors.RESET} {'nenhuma':<20} {Colors.GREY}Todos os componentes do anvil.toml{Colors.RESET}")
+        for i, r in enumerate(recipes, 1):
+            print(f" {Colors.CYAN}[{i}]{Colors.RESET} {r.name:<20} {Colors.GREY}{r.description}{Colors.RESET}")
+        while True:
+            choice = self._configure_input(f"\n{Colors.BOLD}Receita (ENTER mantém) > {Colors.RESET}")
+            if not choice: return current
+            if choice.isdigit() and 0 <= int(choice) <= len(recipes):
+                return recipes[int(choice) - 1].name if int(choice) else None
+
+    async def configure(self):
+        """Interface interativa para escolher a receita ativa e ativar/desativar features de cada componente."""
+        logger.header("Configuração do Build")
+        if not sys.stdin.isatty():
+            raise ConfigError("'anvil configure' exige um terminal interativo",
+                              f"Em scripts, use --recipe ou edite {self.paths.selection_file}")
+        selection = self.selection()
+        selection.recipe = self._choose_recipe(selection.recipe)
+        comps = self.config.components
+        components = [
+            ("Kernel", self.paths.root / comps.kernel.path),
+            ("Bootloader", self.paths.root / comps.bootloader.path),
+            *[(item.name, self.paths.root / item.path) for item in [*comps.services, *comps.apps]],
+        ]
+
+        # Apenas componentes que declaram [features] no Cargo.toml
+        available = []
+        for name, path in components:
+            manifest = path / "Cargo.toml"
+            if not manifest.exists(): continue
+            try: features = [f for f in toml.load(manifest).get("features", {}) if f != "default"]
+            except Exception: continue
+            if features: available.append((name, path, features))
+
+        if not available: logger.warning("Nenhum componente declara features no Cargo.toml.")
+        while available:
+            print()
+            for i, (name, path, _) in enumerate(available, 1):
+                active = selection.features_for(self._component_key(path))
+                print(f" {Colors.CYAN}[{i}]{Colors.RESET} {name:<20} {Colors.GREY}{', '.join(active) or '-'}{Colors.RESET}")
+            choice = self._configure_input(f"\n{Colors.BOLD}Componente (ENTER salva e sai) > {Colors.RESET}")
+            if not choice: break
+            if not choice.isdigit() or not 1 <= int(choice) <= len(available): continue
+
+            name, path, features = available[int(choice) - 1]
+            key = self._component_key(path)
+            active = set(selection.features_for(key))
+            while True:
+                print(f"\n{Colors.BOLD}{name}{Colors.RESET}")
+                for i, feat in enumerate(features, 1):
+                    mark = f"{Colors.GREEN}x{Colors.RESET}" if feat in active else " "
+                    print(f" [{mark}] {Colors.CYAN}{i}{Colors.RESET} {feat}")
+                toggle = self._configure_input(f"{Colors.BOLD}Alternar feature (ENTER volta) > {Colors.RESET}")
+                if not toggle: break
+                if toggle.isdigit() and 1 <= int(toggle) <= len(features):
+                    active ^= {features[int(toggle) - 1]}
+            selection.features[key] = sorted(active)
+
+        selection.save(self.paths.selection_file)
+        logger.success(f"Seleção salva em {self.paths.selection_file} (receita: {selection.recipe or 'nenhuma'})")
+
+    async def create_vdi(self, keep_dist: bool = False, verify_boot: bool = False,
+                         size_mb: int = None, slack_mb: int = 16):
         """Cria imagem de disco VDI para VirtualBox."""
         logger.header("Criando VDI")
         builder = ImageBuilder(self.paths, self.config, log=logger)
-        await builder.build_vdi(profile="release")
+        vdi_path = await builder.build_vdi(profile="release", keep_intermediate=keep_dist,
+                                           size_mb=size_mb, slack_mb=slack_mb)
+
+        if verify_boot:
+            if not await QemuRunner(self.paths, self.config).boot_check(vdi_path, "vdi"):
+                raise RunError(f"Imagem não bootou: banner '{BOOTLOADER_BANNER}' não apareceu na serial", stderr=str(vdi_path))
+            logger.success("Boot verificado: o firmware carregou o BOOTX64.EFI")
+
+    async def create_iso(self, output: Path = None, keep_dist: bool = False, hybrid: bool = False,
+                         checksum: bool = True, verify_boot: bool = False):
+        """Cria ISO bootável (UEFI, ou BIOS+UEFI com hybrid) a partir de dist/qemu; com verify_boot, boota a ISO como CD-ROM."""
+        iso_path = await IsoBuilder(self.paths, self.config, log=logger).build_iso(
+            output=output, keep_intermediate=keep_dist, hybrid=hybrid, checksum=checksum)
+
+        if verify_boot:
+            if not await QemuRunner(self.paths, self.config).boot_check(iso_path, "iso"):
+                raise RunError(f"ISO não bootou: banner '{BOOTLOADER_BANNER}' não apareceu na serial", stderr=str(iso_path))
+            logger.success("Boot verificado: o firmware carregou o BOOTX64.EFI da ISO")
+
+    def select_usb_device(self, device: str = None, all_disks: bool = False) -> UsbDevice:
+        """Disco de destino: o informado em --device ou escolhido numa lista numerada."""
+        devices = list_usb_devices(all_disks)
+        if device:
+            found = next((d for d in devices if d.path == device), None)
+            if found: return found
+            if not all_disks and any(d.path == device for d in list_usb_devices(all_disks=True)):
+                raise ValidationError(f"{device} não é removível; use --all-disks se tiver certeza", device)
+            raise ValidationError(f"Disco não encontrado: {device}", device)
+
+        if not devices:
+            raise ValidationError("Nenhum disco removível encontrado" + ("" if all_disks else " (--all-disks lista todos)"), "usb")
+        logger.header("Discos disponíveis")
+        for i, d in enumerate(devices, 1): logger.raw(f"  [{i}] {d.describe()}")
+        choice = input("Número do disco (ENTER cancela) > ").strip()
+        if not choice.isdigit() or not 1 <= int(choice) <= len(devices):
+            raise ValidationError("Nenhum disco escolhido", "usb")
+        return devices[int(choice) - 1]
+
+    def confirm_usb_write(self, target: UsbDevice, reformat: bool = True) -> None:
+        """Exige digitar o caminho do disco (ou 'yes') antes de apagá-lo ou sobrescrevê-lo; sem TTY, só com --yes."""
+        if reformat: logger.warning(f"TODOS os dados de {target.path} serão apagados!")
+        else: logger.warning(f"Os arquivos do dist em {target.path} serão sobrescritos (os demais ficam)")
+        logger.step(f"Modelo: {target.model or '(sem modelo)'}")
+        logger.step(f"Tamanho: {format_size(target.size)}")
+        if not sys.stdin.isatty():
+            raise ValidationError("Confirmação exige um terminal interativo; use --yes em scripts", target.path)
+        answer = input(f"Digite '{target.path}' ou 'yes' para continuar > ").strip()
+        if answer not in (target.path, "yes"):
+            raise ValidationError("Gravação cancelada", target.path)
+
+    async def usb(self, device: str = None, all_disks: bool = False, yes: bool = False, dry_run: bool = False,
+                  verify: bool = False, reformat: bool = True):
+        """Grava dist/qemu num pendrive; sem reformat, copia sobre o FAT32 que já existe nele."""
+        target = self.select_usb_device(device, all_disks)
+        logger.info(f"Destino: {target.describe()}")
+        if dry_run:
+            logger.header("Gravação (simulação)")
+            if reformat: logger.step(f"Formatar {target.path} como FAT32")
+            else: logger.step(f"Conferir o FAT32 existente em {target.path} (sem formatar)")
+            logger.step(f"Copiar {self.paths.dist_qemu} ({format_size(dir_size(self.paths.dist_qemu))})")
+            if verify: logger.step("Reler e conferir o SHA-256 de cada arquivo")
+            logger.info("Nada foi alterado")
+            return
+        if not yes: self.confirm_usb_write(target, reformat)
+        UsbWriter(self.paths, self.config, logger).write(target, verify=verify, reformat=reformat)
+
+    async def run_qemu(self, gdb=False, options: QemuOptions = None, interactive: bool = True, on_serial=None) -> int:
+        """
+        Inicia QEMU com monitoramento e retorna o código de saída (1 em caso de crash).
+        Fora do menu, dist incompleto é erro em vez de pergunta. on_serial recebe cada
+        LogEntry capturada (ex: o coletor do 'anvil bench').
+        """
+        options = options or QemuOptions(memory_mb=self.config.qemu.memory_mb, cpus=self.config.qemu.cpus)
+        if gdb and options.gdb_port is None: options.gdb_port = GDB_DEFAULT_PORT
+        options.validate()
+        # Falha aqui, com a lista de locais procurados, e não dentro do monitor
+        if not options.firmware_code: find_ovmf(self.paths.root, self.config.arch)
 
-    async def run_qemu(self, gdb=False):
-        """Inicia QEMU com monitoramento."""
         # Verificação rápida se existe algo bootável
-        boot_efi = self.paths.dist_qemu / "EFI" / "BOOT" / "BOOTX64.EFI"
-        if not boot_efi.exists():
-            logger.warning("Bootloader não encontrado! Você rodou a opção [1] Release?")
-            if input("Continuar mesmo assim? (s/N) > ").lower() != "s": return
+        if not self.paths.dist_qemu.is_dir():
+            raise ValidationError(f"{self.paths.dist_qemu} não existe; rode 'anvil dist' primeiro", str(self.paths.dist_qemu))
+        missing = [rel for rel in (self.config.artifact_dest("bootloader", options.profile),
+                                   self.config.artifact_dest("kernel", options.profile))
+                   if not (self.paths.dist_qemu / rel).exists()]
+        if missing:
+            if not interactive:
+                raise ValidationError(f"Faltando em dist/qemu: {', '.join(missing)} (rode 'anvil dist --profile {options.profile}')",
+                                      str(self.paths.dist_qemu))
+            logger.warning(f"Faltando em dist/qemu: {', '.join(missing)}. Você rodou a opção [1] Release?")
+            if input("Continuar mesmo assim? (s/N) > ").lower() != "s": return 1
+
+        for spec in options.new_disks: create_disk(*parse_new_disk(spec), logger)
 
+        if options.initrd:
+            # O QEMU só aceita -initrd com -kernel; no boot UEFI quem carrega o módulo é o Ignite
+            shutil.copy2(options.initrd, self.paths.dist_qemu / "boot" / "initrd")
+            options.boot_set.setdefault("module_path", "boot():/boot/initrd")
+            logger.info(f"Initrd: {options.initrd} -> boot/initrd")
+
+        if options.boot_set:
+            content = DistBuilder(self.paths, self.config).update_ignite_cfg(options.boot_set)
+            logger.debug(f"ignite.cfg:\n{content}")
+
+        if options.gdb_port:
+            kernel = self.paths.kernel_binary(options.profile)
+            logger.info(f"gdb stub em tcp::{options.gdb_port}; a CPU espera o gdb. Conecte com:")
+            logger.raw(f'    gdb {kernel} -ex "target remote :{options.gdb_port}"')
+            if options.gdb_timeout:
+                then = "a CPU continua sozinha" if options.gdb_timeout_action == "continue" else "o QEMU é encerrado"
+                logger.step(f"Sem debugger em {options.gdb_timeout}s, {then}")
+
+        if not options.serial and not options.serial_log and options.headless:
+            logger.warning("Sem serial e sem display: nenhuma saída do guest será visível")
         logger.header("Inicializando QEMU")
+        logger.step(f"Memória: {options.memory_mb} MiB, CPUs: {options.cpus}")
         try:
             # cfg = QemuConfig(...) -> Removido pois config agora é hardcoded no runner
-            monitor = QemuMonitor(self.paths, self.config, stop_on_exception=True, show_serial=True)
+            monitor = QemuMonitor(self.paths, self.config, stop_on_exception=True, show_serial=True, options=options)
+            if on_serial: monitor.capture.add_callback(on_serial)
             
             # Callback para imprimir linhas seriais coloridas foi removido pois show_serial=True já faz isso
             # via logger.raw() que agora tem flush=True
             
-            result = await monitor.run_monitored()
+            timeout = options.test_timeout if options.test else options.boot_timeout
+            result = self.qemu_result = await monitor.run_monitored(timeout=timeout)
             if result.crashed: logger.error(f"CRASH Detectado: {result.crash_info}")
         finally: logger.header("QEMU Finalizado")
+        if options.test: return self._test_status(result, options.test_timeout)
+        if options.boot_timeout: return self._boot_status(result, options)
+        if result.exit_code: logger.info(f"QEMU saiu com código {result.exit_code}")
+        return result.exit_code or (1 if result.crashed else 0)
+
+    def _boot_status(self, result, options: QemuOptions) -> int:
+        """Resultado do --boot-timeout: só o marcador de boot na serial é sucesso."""
+        if result.boot_status == "booted":
+            logger.success(f"Boot concluído ('{options.boot_marker}' na serial)")
+            return 0
+        if result.boot_status == "panic": logger.error(f"Pânico do kernel ('{options.panic_marker}' na serial)")
+        elif result.crashed: logger.error("Exceção de CPU antes do fim do boot")
+        elif result.timed_out: logger.error(f"Nenhum marcador na serial em {options.boot_timeout}s")
+        else: logger.error(f"QEMU saiu com código {result.exit_code} antes de '{options.boot_marker}'")
+        return 1
+
+    def _test_status(self, result, timeout: int) -> int:
+        """Traduz o código do isa-debug-exit: TEST_SUCCESS_CODE é sucesso, o resto é falha."""
+        if result.timed_out:
+            logger.error(f"Teste excedeu {timeout}s e foi encerrado")
+            return 1
+        if result.exit_code == TEST_SUCCESS_CODE:
+            logger.success("Teste passou")
+            return 0
+        if result.exit_code is not None and result.exit_code & 1:
+            logger.error(f"Teste falhou (kernel escreveu {result.exit_code >> 1:#x} na porta {TEST_EXIT_PORT:#x})")
+        else:
+            logger.error(f"Teste falhou (QEMU saiu com {result.exit_code} sem passar pelo isa-debug-exit)")
+        return result.exit_code if result.exit_code and 0 < result.exit_code < 256 else 1
+
+    def env(self, export: bool = False, shell: str = "bash", as_json: bool = False, as_event: bool = False) -> int:
+        """
+        Ferramentas e targets necessários, mais as variáveis de ambiente usadas pelo Anvil
+        e pelo build (OVMF, llvm-tools no PATH), e se o host acelera o guest de --arch.
+        Retorna 1 se faltar algo obrigatório.
+        Com as_event ('--json' global) o relatório vira um evento 'env'.
+        """
+        variables, path_prepend = {}, []
+        try: variables["ANVIL_OVMF"] = str(find_ovmf(self.paths.root, self.config.arch))
+        except ValidationError: pass
+        root, host = sysroot(), host_triple()
+        if root and host and (root / "lib" / "rustlib" / host / "bin").is_dir():
+            path_prepend.append(str(root / "lib" / "rustlib" / host / "bin"))
+
+        if export:
+            # Só os comandos no stdout, para uso com eval
+            for line in export_lines(variables, path_prepend, shell): print(line)
+            return 0
+
+        report = EnvReport.collect(self.config.arch)
+        arch = self.config.arch
+        accel = {"arch": arch, "host_arch": host_arch(), "hw_accel_possible": hw_accel_possible(arch)}
+        data = {**report.to_dict(), "variables": variables, "path_prepend": path_prepend, "acceleration": accel}
+        if as_event:
+            events.emit("env", **data)
+            return 0 if report.ok else 1
+        if as_json:
+            print(json.dumps(data, indent=2))
+            return 0 if report.ok else 1
+
+        logger.header("Ferramentas")
+        for tool in report.tools:
+            if tool.found: logger.success(f"{tool.name}: {tool.version or tool.path}")
+            elif tool.required:
+                logger.error(f"{tool.name} não encontrado")
+                logger.step(f"Instale: {tool.hint}")
+            else:
+                logger.warning(f"{tool.name} não encontrado (opcional)")
+                logger.step(f"Instale: {tool.hint}")
+        if report.installed_targets is None:
+            logger.error("rustup não encontrado; não foi possível conferir os targets")
+        else:
+            for target in report.required_targets:
+                if target not in report.missing_targets: logger.success(f"target {target}")
+                else:
+                    logger.error(f"target {target} não instalado")
+                    logger.step(f"Instale: rustup target add {target}")
+
+        logger.header("Ambiente")
+        logger.step(f"ANVIL_OVMF = {variables.get('ANVIL_OVMF', '(OVMF não encontrado)')}")
+        logger.step(f"PATH += {', '.join(path_prepend) or '(llvm-tools-preview não instalado)'}")
+        if accel["hw_accel_possible"]: logger.success(f"Aceleração por hardware possível: host {accel['host_arch']} = alvo {arch}")
+        else: logger.warning(f"Sem aceleração por hardware: host {accel['host_arch']} ≠ alvo {arch}; 'anvil run' usará TCG (lento)")
+        logger.info('Para aplicar no shell: eval "$(./run.sh env --export)"')
+        return 0 if report.ok else 1
+
+    def bootstrap(self, dry_run: bool = False) -> int:
+        """
+        Instala os targets e componentes do rustup que faltam no toolchain do projeto
+        e mostra como instalar QEMU/xorriso. O que já existe é pulado, então pode
+        ser repetido; com dry_run só lista os comandos.
+        """
+        logger.header("Toolchain Rust")
+        plan = bootstrap_plan(self.paths.root, self.config.arch)
+        if plan is None:
+            rustup_hint = next(t[4] for t in TOOLS if t[0] == "rustc")
+            raise BuildError("rustup não encontrado", "bootstrap", [f"Instale: {rustup_hint}"])
+        failed = []
+        for step in plan:
+            if step.present:
+                logger.success(f"{step.kind} {step.name} já instalado")
+                continue
+            if dry_run:
+                logger.step(f"Executaria: {' '.join(step.command)}")
+                continue
+            logger.info(f"Instalando {step.kind} {step.name}...")
+            p = subprocess.run(step.command, cwd=self.paths.root, capture_output=True, text=True)
+            if p.returncode == 0:
+                logger.success(f"{step.kind} {step.name} instalado")
+                continue
+            logger.error(f"Falha: {' '.join(step.command)}")
+            detail = (p.stderr or p.stdout).strip().splitlines()
+            failed.append(f"{step.name}: {detail[-1] if detail else f'código {p.returncode}'}")
+
+        logger.header("Ferramentas do sistema")
+        # rustc e cargo já vieram com o rustup
+        tools = [t for t in check_tools(self.config.arch) if t.name not in ("rustc", "cargo")]
+        for tool in tools:
+            if tool.found: logger.success(f"{tool.name}: {tool.version or tool.path}")
+        missing = [t for t in tools if not t.found]
+        if missing:
+            logger.warning(f"Faltando: {', '.join(t.name for t in missing)}. Instale com o gerenciador de pacotes:")
+            for hint in system_package_hints(): logger.step(hint)
+        if failed: raise BuildError(f"Falha ao instalar {len(failed)} item(ns) do rustup", "bootstrap", failed)
+        if dry_run: logger.info("Nada foi instalado (--dry-run)")
+        elif not missing: logger.success("Ambiente pronto")
+        return 0
+
+    def recipe_list(self):
+        """Receitas que carregam (recipes/*.toml e as compiladas no Anvil), indicando a origem."""
+        logger.header("Receitas")
+        if not self.paths.recipes.is_dir(): logger.info(f"{self.paths.recipes} não existe; só as receitas embutidas:")
+        recipes, errors = list_recipes(self.paths.recipes)
+        for e in errors: logger.warning(str(e))
+        if not recipes: logger.info(f"Nenhuma receita em {self.paths.recipes}")
+        width = max((len(r.name) for r in recipes), default=0)
+        for r in recipes:
+            count = f" ({len(r.packages)} pacotes)" if logger.verbose else ""
+            source = "embutida" if r.builtin else f"recipes/{r.name}.toml"
+            logger.step(f"{r.name:<{width}}  {r.description}{count} {Colors.GREY}({source}){Colors.RESET}")
+
+    def recipe_validate(self, name: Optional[str] = None) -> int:
+        """
+        Confere uma receita (ou todas: recipes/ e as embutidas) e lista os problemas de cada
+        uma; 1 se alguma for inválida. Sem recipes/, valem as embutidas.
+        """
+        logger.header("Validação de Receitas")
+        if not self.paths.recipes.is_dir(): logger.info(f"{self.paths.recipes} não existe; só as receitas embutidas")
+        names = [name] if name else sorted(recipe_names(self.paths.recipes))
+        if builtin_targets() is None: logger.warning("rustc não encontrado; os triples de [targets] não foram conferidos")
+        invalid = 0
+        for recipe in names:
+            problems = validate_recipe(self.paths.recipes, recipe, self.config, self.paths.root)
+            if not problems:
+                logger.success(recipe)
+                continue
+            invalid += 1
+            logger.error(f"{recipe}: {len(problems)} problema(s)")
+            for problem in problems: logger.step(problem)
+        if invalid:
+            logger.error(f"{invalid} de {len(names)} receita(s) inválida(s)")
+            return 1
+        return 0
+
+    def default_target(self, package: str) -> Optional[str]:
+        """Target do anvil.toml para um componente ('kernel', 'bootloader' ou nome de serviço/app)."""
+        comps = self.config.components
+        if package in ("kernel", "bootloader"): return getattr(comps, package).target
+        return next((c.target for c in [*comps.services, *comps.apps] if c.name == package), None)
+
+    def recipe_show(self, name: str, as_json: bool = False):
+        """Mostra uma receita já interpretada (tabela ou JSON)."""
+        if not (self.paths.recipes / f"{name}.toml").is_file() and name not in BUILTIN_RECIPE_DATA:
+            available = [r.name for r in list_recipes(self.paths.recipes)[0]]
+            raise ConfigError(f"Receita '{name}' não encontrada em {self.paths.recipes}",
+                              f"Disponíveis: {', '.join(available) or '(nenhuma)'}")
+        recipe = RecipeSpec.load(self.paths.recipes, name)
+        if as_json:
+            print(json.dumps(dataclasses.asdict(recipe), indent=2, ensure_ascii=False))
+            return
+        logger.header(f"Receita: {recipe.name}")
+        print(f"  {Colors.BOLD}{'Descrição':<10}{Colors.RESET} {recipe.description}")
+        print(f"  {Colors.BOLD}{'Perfil':<10}{Colors.RESET} {recipe.profile}")
+        if recipe.extends: print(f"  {Colors.BOLD}{'Estende':<10}{Colors.RESET} {recipe.extends}")
+        if recipe.builtin: print(f"  {Colors.BOLD}{'Origem':<10}{Colors.RESET} embutida (sem recipes/{recipe.name}.toml)")
+        if recipe.programs is not None: print(f"  {Colors.BOLD}{'Initfs':<10}{Colors.RESET} {', '.join(recipe.programs) or '-'}")
+        print(f"\n  {Colors.BOLD}{'Pacote':<20} Target{Colors.RESET}")
+        for package in recipe.packages:
+            # Amarelo: target da receita; cinza: do anvil.toml
+            target = recipe.targets.get(package)
+            shown = f"{Colors.YELLOW}{target}{Colors.RESET}" if target else \
+                f"{Colors.GREY}{self.default_target(package) or '?'}{Colors.RESET}"
+            print(f"  {Colors.CYAN}{package:<20}{Colors.RESET} {shown}")
+
+    def template_list(self):
+        """Modelos de anvil/templates com descrição e destino."""
+        logger.header("Modelos")
+        templates = list_templates(self.paths.templates)
+        if not templates: logger.info(f"Nenhum modelo em {self.paths.templates}")
+        width = max((len(t.kind) for t in templates), default=0)
+        for t in templates:
+            dest = f"{t.dest_dir}/" if t.dest_dir else "(sem destino)"
+            logger.step(f"{t.kind:<{width}}  {dest:<12} {t.description or '-'}")
+
+    def template_new(self, kind: str, name: str) -> Path:
+        """Cria um crate a partir de anvil/templates/<kind>."""
+        dest = Template.find(self.paths.templates, kind).create(self.paths.root, name)
+        member = dest.relative_to(self.paths.root).as_posix()
+        logger.success(f"Crate criado: {member}")
+        manifest = self.paths.root / "Cargo.toml"
+        if manifest.exists() and add_workspace_member(manifest, member):
+            logger.step(f"Registrado em [workspace] members de {manifest.name}")
+        else:
+            logger.warning(f"[workspace] members não encontrado em {manifest}; adicione \"{member}\" manualmente")
+        return dest
 
     async def listen_serial(self):
         """Modo standalone de escuta serial."""
@@ -277,17 +1383,83 @@ class AnvilCLI:
         print(f"{Colors.CYAN}{'TOTAL':<20} | {grand_files:<10} | {grand_code:<15,}{Colors.RESET}")
         logger.success("Concluído.")
 
-    async def clean(self):
-        """Limpa diretórios de build (target e dist)."""
-        for p in [self.paths.forge/"target", self.paths.ignite/"target", self.paths.dist]:
-            if p.exists(): 
-                try: 
-                    shutil.rmtree(p)
-                    logger.step(f"Removido {p}")
-                except Exception as e:
-                    logger.error(f"Não foi possível remover {p}: {e}")
+    async def clean_packages(self, target: str):
+        """'cargo clean -p' só nos pacotes do alvo; o resto do cache de build fica intacto."""
+        logger.header(f"Limpeza: {target}")
+        failed = False
+        groups = ["services", "apps"] if target == "userspace" else [target]
+        # O kernel usa o target do .cargo/config.toml do forge, que o cargo clean também lê
+        for name, path, triple in [job for g in groups for job in self.component_jobs(g)]:
+            if not (path / "Cargo.toml").exists(): continue
+            cmd = ["cargo", "clean", "-p", crate_name(path), *(["--target", resolve_target(triple, self.paths.root)] if triple else [])]
+            logger.debug(f"Comando: {' '.join(cmd)}")
+            p = await asyncio.create_subprocess_exec(
+                *cmd, cwd=path, stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.STDOUT
+            )
+            out, _ = await p.communicate()
+            if p.returncode != 0:
+                failed = True
+                logger.error(f"cargo clean -p falhou em {name}: {out.decode(errors='replace').strip()}")
+            else: logger.step(f"{name} ({crate_name(path)})")
+        # As impressões digitais não valem mais para os pacotes limpos
+        self.paths.fast_check_file.unlink(missing_ok=True)
+        if failed: raise BuildError(f"Falha ao limpar {target}", "clean")
         logger.success("Limpo!")
 
+    def _clean_targets(self, everything: bool = False) -> list[Path]:
+        """Caminhos removidos pelo clean; com everything também cache/ e ISOs geradas."""
+        targets = [self.paths.root / "target", self.paths.forge / "target", self.paths.ignite / "target", self.paths.dist]
+        if everything:
+            targets.append(self.paths.cache)
+            targets += sorted(p for pattern in ("*.iso", "*.iso.sha256") for p in self.paths.root.glob(pattern))
+        safe = []
+        for p in targets:
+            if not p.exists(): continue
+            # Nunca remove nada fora do projeto (ex: symlink para outro disco)
+            resolved = p.resolve()
+            if resolved == self.paths.root or self.paths.root not in resolved.parents:
+                logger.error(f"Ignorado (fora do projeto): {p} -> {resolved}")
+                continue
+            safe.append(p)
+        return safe
+
+    async def clean(self, dry_run: bool = False, everything: bool = False):
+        """Roda 'cargo clean' no workspace e remove target/ e dist/ (com everything, também cache/ e ISOs)."""
+        targets = self._clean_targets(everything)
+        sizes = {p: dir_size(p) for p in targets}
+        total = sum(sizes.values())
+
+        if dry_run:
+            logger.header("Limpeza (simulação)")
+            for p in targets: logger.step(f"{p} ({format_size(sizes[p])})")
+            logger.info(f"Espaço recuperável: {format_size(total)} (nada foi removido)")
+            return
+
+        logger.header("Limpeza")
+        if (self.paths.root / "Cargo.toml").exists() and shutil.which("cargo"):
+            p = await asyncio.create_subprocess_exec(
+                "cargo", "clean", cwd=self.paths.root, stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.STDOUT
+            )
+            out, _ = await p.communicate()
+            if p.returncode != 0: logger.warning(f"cargo clean falhou: {out.decode(errors='replace').strip()}")
+            elif logger.verbose: logger.step("cargo clean (workspace)")
+
+        reclaimed = 0
+        for p in targets:
+            if not p.exists():
+                # Já removido pelo cargo clean
+                reclaimed += sizes[p]
+                continue
+            try:
+                if p.is_dir(): shutil.rmtree(p)
+                else: p.unlink()
+                reclaimed += sizes[p]
+                if logger.verbose: logger.step(f"Removido {p} ({format_size(sizes[p])})")
+            except Exception as e:
+                logger.error(f"Não foi possível remover {p}: {e}")
+        self.paths.fast_check_file.unlink(missing_ok=True)
+        logger.success(f"Limpo! {format_size(reclaimed)} recuperados")
+
 def getch():
     """Captura uma tecla do terminal (compatível com Linux/Unix)."""
     fd = sys.stdin.fileno()
@@ -301,13 +1473,487 @@ def getch():
 
 def clear_screen():
     """Limpa a tela do terminal."""
-    import os
     os.system('cls' if os.name == 'nt' else 'clear')
 
-async def main():
+def build_parser() -> argparse.ArgumentParser:
+    """Define os subcomandos aceitos na linha de comando (sem argumentos abre o menu)."""
+    parser = argparse.ArgumentParser(prog="anvil", description="Anvil - RedstoneOS Builder")
+    verbosity = parser.add_mutually_exclusive_group()
+    verbosity.add_argument("-v", "--verbose", action="store_true", help="Mostra comandos executados e detalhes extras")
+    verbosity.add_argument("-q", "--quiet", action="store_true",
+                           help="Mostra apenas erros (a saída do cargo só aparece se ele falhar)")
+    parser.add_argument("--color", choices=COLOR_MODES, default="auto",
+                        help="Cores ANSI: auto (só em terminal e sem NO_COLOR), always ou never (padrão: auto)")
+    parser.add_argument("--config", type=Path, metavar="PATH",
+                        help="Usa este anvil.toml em vez de procurar nos locais padrão (ex: matrizes de CI)")
+    parser.add_argument("--reproducible", action="store_true",
+                        help="Build reproduzível: SOURCE_DATE_EPOCH, cargo --locked, datas e ordem fixas no dist/initfs/imagem")
+    parser.add_argument("--arch", choices=ARCHES, default=DEFAULT_ARCH,
+                        help="Arquitetura do guest: troca os targets x86_64-* do anvil.toml, o BOOTX64.EFI e o QEMU "
+                             "(aarch64: qemu-system-aarch64 -machine virt -cpu cortex-a72) (padrão: x86_64)")
+    machine = parser.add_mutually_exclusive_group()
+    machine.add_argument("--event-stream", nargs="?", const="stderr", metavar="FD",
+                         help="Emite eventos de progresso em JSON (uma linha por evento) no descritor FD ou no stderr")
+    machine.add_argument("--json", dest="json_events", action="store_true",
+                         help="Troca a saída colorida por eventos JSON no stdout (erros continuam no stderr)")
+    sub = parser.add_subparsers(dest="command")
+
+    run = sub.add_parser("run", help="Executa o sistema no QEMU")
+    run.add_argument("--rtc-base", default="utc", metavar="<utc|localtime|YYYY-MM-DDTHH:MM:SS>",
+                     help="Base do relógio do guest; um horário fixo torna testes de boot reproduzíveis (padrão: utc)")
+    run.add_argument("--accel", default="auto", choices=ACCELERATORS,
+                     help="Acelerador do QEMU; 'auto' detecta o melhor do host (padrão: auto)")
+    run.add_argument("--kvm", dest="accel", action="store_const", const="kvm",
+                     help="Atalho para --accel kvm")
+    run.add_argument("--firmware-code", type=Path, metavar="FILE",
+                     help="Firmware UEFI (CODE) a usar em vez do OVMF detectado")
+    run.add_argument("--firmware-vars", type=Path, metavar="FILE",
+                     help="Arquivo de variáveis UEFI (VARS); uma cópia em dist/ é usada pelo guest")
+    run.add_argument("--trace", metavar="EVENTS",
+                     help="Eventos de trace do QEMU (globs separados por vírgula, ex: 'pci_*,virtio_*', "
+                          "ou arquivo com um evento por linha); saída em .anvil/logs/qemu-trace.log")
+    run.add_argument("--set", dest="boot_set", action="append", default=[], metavar="KEY=VALUE",
+                     help="Grava KEY=VALUE no ignite.cfg do dist antes do boot (ex: --set cmdline=quiet); repetível")
+    run.add_argument("--initrd", type=Path, metavar="FILE",
+                     help="Usa FILE como initramfs sem refazer o dist (copiado para boot/initrd; "
+                          "o ignite.cfg aponta para ele até o próximo 'anvil dist')")
+    run.add_argument("--gdb", action="store_true",
+                     help="Abre o gdb stub do QEMU e para a CPU no início até o gdb conectar")
+    run.add_argument("--gdb-port", type=int, default=GDB_DEFAULT_PORT, metavar="PORT",
+                     help=f"Porta TCP do gdb stub (padrão: {GDB_DEFAULT_PORT}); útil para várias instâncias")
+    run.add_argument("--gdb-timeout", type=int, metavar="SECS",
+                     help="Com --gdb, se nenhum debugger conectar em SECS segundos, continua a execução "
+                          "(ou aborta, com --gdb-timeout-action); padrão: espera para sempre")
+    run.add_argument("--gdb-timeout-action", choices=GDB_TIMEOUT_ACTIONS, default="continue",
+                     help="O que fazer ao fim do --gdb-timeout: continue (destrava a CPU) ou abort (encerra o QEMU)")
+    run.add_argument("--serial", action=argparse.BooleanOptionalAction, default=True,
+                     help="Mostra a serial do guest no terminal (padrão; --no-serial desliga)")
+    run.add_argument("--serial-log", type=Path, metavar="PATH",
+                     help="Grava também a serial do guest em PATH (truncado a cada boot), para anexar a bug reports")
+    display = run.add_mutually_exclusive_group()
+    display.add_argument("--display", dest="display_mode", metavar="MODE",
+                         help=f"Frontend de vídeo: {', '.join(DISPLAYS)} (vnc:N abre o VNC na porta 5900+N; "
+                              "padrão: gtk, ou cocoa no macOS)")
+    display.add_argument("--no-display", dest="display", action="store_false",
+                         help="Sem janela gráfica (o mesmo que --display none); com a serial ligada serve para smoke tests em CI")
+    run.add_argument("--memory", type=int, metavar="MB", help="Memória do guest (padrão: [qemu] memory_mb ou 512, mínimo 64)")
+    run.add_argument("--cpus", type=int, metavar="N", help="Número de CPUs do guest, 1-255 (padrão: [qemu] cpus ou 1)")
+    run.add_argument("--test", action="store_true",
+                     help="Modo de teste headless: isa-debug-exit na porta 0xf4; código 33 do QEMU vira sucesso")
+    run.add_argument("--test-timeout", type=int, default=300, metavar="SECS",
+                     help="Com --test, encerra o QEMU e falha após SECS segundos (padrão: 300)")
+    run.add_argument("--boot-timeout", type=int, metavar="SECS",
+                     help="Smoke test pela serial: sucesso ao ver o marcador de boot, falha no de pânico "
+                          "ou se nenhum aparecer em SECS segundos")
+    run.add_argument("--boot-marker", metavar="TEXT", help="Marcador de boot concluído (padrão: [qemu] boot_marker)")
+    run.add_argument("--panic-marker", metavar="TEXT", help="Marcador de pânico (padrão: [qemu] panic_marker)")
+    run.add_argument("--profile", default="release",
+                     help="Perfil usado no 'anvil dist' (para localizar os artefatos; padrão: release)")
+    run.add_argument("--drive", "--disk", dest="drives", action="append", default=[], metavar="SPEC",
+                     help="Disco extra: caminho da imagem raw/qcow2 (virtio-blk) ou spec completa do -drive do QEMU; repetível")
+    run.add_argument("--new-disk", dest="new_disks", action="append", default=[], metavar="PATH:SIZE",
+                     help="Cria PATH como qcow2 vazio de SIZE (ex: data.qcow2:1G) se não existir e o anexa; repetível")
+    run.add_argument("--cdrom", dest="cdroms", action="append", default=[], type=Path, metavar="FILE",
+                     help="Anexa FILE como CD-ROM; repetível")
+    run.add_argument("--snapshot", action="store_true",
+                     help="Descarta ao sair tudo que o guest gravar nos discos (-snapshot do QEMU)")
+    run.add_argument("--loadvm", metavar="NAME",
+                     help="Boota do estado NAME salvo com 'savevm' num disco qcow2 (--drive/--new-disk)")
+    run.add_argument("--net", default="none", choices=NET_MODES,
+                     help="Rede do guest (virtio-net): none, user (NAT do QEMU) ou tap (padrão: none)")
+    run.add_argument("--tap-name", default=DEFAULT_TAP, metavar="IFACE",
+                     help=f"Interface tap do host para --net tap (padrão: {DEFAULT_TAP})")
+    run.add_argument("--hostfwd", action="append", default=[], metavar="HOST:GUEST",
+                     help="Redireciona a porta HOST do host para GUEST no guest (--net user; ex: 2222:22, udp:5353:53); repetível")
+
+    build = sub.add_parser("build", help="Compila componentes do sistema")
+    build.add_argument("target", nargs="?", default="all",
+                       choices=["all", "kernel", "bootloader", "services", "apps", "drivers", "tools"],
+                       help="O que compilar (padrão: all, em modo release)")
+    build.add_argument("--timings", action="store_true",
+                       help="Repassa --timings ao cargo e lista os relatórios HTML ao final")
+    build.add_argument("--open", action="store_true",
+                       help="Abre os relatórios de --timings no navegador")
+    build.add_argument("--version-string", metavar="S",
+                       help="Versão exposta aos componentes em ANVIL_BUILD_VERSION (padrão: git describe)")
+    build.add_argument("--pgo", action="store_true",
+                       help="Build do kernel em dois estágios guiada por perfil (requer llvm-tools-preview)")
+    build.add_argument("--pgo-timeout", type=int, default=120, metavar="SECS",
+                       help="Tempo máximo do workload de perfil no QEMU (padrão: 120)")
+    build.add_argument("--fast-check", action="store_true",
+                       help="Não chama o cargo se os fontes não mudaram desde o último build (ver limitações no README)")
+    build.add_argument("--target-cpu", metavar="CPU",
+                       help="Só com 'build tools': otimiza as ferramentas de host para a CPU (ex: native)")
+    build_jobs = build.add_mutually_exclusive_group()
+    build_jobs.add_argument("--jobs", "-j", type=int, metavar="N",
+                            help="Compila até N componentes ao mesmo tempo (o -j do cargo é dividido entre eles)")
+    build_jobs.add_argument("--parallel", dest="jobs", action="store_const", const=DEFAULT_PARALLEL_BUILDS,
+                            help=f"Atalho para --jobs {DEFAULT_PARALLEL_BUILDS}")
+    build.add_argument("--driver", metavar="NAME", help="Com 'build drivers', compila só esse driver")
+    build.add_argument("--with-drivers", action="store_true",
+                       help="Inclui os drivers de drivers/ no build completo (fora do boot mínimo por padrão)")
+    build.add_argument("--recipe", metavar="NAME",
+                       help="Compila só os pacotes de recipes/NAME.toml, no perfil da receita "
+                            "(padrão: a receita do 'anvil configure')")
+    build.add_argument("--release", action="store_true", help="Com --recipe, usa release no lugar do perfil da receita")
+
+    check = sub.add_parser("check", help="Roda 'cargo check' em todos os componentes, cada um no seu target")
+    check.add_argument("--target", dest="only", choices=["kernel", "bootloader", "services", "apps", "drivers"],
+                       help="Confere só esse componente")
+
+    fmt = sub.add_parser("fmt", help="Formata o código com 'cargo fmt --all'")
+    fmt.add_argument("--check", action="store_true", help="Só confere, sem alterar arquivos (falha se algo mudaria; para CI)")
+    fmt.add_argument("--since", metavar="REF", help="Só os componentes com arquivos alterados desde REF (git)")
+
+    clippy = sub.add_parser("clippy", help="Roda 'cargo clippy' em todos os componentes, cada um no seu target")
+    clippy.add_argument("--target", dest="only", choices=["kernel", "bootloader", "services", "apps", "drivers"],
+                        help="Analisa só esse componente")
+    clippy.add_argument("--fix", action="store_true", help="Aplica as sugestões automaticamente (cargo clippy --fix)")
+    clippy.add_argument("--deny-warnings", action="store_true", help="Avisos viram erros (-- -D warnings)")
+    clippy.add_argument("--since", metavar="REF", help="Só os componentes com arquivos alterados desde REF (git)")
+
+    doc = sub.add_parser("doc", help="Gera a documentação (cargo doc --no-deps) de todos os componentes")
+    doc.add_argument("--target", dest="only", choices=["kernel", "bootloader", "services", "apps", "drivers"],
+                     help="Documenta só esse componente")
+    doc.add_argument("--open", action="store_true", help="Abre a documentação do kernel no navegador")
+    doc.add_argument("--document-private-items", dest="private", action="store_true",
+                     help="Inclui itens privados (internos do kernel)")
+
+    watch = sub.add_parser("watch", help="Refaz o build a cada alteração nos fontes (Ctrl+C encerra)")
+    watch.add_argument("--target", dest="only", choices=["kernel", "bootloader", "services", "apps", "drivers"],
+                       help="Só alterações nesse componente disparam o rebuild (padrão: qualquer um)")
+    watch.add_argument("--run", action="store_true", help="Reinicia o QEMU após cada build bem-sucedido")
+    watch.add_argument("--debounce", type=float, default=DEFAULT_DEBOUNCE, metavar="SECS",
+                       help=f"Espera os fontes ficarem estáveis por SECS antes do rebuild (padrão: {DEFAULT_DEBOUNCE})")
+
+    size = sub.add_parser("size", help="Mostra o tamanho das seções (.text, .rodata, .data, .bss) dos binários")
+    size.add_argument("--target", dest="only", choices=["kernel", "bootloader", "services", "apps", "drivers"],
+                      help="Mede só esse componente (padrão: kernel e bootloader)")
+    size_profile = size.add_mutually_exclusive_group()
+    size_profile.add_argument("--profile", default="release", help="Perfil dos binários (padrão: release)")
+    size_profile.add_argument("--release", action="store_true", help="Atalho para --profile release")
+
+    dist = sub.add_parser("dist", help="Monta dist/qemu com os binários já compilados")
+    dist_profile = dist.add_mutually_exclusive_group()
+    dist_profile.add_argument("--profile", help="Perfil dos binários a usar (padrão: o da receita, ou release)")
+    dist_profile.add_argument("--release", action="store_true", help="Atalho para --profile release")
+    dist.add_argument("--recipe", metavar="NAME", help="Inclui no dist só os serviços/apps de recipes/NAME.toml "
+                      "(padrão: a receita do 'anvil configure')")
+    dist.add_argument("--incremental", action="store_true",
+                      help="Só copia os binários que mudaram desde o último dist (tamanho e data de modificação)")
+    dist.add_argument("--strict", action="store_true",
+                      help="Falha se faltar algum serviço ou app compilado, em vez de só avisar")
+    dist.add_argument("--squashfs", action="store_true",
+                      help="Empacota system/ em system.sfs (mksquashfs) em vez de arquivos soltos")
+    dist.add_argument("--strip", nargs="?", const="all", default="none", choices=STRIP_LEVELS,
+                      help="Remove símbolos: 'debug' guarda debug info em dist/debug, 'all' (ou só --strip) remove tudo "
+                           "(padrão: none)")
+    dist.add_argument("--splash", type=Path, metavar="BMP",
+                      help="Imagem de boot copiada para boot/splash.bmp e referenciada no ignite.cfg")
+    dist.add_argument("--layout", default="uefi", metavar="LAYOUT",
+                      help="Artefatos de boot: uefi (Ignite), bios (kernel em boot/forge) ou hybrid (ambos); "
+                           "definidos em [dist.layouts] (padrão: uefi)")
+    dist.add_argument("--no-checksum", dest="checksum", action="store_false",
+                      help="Não gera dist/SHA256SUMS (nem o .sha256 do --archive)")
+    dist.add_argument("--archive", choices=ARCHIVE_FORMATS,
+                      help="Empacota o dist em dist/redstone.tar.gz ou dist/redstone.zip, com o SHA-256 ao lado")
+    dist.add_argument("--sign-manifest", dest="sign", action="store_true",
+                      help="Assina o manifest.json com a chave de [dist].signing_key (manifest.json.sig, ssh-keygen -Y)")
+
+    verify = sub.add_parser("verify", help="Confere dist/qemu contra o manifest.json (e a assinatura, se pedido)")
+    verify.add_argument("--check-signature", action="store_true",
+                        help="Valida o manifest.json.sig com a chave pública")
+    verify.add_argument("--key", type=Path, metavar="PUB",
+                        help="Chave pública (.pub) da verificação (padrão: [dist].verify_key ou signing_key + '.pub')")
+
+    sub.add_parser("configure", help="Escolhe interativamente a receita e as features usadas nos builds")
+
+    image = sub.add_parser("image", help="Gera imagem VDI a partir de dist/qemu (somente leitura)")
+    image.add_argument("--keep-dist", action="store_true",
+                       help="Preserva a imagem RAW intermediária em dist/img para inspeção")
+    image.add_argument("--verify-boot", action="store_true",
+                       help="Boota a imagem headless no QEMU e confere o banner do bootloader na serial")
+    image.add_argument("--esp-size", "--size", dest="esp_size", type=int, metavar="MB",
+                       help="Tamanho da partição FAT; falha antes de escrever se dist/qemu não couber "
+                            "(padrão: conteúdo + folga)")
+    image.add_argument("--slack", type=int, default=16, metavar="MB",
+                       help="Folga somada ao tamanho calculado quando --esp-size não é informado (padrão: 16)")
+
+    iso = sub.add_parser("iso", help="Gera ISO bootável (UEFI) a partir de dist/qemu (somente leitura)")
+    iso.add_argument("--output", "-o", type=Path, metavar="FILE", help="Arquivo de saída (padrão: dist/redstone.iso)")
+    iso.add_argument("--keep-dist", action="store_true",
+                     help="Preserva a imagem EFI intermediária (efiboot.img) em dist/img para inspeção")
+    iso.add_argument("--hybrid", action="store_true",
+                     help="Adiciona boot BIOS (Limine/ISOLINUX do dist) à entrada UEFI; requer xorriso")
+    iso.add_argument("--no-checksum", dest="checksum", action="store_false",
+                     help="Não gera o <iso>.sha256")
+    iso.add_argument("--verify-boot", action="store_true",
+                     help="Boota a ISO headless no QEMU (como CD-ROM) e confere o banner do bootloader na serial")
+
+    usb = sub.add_parser("usb", help="Grava dist/qemu num pendrive USB")
+    usb.add_argument("--device", metavar="PATH", help="Disco de destino (ex: /dev/sdb); sem ele, escolhe numa lista")
+    usb.add_argument("--all-disks", action="store_true", help="Inclui discos não removíveis (cuidado: disco do sistema)")
+    usb.add_argument("--yes", "-y", action="store_true", help="Não pede confirmação (scripts)")
+    usb.add_argument("--dry-run", action="store_true", help="Mostra o que seria feito sem tocar no disco")
+    usb.add_argument("--verify", action="store_true", help="Relê os arquivos gravados e confere o SHA-256 com o dist")
+    usb.add_argument("--no-format", dest="reformat", action="store_false",
+                     help="Copia sobre o FAT32 que já existe no pendrive, sem formatar (atualização incremental)")
+
+    clean = sub.add_parser("clean", help="Limpa o build: cargo clean, target/ e dist/")
+    clean.add_argument("target", nargs="?", choices=["kernel", "bootloader", "userspace"],
+                       help="Limpa só os artefatos desse alvo (cargo clean -p), mantendo o resto do cache")
+    clean.add_argument("--dry-run", action="store_true",
+                       help="Lista o que seria removido e o espaço recuperável, sem apagar nada")
+    clean.add_argument("--all", dest="everything", action="store_true",
+                       help="Remove também cache/ e as ISOs geradas na raiz do projeto")
+
+    test = sub.add_parser("test", help="Compila o kernel de testes e roda headless no QEMU")
+    test.add_argument("--release", action="store_true", help="Usa o perfil release (padrão: debug)")
+    test.add_argument("--timeout", type=int, default=300, metavar="SECS",
+                      help="Tempo máximo do boot de testes (padrão: 300)")
+    test.add_argument("--watch", action="store_true",
+                      help="Refaz o build e roda os testes a cada alteração nos fontes do kernel (Ctrl+C encerra)")
+    test.add_argument("--debounce", type=float, default=DEFAULT_DEBOUNCE, metavar="SECS",
+                      help=f"Com --watch, espera os arquivos ficarem estáveis por SECS (padrão: {DEFAULT_DEBOUNCE})")
+    test.add_argument("--json", action="store_true",
+                      help="Resumo em JSON no stdout (status, duração, código do QEMU, timeout, fim da serial)")
+
+    bench_cmd = sub.add_parser("bench", help="Compila o kernel de benchmarks, roda headless e tabela os resultados")
+    bench_cmd.add_argument("--timeout", type=int, default=300, metavar="SECS",
+                           help="Tempo máximo do boot de benchmarks (padrão: 300)")
+    bench_cmd.add_argument("--output", nargs="?", type=Path, const=Path(bench.BENCH_RESULTS), metavar="FILE",
+                           help=f"Grava os resultados em JSON, relativo à raiz do projeto (padrão: {bench.BENCH_RESULTS})")
+    bench_cmd.add_argument("--baseline", type=Path, metavar="FILE",
+                           help="Compara com um JSON de uma execução anterior (gerado por --output; relativo à raiz)")
+    bench_cmd.add_argument("--threshold", type=float, default=bench.DEFAULT_THRESHOLD, metavar="PCT",
+                           help=f"Variação acima da qual um benchmark é regressão (padrão: {bench.DEFAULT_THRESHOLD:g}%%)")
+
+    env = sub.add_parser("env", help="Confere ferramentas/targets e mostra as variáveis de ambiente do build")
+    env_output = env.add_mutually_exclusive_group()
+    env_output.add_argument("--export", action="store_true", help="Imprime comandos para o shell (use com eval)")
+    env_output.add_argument("--json", action="store_true", help="Relatório em JSON no stdout (ferramentas, targets, ok)")
+    env.add_argument("--shell", default="bash", choices=SHELLS, help="Sintaxe dos comandos (padrão: bash)")
+
+    bootstrap = sub.add_parser("bootstrap", help="Instala os targets e componentes do rustup e indica QEMU/xorriso")
+    bootstrap.add_argument("--dry-run", action="store_true", help="Só mostra o que seria instalado")
+
+    recipe = sub.add_parser("recipe", help="Receitas de build (recipes/*.toml)")
+    recipe_sub = recipe.add_subparsers(dest="recipe_command", required=True)
+    recipe_sub.add_parser("list", help="Lista as receitas do projeto")
+    recipe_show = recipe_sub.add_parser("show", help="Mostra pacotes, perfil e targets de uma receita")
+    recipe_show.add_argument("name", help="Nome da receita (recipes/<nome>.toml)")
+    recipe_show.add_argument("--json", action="store_true", help="Imprime a receita interpretada em JSON")
+    recipe_validate = recipe_sub.add_parser("validate", help="Confere pacotes, targets e extends das receitas")
+    recipe_validate.add_argument("name", nargs="?", help="Só esta receita (padrão: todas de recipes/)")
+
+    template = sub.add_parser("template", help="Cria crates a partir de modelos (anvil/templates)")
+    template_sub = template.add_subparsers(dest="template_command", required=True)
+    template_sub.add_parser("list", help="Lista os modelos disponíveis")
+    template_new = template_sub.add_parser("new", help="Gera um crate novo no diretório do tipo")
+    template_new.add_argument("kind", metavar="TYPE", help=f"Tipo do modelo ({', '.join(TEMPLATE_DESTS)} ou um modelo próprio)")
+    template_new.add_argument("name", help="Nome do crate (ex: net-card)")
+
+    bisect = sub.add_parser("bisect", help="Encontra o commit que quebrou o boot via git bisect")
+    bisect.add_argument("--good", required=True, metavar="REF", help="Commit/tag onde o sistema funcionava")
+    bisect.add_argument("--bad", required=True, metavar="REF", help="Commit/tag onde o problema aparece")
+    bisect.add_argument("--test", required=True, metavar="CMD",
+                        help="Comando de shell rodado em anvil/ a cada passo: 0 bom, 125 pular, 1-127 ruim")
+    bisect.add_argument("--no-build", dest="build", action="store_false",
+                        help="Não recompila antes do teste (o próprio CMD faz o build)")
+    bisect.add_argument("--clean", action="store_true", help="Remove target/ e dist/ antes de cada build")
+
+    return parser
+
+async def run_command(cli: AnvilCLI, args: argparse.Namespace) -> int:
+    """Executa um subcomando da linha de comando e retorna o código de saída."""
+    if args.command in ("build", "dist", "test", "bench"): events.emit("build-started", command=args.command)
+    if args.command == "build": cli.check_cross_arch()
+    try:
+        if args.command == "run":
+            boot_set = {}
+            for item in args.boot_set:
+                key, sep, value = item.partition("=")
+                if not sep or not key: raise ConfigError(f"--set espera KEY=VALUE, recebido: {item}")
+                boot_set[key.strip()] = value.strip()
+            options = QemuOptions(
+                rtc_base=args.rtc_base, accel=args.accel, boot_set=boot_set,
+                firmware_code=args.firmware_code, firmware_vars=args.firmware_vars,
+                trace=args.trace, initrd=args.initrd, drives=args.drives, cdroms=args.cdroms,
+                new_disks=args.new_disks, snapshot=args.snapshot, loadvm=args.loadvm,
+                net=args.net, tap_name=args.tap_name, hostfwd=args.hostfwd,
+                profile=args.profile, gdb_port=args.gdb_port if args.gdb else None,
+                gdb_timeout=args.gdb_timeout, gdb_timeout_action=args.gdb_timeout_action,
+                serial=args.serial, serial_log=args.serial_log, display=args.display, display_mode=args.display_mode,
+                memory_mb=cli.config.qemu.memory_mb if args.memory is None else args.memory,
+                cpus=cli.config.qemu.cpus if args.cpus is None else args.cpus,
+                test=args.test, test_timeout=args.test_timeout, boot_timeout=args.boot_timeout,
+                boot_marker=cli.config.qemu.boot_marker if args.boot_marker is None else args.boot_marker,
+                panic_marker=cli.config.qemu.panic_marker if args.panic_marker is None else args.panic_marker,
+            )
+            code = await cli.run_qemu(options=options, interactive=False)
+            if code:
+                events.emit("done", command=args.command, ok=False)
+                return code if 0 < code < 256 else 1
+        elif args.command == "build":
+            targets = {
+                "all": cli.build_release,
+                "kernel": cli.build_kernel,
+                "bootloader": cli.build_bootloader,
+                "services": cli.build_services,
+                "apps": cli.build_apps,
+                "drivers": lambda: cli.build_drivers(only=args.driver),
+                "tools": cli.build_tools,
+            }
+            cli.timings = args.timings
+            cli.version_string = args.version_string
+            if args.target_cpu and args.target != "tools":
+                # Os componentes do SO rodam no guest; -Ctarget-cpu=native usaria a CPU do host
+                raise ConfigError("--target-cpu só se aplica a 'build tools'")
+            cli.tools_target_cpu = args.target_cpu
+            if args.driver and args.target != "drivers": raise ConfigError("--driver só se aplica a 'build drivers'")
+            cli.with_drivers = args.with_drivers
+            cli.fast_check = args.fast_check
+            if args.jobs is not None:
+                if args.jobs < 1: raise ConfigError("--jobs deve ser pelo menos 1")
+                cli.jobs = min(args.jobs, os.cpu_count() or 1)
+            # Sem --recipe, a receita escolhida no 'anvil configure'
+            recipe = args.recipe or cli.selection().recipe
+            if recipe: cli.use_recipe(recipe)
+            # Ferramentas de host usam o toolchain estável normal
+            if args.target != "tools": cli.check_prerequisites(None if args.target == "all" else args.target)
+            if args.pgo: await cli.build_pgo(timeout=args.pgo_timeout)
+            elif recipe and args.target == "all":
+                await cli.build_recipe("release" if args.release else cli.recipe.profile)
+            else: await targets[args.target]()
+            cli.report.print(logger)
+            if args.timings: cli.report_timings(open_reports=args.open)
+        elif args.command == "check":
+            await cli.check(only=args.only)
+        elif args.command == "doc":
+            await cli.doc(only=args.only, open_docs=args.open, private=args.private)
+        elif args.command == "watch":
+            await cli.watch(only=args.only, run=args.run, debounce=args.debounce)
+        elif args.command == "size":
+            await cli.size(only=args.only, profile="release" if args.release else args.profile)
+        elif args.command == "fmt":
+            await cli.fmt(check=args.check, since=args.since)
+        elif args.command == "clippy":
+            await cli.clippy(only=args.only, fix=args.fix, deny_warnings=args.deny_warnings, since=args.since)
+        elif args.command == "dist":
+            recipe = args.recipe or cli.selection().recipe
+            if recipe: cli.use_recipe(recipe)
+            profile = args.profile or ("release" if args.release or not cli.recipe else cli.recipe.profile)
+            await cli.dist(profile=profile, squashfs=args.squashfs, strip=args.strip, splash=args.splash,
+                           layout=args.layout, checksum=args.checksum, incremental=args.incremental,
+                           strict=args.strict, archive=args.archive, sign=args.sign)
+        elif args.command == "verify":
+            code = cli.verify(check_signature=args.check_signature, public_key=args.key)
+            if code:
+                events.emit("done", command=args.command, ok=False)
+                return code
+        elif args.command == "clean":
+            if args.target:
+                if args.dry_run or args.everything: raise ConfigError("--dry-run/--all não se aplicam à limpeza de um alvo")
+                await cli.clean_packages(args.target)
+            else: await cli.clean(dry_run=args.dry_run, everything=args.everything)
+        elif args.command == "configure":
+            await cli.configure()
+        elif args.command == "image":
+            await cli.create_vdi(keep_dist=args.keep_dist, verify_boot=args.verify_boot,
+                                 size_mb=args.esp_size, slack_mb=args.slack)
+        elif args.command == "iso":
+            await cli.create_iso(output=args.output, keep_dist=args.keep_dist, hybrid=args.hybrid,
+                                 checksum=args.checksum, verify_boot=args.verify_boot)
+        elif args.command == "usb":
+            await cli.usb(device=args.device, all_disks=args.all_disks, yes=args.yes, dry_run=args.dry_run,
+                          verify=args.verify, reformat=args.reformat)
+        elif args.command == "test" and args.watch:
+            await cli.test_watch(profile="release" if args.release else "debug", timeout=args.timeout, debounce=args.debounce)
+        elif args.command == "test":
+            code = await cli.test(profile="release" if args.release else "debug", timeout=args.timeout, as_json=args.json)
+            if code:
+                events.emit("done", command=args.command, ok=False)
+                return code
+        elif args.command == "bench":
+            code = await cli.bench(timeout=args.timeout, output=args.output, baseline=args.baseline,
+                                   threshold=args.threshold)
+            if code:
+                events.emit("done", command=args.command, ok=False)
+                return code
+        elif args.command == "env":
+            code = cli.env(export=args.export, shell=args.shell, as_json=args.json, as_event=args.json_events)
+            if code:
+                events.emit("done", command=args.command, ok=False)
+                return code
+        elif args.command == "bootstrap":
+            cli.bootstrap(dry_run=args.dry_run)
+        elif args.command == "recipe":
+            if args.recipe_command == "list": cli.recipe_list()
+            elif args.recipe_command == "show": cli.recipe_show(args.name, as_json=args.json)
+            elif args.recipe_command == "validate":
+                code = cli.recipe_validate(args.name)
+                if code:
+                    events.emit("done", command=args.command, ok=False)
+                    return code
+        elif args.command == "template":
+            if args.template_command == "list": cli.template_list()
+            elif args.template_command == "new": cli.template_new(args.kind, args.name)
+        elif args.command == "bisect":
+            first_bad = await Bisector(cli.paths, cli.config).run(
+                args.good, args.bad, args.test, build=args.build, clean=args.clean)
+            if first_bad is None: raise AnvilError("Bisect não concluído")
+    except AnvilError as e:
+        logger.error(str(e))
+        events.emit("error", message=str(e))
+        events.emit("done", command=args.command, ok=False)
+        return 1
+    events.emit("done", command=args.command, ok=True)
+    return 0
+
+async def main(argv=None) -> int:
+    """Ponto de entrada: despacha o subcomando ou abre o menu interativo."""
+    parser = build_parser()
+    args = parser.parse_args(argv)
+    if args.json_events:
+        if not args.command: parser.error("--json exige um subcomando (o menu é interativo)")
+        if args.command == "env" and args.export: parser.error("--json não combina com env --export")
+        if args.command == "test" and args.json: parser.error("--json global não combina com test --json")
+    if args.command == "test" and args.watch and args.json: parser.error("test --watch não combina com --json")
+    if (args.command == "env" and (args.export or args.json)) or (args.command in ("recipe", "test") and getattr(args, "json", False)):
+        logger.stream = sys.stderr
+    if args.json_events:
+        # O stdout é só dos eventos; do log restam os erros, sem cores, no stderr
+        logger.stream = sys.stderr
+        events.open("stdout")
+        set_color_mode("never")
+        logger.configure(quiet=True)
+    else:
+        set_color_mode(args.color, logger.stream)
+        logger.configure(verbose=args.verbose, quiet=args.quiet)
+    if args.event_stream:
+        try: events.open(args.event_stream)
+        except (ValueError, OSError) as e: parser.error(f"--event-stream inválido ({args.event_stream}): {e}")
+    try:
+        cli = AnvilCLI(args.config)
+    except ConfigError as e:
+        logger.error(str(e))
+        events.emit("error", message=str(e))
+        events.emit("done", command=args.command, ok=False)
+        return 1
+    cli.use_arch(args.arch)
+    if args.reproducible:
+        cli.reproducible = True
+        epoch = reproducible.enable(cli.paths.root)
+        logger.info(f"Modo reproduzível (SOURCE_DATE_EPOCH={epoch})")
+
+    if args.command:
+        return await run_command(cli, args)
+
+    await menu(cli)
+    return 0
+
+async def menu(cli: AnvilCLI):
     """Loop principal do menu."""
-    cli = AnvilCLI()
-    
     while True:
         clear_screen()
         print(f"{Colors.BOLD}{Colors.YELLOW}Anvil - RedstoneOS Builder{Colors.RESET}\n")
@@ -354,6 +2000,8 @@ async def main():
             elif choice == "c": await cli.clean()
         except KeyboardInterrupt:
             logger.warning("Interrompido!")
+        except AnvilError as e:
+            logger.error(str(e))
         except Exception as e:
             logger.error(f"Exceção Inesperada: {e}")
         
@@ -362,6 +2010,6 @@ async def main():
 
 if __name__ == "__main__":
     try:
-        asyncio.run(main())
+        sys.exit(asyncio.run(main()))
     except KeyboardInterrupt:
         pass
@@ -0,0 +1,98 @@
+"""Anvil Runner - Microbenchmarks do kernel ('anvil bench').
+
+O kernel compilado com as features de benchmark imprime na serial uma linha
+'BENCH <nome> <nanos>' por medição e sai pelo isa-debug-exit, como no modo de
+teste. Um nome repetido conta como várias amostras; vale a mediana. Os
+resultados podem ser gravados em JSON e servir de baseline para a próxima
+execução, que aponta regressões acima de um limite percentual.
+"""
+
+import json
+import re
+import statistics
+from dataclasses import dataclass, field
+from datetime import datetime, timezone
+from pathlib import Path
+from typing import Optional
+
+from core.errors import ValidationError
+from core.logger import Colors, Logger
+from runner.streams import LogEntry, StreamSource
+
+BENCH_LINE = re.compile(r"\bBENCH\s+(\S+)\s+(\d+)\s*$")
+BENCH_RESULTS = "bench-results.json"
+BENCH_VERSION = 1
+DEFAULT_THRESHOLD = 10.0  # %
+
+@dataclass
+class BenchResult:
+    name: str
+    samples: list[int] = field(default_factory=list)
+
+    @property
+    def nanos(self) -> int:
+        return int(statistics.median(self.samples))
+
+@dataclass
+class BenchComparison:
+    result: BenchResult
+    baseline: Optional[int] = None
+
+    @property
+    def delta(self) -> Optional[float]:
+        """Variação em % sobre a baseline (positivo = mais lento)."""
+        if not self.baseline: return None
+        return (self.result.nanos - self.baseline) * 100 / self.baseline
+
+    def regressed(self, threshold: float) -> bool:
+        return self.delta is not None and self.delta > threshold
+
+class BenchCollector:
+    """Callback da StreamCapture que junta as amostras da serial, na ordem em que aparecem."""
+
+    def __init__(self):
+        self.results: dict[str, BenchResult] = {}
+
+    def on_entry(self, entry: LogEntry) -> None:
+        if entry.source != StreamSource.SERIAL: return
+        match = BENCH_LINE.search(entry.line)
+        if not match: return
+        name, nanos = match.group(1), int(match.group(2))
+        self.results.setdefault(name, BenchResult(name)).samples.append(nanos)
+
+def write_results(results: list[BenchResult], output: Path, profile: str = "release") -> Path:
+    data = {
+        "version": BENCH_VERSION,
+        "created": datetime.now(timezone.utc).strftime("%Y-%m-%dT%H:%M:%SZ"),
+        "profile": profile,
+        "results": [{"name": r.name, "nanos": r.nanos, "samples": r.samples} for r in results],
+    }
+    output.parent.mkdir(parents=True, exist_ok=True)
+    output.write_text(json.dumps(data, indent=2) + "\n", encoding="utf-8")
+    return output
+
+def read_baseline(path: Path) -> dict[str, int]:
+    """Nanos (mediana) por benchmark de um JSON gravado por write_results."""
+    try:
+        data = json.loads(path.read_text(encoding="utf-8"))
+        return {e["name"]: int(e["nanos"]) for e in data["results"]}
+    except (OSError, ValueError, KeyError, TypeError) as e:
+        raise ValidationError(f"Baseline inválida: {path} ({e})", str(path))
+
+def compare(results: list[BenchResult], baseline: Optional[dict[str, int]]) -> list[BenchComparison]:
+    return [BenchComparison(r, (baseline or {}).get(r.name)) for r in results]
+
+def print_table(rows: list[BenchComparison], threshold: float, log: Logger) -> None:
+    """Tabela com a mediana em ns, o número de amostras e, com baseline, a variação."""
+    name_w = max(len("Benchmark"), *(len(r.result.name) for r in rows))
+    with_baseline = any(r.baseline is not None for r in rows)
+    header = f"{'ns':>14}{'amostras':>10}" + (f"{'baseline':>14}{'variação':>11}" if with_baseline else "")
+    log.raw(f"  {Colors.BOLD}{'Benchmark':<{name_w}}{header}{Colors.RESET}")
+    for row in rows:
+        line = f"  {Colors.CYAN}{row.result.name:<{name_w}}{Colors.RESET}{row.result.nanos:>14,}{len(row.result.samples):>10}"
+        if with_baseline:
+            if row.delta is None: line += f"{'-':>14}{'novo':>11}"
+            else:
+                color = Colors.RED if row.regressed(threshold) else Colors.GREEN if row.delta < 0 else ""
+                line += f"{row.baseline:>14,}{color}{row.delta:>+10.1f}%{Colors.RESET}"
+        log.raw(line)
@@ -14,7 +14,7 @@ from typing import Optional, Callable
 from core.config import Config
 from core.paths import Paths
 from core.logger import Logger, get_logger
-from runner.qemu import QemuRunner
+from runner.qemu import QemuRunner, QemuOptions
 from runner.streams import StreamCapture, LogEntry, StreamSource
 from runner.serial import SerialColorizer
 
@@ -38,6 +38,10 @@ class MonitorResult:
     crash_info: Optional[CrashInfo] = None
     all_crashes: list = field(default_factory=list)
     total_lines: int = 0
+    exit_code: Optional[int] = None  # Código de saída do QEMU (None se não terminou sozinho)
+    timed_out: bool = False
+    # Com --boot-timeout: "booted" ou "panic", conforme o marcador visto primeiro na serial
+    boot_status: Optional[str] = None
 
 class QemuMonitor:
     """Monitor de execução do QEMU."""
@@ -51,19 +55,20 @@ class QemuMonitor:
         "v=0e": ("Page Fault", "#PF"),
     }
     
-    def __init__(self, paths, config, log=None, stop_on_exception=True, show_serial=True, on_exception=None):
+    def __init__(self, paths, config, log=None, stop_on_exception=True, show_serial=True, on_exception=None, options=None):
         self.paths = paths
         self.config = config
         self.log = log or get_logger()
         self.stop_on_exception = stop_on_exception
         self.show_serial = show_serial
         self.on_exception = on_exception
-        self.runner = QemuRunner(paths, config, log)
+        self.runner = QemuRunner(paths, config, log, options)
         self.capture = StreamCapture()
         self._crash_info = None
         self._all_crashes = []
         self._last_rip = None
         self._should_stop = False
+        self._boot_status = None
         
     def _on_entry(self, entry):
         """Callback chamado para cada nova linha de log."""
@@ -81,6 +86,8 @@ class QemuMonitor:
             if colored.strip():
                 self.log.raw(colored)
 
+        if entry.source == StreamSource.SERIAL and self._boot_status is None: self._check_boot_markers(entry.line)
+
         # Rastreia RIP para contexto de crash (fallback se passou pelo filtro visual)
         if entry.line.startswith("RIP="): self._last_rip = entry.line.split()[0]
         
@@ -94,6 +101,14 @@ class QemuMonitor:
             if self.on_exception: self.on_exception(crash)
             if self.stop_on_exception: self._should_stop = True
             
+    def _check_boot_markers(self, line: str) -> None:
+        """Com --boot-timeout, para o QEMU no primeiro marcador de pânico ou de boot concluído."""
+        options = self.runner.options
+        if options.boot_timeout is None: return
+        if options.panic_marker in line: self._boot_status = "panic"
+        elif options.boot_marker in line: self._boot_status = "booted"
+        if self._boot_status: self._should_stop = True
+
     def _detect_exception(self, entry):
         """Analisa a linha procurando por padrões de exceção x86."""
         line = entry.line
@@ -122,16 +137,20 @@ class QemuMonitor:
         self._all_crashes = []
         self._should_stop = False
         self._last_rip = None
+        self._boot_status = None
+        timed_out = False
         
         # Registra nosso listener
         self.capture.add_callback(self._on_entry)
+        # O runner é o único leitor do stdout (dois readline() no mesmo stream falham); recebemos as linhas dele
+        self.runner.on_line = self.capture.feed
         
         try:
             process = await self.runner.start()
             if not process.stdout: return MonitorResult(False, 0, True)
             
             # Tasks para capturar output
-            serial_task = asyncio.create_task(self.capture.capture_serial(process.stdout))
+            serial_task = self.runner.serial_task
             # CPU Log removido a pedido
             # cpu_task = asyncio.create_task(self.capture.capture_cpu_log(self.paths.cpu_log))
             
@@ -147,7 +166,8 @@ class QemuMonitor:
                 if exit_task.done(): break
                 
                 if timeout and (time.time() - start) > timeout:
-                    try: process.terminate()
+                    timed_out = True
+                    try: process.terminate(); await process.wait()
                     except: pass
                     break
                     
@@ -166,8 +186,15 @@ class QemuMonitor:
                 crashed=self._crash_info is not None, 
                 crash_info=self._crash_info, 
                 all_crashes=self._all_crashes, 
-                total_lines=self.capture.total_lines
+                total_lines=self.capture.total_lines,
+                exit_code=process.returncode,
+                timed_out=timed_out,
+                boot_status=self._boot_status,
             )
+        except asyncio.CancelledError:
+            # Ctrl+C ou 'anvil watch --run' reiniciando: não deixa o QEMU órfão
+            await self.runner.stop()
+            raise
         except Exception as e:
             self.log.error(f"Erro: {e}"); await self.runner.stop()
             return MonitorResult(False, int((time.time()-start)*1000), True)
@@ -2,25 +2,525 @@
 
 Responsável por montar o comando e executar o QEMU nativamente no Linux,
 seguindo EXATAMENTE os padrões definidos pelo usuário (hardcoded).
+
+Snapshots: '--snapshot' passa -snapshot, então nada do que o guest grava em
+qualquer disco (partição do dist, --drive, --new-disk e a cópia das
+variáveis UEFI) sobrevive ao fim do QEMU. '--loadvm NAME' boota de um estado
+salvo com 'savevm NAME', que o QEMU guarda dentro de uma imagem qcow2: exige
+ao menos um disco qcow2 anexado e não combina com --snapshot (os overlays
+temporários não têm o estado) nem com --firmware-vars (pflash raw gravável
+não suporta snapshot). Com --loadvm a partição do dist fica somente leitura,
+pelo mesmo motivo. Sempre que há um disco qcow2 o monitor do QEMU fica em
+dist/qemu-monitor.sock para o savevm.
 """
 
 import asyncio
 import os
+import platform
+import re
+import shutil
+import socket
+import subprocess
+import sys
+from dataclasses import dataclass, field
+from datetime import datetime
 from pathlib import Path
 from typing import Optional
-from core.config import Config
+from core.config import DEFAULT_ARCH, Config, QemuConfig
 from core.paths import Paths
+from core.errors import BuildError, ConfigError, ValidationError
 from core.logger import Logger, get_logger
 
+ACCELERATORS = ["auto", "tcg", "kvm", "whpx", "hvf"]
+
+# Texto impresso pelo Ignite na serial ao iniciar; prova que o firmware carregou o BOOTX64.EFI
+BOOTLOADER_BANNER = "Ignite"
+
+GDB_DEFAULT_PORT = 1234
+# '--gdb-timeout': sem debugger no stub após o prazo, continua o guest ou encerra o QEMU
+GDB_TIMEOUT_ACTIONS = ["continue", "abort"]
+# Espera pela resposta do stub; sem ela, outro cliente (o gdb do usuário) já está conectado
+GDB_PROBE_TIMEOUT = 2.0
+
+# Modo de teste (isa-debug-exit): o kernel escreve em TEST_EXIT_PORT e o QEMU sai com
+# (valor << 1) | 1; TEST_SUCCESS_CODE corresponde ao valor 0x10 escrito em caso de sucesso.
+# No aarch64 não há porta de I/O: o QEMU liga o semihosting e o kernel sai por
+# SYS_EXIT_EXTENDED com o mesmo código que o x86 devolveria
+TEST_EXIT_PORT = 0xf4
+TEST_SUCCESS_CODE = 33
+# Nomes equivalentes reportados por platform.machine()
+ARCH_ALIASES = {"amd64": "x86_64", "x64": "x86_64", "arm64": "aarch64"}
+
+@dataclass(frozen=True)
+class QemuSystem:
+    """Binário do QEMU de uma arquitetura; machine e tcg_cpu None usam o padrão do QEMU (x86: pc e max)."""
+    binary: str
+    machine: Optional[str] = None
+    tcg_cpu: str = "max"
+
+QEMU_SYSTEMS = {
+    "x86_64": QemuSystem("qemu-system-x86_64"),
+    "aarch64": QemuSystem("qemu-system-aarch64", machine="virt", tcg_cpu="cortex-a72"),
+}
+
+def host_arch() -> str:
+    machine = platform.machine().lower()
+    return ARCH_ALIASES.get(machine, machine)
+
+def hw_accel_possible(arch: str = DEFAULT_ARCH) -> bool:
+    """Aceleração por hardware só é possível quando host e guest têm a mesma arquitetura."""
+    return host_arch() == arch
+
+def accel_available(accel: str, arch: str = DEFAULT_ARCH) -> bool:
+    """Verifica se o acelerador pode ser usado neste host."""
+    if accel != "tcg" and not hw_accel_possible(arch): return False
+    if accel == "kvm": return sys.platform.startswith("linux") and os.access("/dev/kvm", os.R_OK | os.W_OK)
+    if accel == "hvf": return sys.platform == "darwin"
+    if accel == "whpx": return sys.platform == "win32" and arch == "x86_64"
+    return accel == "tcg"
+
+def detect_accel(arch: str = DEFAULT_ARCH) -> str:
+    """Escolhe o melhor acelerador do host (WHPX no Windows, HVF no macOS, KVM no Linux)."""
+    for accel in ("kvm", "hvf", "whpx"):
+        if accel_available(accel, arch): return accel
+    return "tcg"
+
+# Locais conhecidos do firmware UEFI, por plataforma
+OVMF_PATHS = [
+    # Debian/Ubuntu
+    "/usr/share/OVMF/OVMF_CODE_4M.fd",
+    "/usr/share/OVMF/OVMF.fd",
+    "/usr/share/ovmf/OVMF.fd",
+    # Arch/Fedora
+    "/usr/share/edk2-ovmf/x64/OVMF.fd",
+    "/usr/share/edk2/x64/OVMF.fd",
+    "/usr/share/edk2/ovmf/OVMF_CODE.fd",
+    "/usr/share/qemu/OVMF.fd",
+    # macOS (Homebrew, Apple Silicon e Intel)
+    "/opt/homebrew/share/qemu/edk2-x86_64-code.fd",
+    "/usr/local/share/qemu/edk2-x86_64-code.fd",
+]
+# aarch64 (AAVMF): só imagens já no tamanho do pflash da máquina virt (64 MiB); o
+# QEMU_EFI.fd de 2 MiB do qemu-efi-aarch64 precisa de 'truncate -s 64M' numa cópia
+AAVMF_PATHS = [
+    # Debian/Ubuntu
+    "/usr/share/AAVMF/AAVMF_CODE.fd",
+    # Fedora/Arch
+    "/usr/share/edk2/aarch64/QEMU_EFI-pflash.raw",
+    "/usr/share/edk2-armvirt/aarch64/QEMU_CODE.fd",
+    # macOS (Homebrew)
+    "/opt/homebrew/share/qemu/edk2-aarch64-code.fd",
+    "/usr/local/share/qemu/edk2-aarch64-code.fd",
+]
+# Caminhos do sistema e nome em firmware/ na raiz do projeto, por arquitetura
+FIRMWARE_PATHS = {"x86_64": (OVMF_PATHS, "OVMF.fd"), "aarch64": (AAVMF_PATHS, "AAVMF_CODE.fd")}
+
+def find_ovmf(project_root: Optional[Path] = None, arch: str = DEFAULT_ARCH) -> Path:
+    """
+    Firmware UEFI da arquitetura: ANVIL_OVMF (tem prioridade sobre tudo), os locais
+    do sistema e por fim firmware/OVMF.fd (aarch64: firmware/AAVMF_CODE.fd) na raiz do projeto.
+    """
+    override = os.environ.get("ANVIL_OVMF")
+    if override:
+        if not Path(override).is_file():
+            raise ValidationError(f"ANVIL_OVMF aponta para um arquivo inexistente: {override}", override)
+        return Path(override)
+    system_paths, local_name = FIRMWARE_PATHS[arch]
+    candidates = [Path(p) for p in system_paths]
+    if project_root: candidates.append(project_root / "firmware" / local_name)
+    found = next((p for p in candidates if p.is_file()), None)
+    if found is None:
+        package = "ovmf/edk2-ovmf" if arch == "x86_64" else "qemu-efi-aarch64/edk2-aarch64"
+        raise ValidationError(
+            f"Firmware UEFI ({arch}) não encontrado. Instale o pacote {package}, defina ANVIL_OVMF ou use --firmware-code.\n"
+            "Procurado em:\n" + "\n".join(f"  {p}" for p in candidates), "OVMF")
+    return found
+
+# Frontends de vídeo (--display); 'vnc' aceita ':N' (display N do VNC, porta TCP 5900+N)
+DISPLAYS = ["gtk", "sdl", "cocoa", "none", "vnc"]
+# Frontends com janela, na ordem de preferência para o fallback
+WINDOWED_DISPLAYS = ["gtk", "sdl", "cocoa"]
+
+def default_display() -> str:
+    """gtk no Linux e no Windows, cocoa no macOS."""
+    return "cocoa" if sys.platform == "darwin" else "gtk"
+
+def parse_display(spec: str) -> tuple[str, Optional[int]]:
+    """'gtk', 'none', 'vnc' ou 'vnc:N' -> (frontend, display do VNC)."""
+    mode, sep, number = spec.partition(":")
+    if mode not in DISPLAYS or (sep and (mode != "vnc" or not number.isdigit())):
+        raise ConfigError(f"Display inválido: {spec}", f"Valores aceitos: {', '.join(DISPLAYS)} (vnc aceita :N, ex: vnc:1)")
+    return mode, int(number) if sep else (0 if mode == "vnc" else None)
+
+def available_displays(qemu: str = QEMU_SYSTEMS[DEFAULT_ARCH].binary) -> Optional[list[str]]:
+    """Frontends compilados no QEMU ('-display help'); None se não der para perguntar."""
+    try:
+        p = subprocess.run([qemu, "-display", "help"], capture_output=True, text=True, timeout=10)
+    except (OSError, subprocess.TimeoutExpired):
+        return None
+    if p.returncode != 0: return None
+    # Primeira linha é o cabeçalho "Available display backend types:"
+    return [l.strip() for l in p.stdout.splitlines()[1:] if l.strip()]
+
+# Rede do guest (--net): none desliga até a NIC padrão do QEMU, para boots mínimos
+NET_MODES = ["none", "user", "tap"]
+DEFAULT_TAP = "tap0"
+
+def parse_hostfwd(spec: str) -> tuple[str, int, int]:
+    """'[tcp|udp:]HOST:GUEST' (ex: 2222:22) -> (protocolo, porta do host, porta do guest)."""
+    parts = spec.split(":")
+    proto = parts.pop(0) if len(parts) == 3 else "tcp"
+    if proto not in ("tcp", "udp") or len(parts) != 2 or not all(p.isdigit() for p in parts):
+        raise ConfigError(f"--hostfwd inválido: {spec}", "Formato: [tcp|udp:]HOST:GUEST, ex: --hostfwd 2222:22")
+    host, guest = map(int, parts)
+    for port in (host, guest):
+        if not 1 <= port <= 65535: raise ConfigError(f"Porta fora do intervalo em --hostfwd: {spec}", "Use de 1 a 65535")
+    return proto, host, guest
+
+DRIVE_FORMATS = {".qcow2": "qcow2", ".vdi": "vdi", ".vmdk": "vmdk", ".vhdx": "vhdx"}
+
+def drive_file(spec: str) -> Optional[str]:
+    """Arquivo referenciado por uma spec de --drive (caminho simples ou 'file=...')."""
+    if "=" not in spec: return spec
+    for part in spec.split(","):
+        key, _, value = part.partition("=")
+        if key == "file": return value
+    return None
+
+def is_qcow2(spec: str) -> bool:
+    """Se uma spec de --drive usa uma imagem qcow2."""
+    if "=" in spec: return "format=qcow2" in spec.split(",")
+    return DRIVE_FORMATS.get(Path(spec).suffix.lower()) == "qcow2"
+
+def drive_args(spec: str) -> list[str]:
+    """Argumentos '-drive' do QEMU; um caminho simples vira disco virtio no formato do sufixo."""
+    if "=" in spec: return ["-drive", spec]
+    fmt = DRIVE_FORMATS.get(Path(spec).suffix.lower(), "raw")
+    return ["-drive", f"file={Path(spec).absolute()},format={fmt},if=virtio"]
+
+# Tamanho aceito pelo qemu-img: bytes ou sufixo K/M/G/T (ex: 512M, 4G)
+DISK_SIZE_PATTERN = re.compile(r"^[1-9][0-9]*[KMGT]?$", re.IGNORECASE)
+
+def parse_new_disk(spec: str) -> tuple[Path, str]:
+    """'PATH:SIZE' (ex: data.qcow2:1G) -> (caminho, tamanho); divide no último ':' por causa de C:\\ no Windows."""
+    path, sep, size = spec.rpartition(":")
+    if not sep or not path or not DISK_SIZE_PATTERN.match(size):
+        raise ConfigError(f"--new-disk inválido: {spec}", "Formato: PATH:SIZE, ex: --new-disk data.qcow2:1G")
+    return Path(path), size.upper()
+
+def create_disk(path: Path, size: str, log: Optional[Logger] = None) -> bool:
+    """Cria uma imagem qcow2 vazia com 'qemu-img create'; não toca em arquivos existentes. True se criou."""
+    log = log or get_logger()
+    if path.exists():
+        log.debug(f"Disco já existe, mantido: {path}")
+        return False
+    qemu_img = shutil.which("qemu-img")
+    if not qemu_img:
+        raise BuildError("qemu-img não encontrado", "qemu", ["Vem com o QEMU (Debian/Ubuntu: sudo apt install qemu-utils)"])
+    path.parent.mkdir(parents=True, exist_ok=True)
+    p = subprocess.run([qemu_img, "create", "-f", "qcow2", str(path), size], capture_output=True, text=True)
+    if p.returncode != 0:
+        raise BuildError(f"Falha ao criar disco {path}", "qemu", (p.stdout + p.stderr).splitlines())
+    log.step(f"Disco criado: {path} ({size}, qcow2)")
+    return True
+
+@dataclass
+class QemuOptions:
+    """Opções de execução do QEMU vindas da linha de comando (`anvil run`)."""
+    rtc_base: str = "utc"
+    accel: str = "auto"
+    # Firmware explícito; quando definido a autodetecção do OVMF é ignorada.
+    # Para Secure Boot passe um par CODE/VARS com as chaves já inscritas
+    # (ex: OVMF_CODE_4M.secboot.fd + OVMF_VARS_4M.ms.fd); não há flag dedicada.
+    firmware_code: Optional[Path] = None
+    firmware_vars: Optional[Path] = None
+    # Eventos de trace do QEMU: lista de globs separada por vírgula (ex: "pci_*,virtio_blk_*")
+    # ou caminho de um arquivo com um evento por linha
+    trace: Optional[str] = None
+    # Pares chave=valor gravados no ignite.cfg do dist antes do boot (--set)
+    boot_set: dict = field(default_factory=dict)
+    # Initramfs alternativo: copiado para boot/initrd no dist e usado como module_path
+    initrd: Optional[Path] = None
+    # Discos extras: spec '-drive' do QEMU ou só o caminho da imagem (--drive/--disk, repetível)
+    drives: list = field(default_factory=list)
+    # Imagens qcow2 criadas se faltarem e anexadas como virtio-blk (--new-disk PATH:SIZE)
+    new_disks: list = field(default_factory=list)
+    cdroms: list = field(default_factory=list)
+    # Descarta as gravações nos discos ao sair (--snapshot) ou boota de um estado salvo no qcow2 (--loadvm)
+    snapshot: bool = False
+    loadvm: Optional[str] = None
+    # Rede: none, user (NAT do QEMU, com redirecionamentos --hostfwd) ou tap (interface do host)
+    net: str = "none"
+    tap_name: str = DEFAULT_TAP
+    hostfwd: list = field(default_factory=list)
+    # Perfil usado no 'anvil dist' (resolve os destinos dos artefatos de [dist])
+    profile: str = "release"
+    # Porta do gdb stub (--gdb); a CPU fica parada até o gdb conectar e continuar
+    gdb_port: Optional[int] = None
+    # Sem debugger conectado em gdb_timeout segundos: gdb_timeout_action (None espera para sempre)
+    gdb_timeout: Optional[int] = None
+    gdb_timeout_action: str = "continue"
+    # Serial do guest no stdout (--no-serial desliga) e janela gráfica (--no-display)
+    serial: bool = True
+    display: bool = True
+    # Cópia da serial do guest num arquivo (--serial-log), truncado a cada boot
+    serial_log: Optional[Path] = None
+    # Frontend (--display gtk|sdl|cocoa|none|vnc[:N]); None usa o padrão da plataforma
+    display_mode: Optional[str] = None
+    memory_mb: int = 512
+    cpus: int = 1
+    # Teste automatizado (--test): headless, com isa-debug-exit e tempo máximo
+    test: bool = False
+    test_timeout: int = 300
+    # Smoke test pela serial (--boot-timeout): sai ao ver um dos marcadores ou falha após o tempo
+    boot_timeout: Optional[int] = None
+    boot_marker: str = QemuConfig.boot_marker
+    panic_marker: str = QemuConfig.panic_marker
+
+    RTC_TIMESTAMP_FORMAT = "%Y-%m-%dT%H:%M:%S"
+
+    @property
+    def headless(self) -> bool:
+        return not self.display or self.test or self.display_mode == "none"
+
+    def has_qcow2(self) -> bool:
+        """Se algum disco anexado é qcow2 (--new-disk sempre é)."""
+        return bool(self.new_disks) or any(is_qcow2(d) for d in self.drives)
+
+    def validate(self) -> None:
+        """Valida as opções antes de lançar o QEMU."""
+        if self.accel not in ACCELERATORS:
+            raise ConfigError(f"Acelerador inválido: {self.accel}", f"Valores aceitos: {', '.join(ACCELERATORS)}")
+        for flag, path in (("--firmware-code", self.firmware_code), ("--firmware-vars", self.firmware_vars)):
+            if path is not None and not path.is_file():
+                raise ValidationError(f"Firmware não encontrado ({flag}): {path}", str(path))
+        if self.memory_mb < 64:
+            raise ConfigError(f"--memory muito pequeno: {self.memory_mb} MiB", "Mínimo: 64 MiB")
+        if not 1 <= self.cpus <= 255:
+            raise ConfigError(f"--cpus fora do intervalo: {self.cpus}", "Use de 1 a 255")
+        if self.test and self.test_timeout <= 0:
+            raise ConfigError(f"--test-timeout deve ser positivo: {self.test_timeout}")
+        if self.boot_timeout is not None:
+            if self.boot_timeout <= 0:
+                raise ConfigError(f"--boot-timeout deve ser positivo: {self.boot_timeout}")
+            if self.test:
+                raise ConfigError("--boot-timeout não combina com --test", "O --test já encerra pelo isa-debug-exit e pelo --test-timeout")
+            if not self.serial:
+                raise ConfigError("--boot-timeout exige a serial", "Os marcadores são procurados na serial; tire o --no-serial")
+            if not self.boot_marker or not self.panic_marker:
+                raise ConfigError("Marcador de boot ou de pânico vazio")
+        if self.gdb_port is not None and not 1 <= self.gdb_port <= 65535:
+            raise ConfigError(f"Porta inválida para --gdb-port: {self.gdb_port}")
+        if self.gdb_timeout is not None:
+            if self.gdb_port is None: raise ConfigError("--gdb-timeout exige --gdb")
+            if self.gdb_timeout <= 0: raise ConfigError(f"--gdb-timeout deve ser positivo: {self.gdb_timeout}")
+        if self.gdb_timeout_action not in GDB_TIMEOUT_ACTIONS:
+            raise ConfigError(f"Ação inválida para --gdb-timeout-action: {self.gdb_timeout_action}",
+                              f"Valores aceitos: {', '.join(GDB_TIMEOUT_ACTIONS)}")
+        if self.initrd is not None and not self.initrd.is_file():
+            raise ValidationError(f"Initrd não encontrado: {self.initrd}", str(self.initrd))
+        created = {path.absolute() for path, _ in map(parse_new_disk, self.new_disks)}
+        for spec in self.drives:
+            path = drive_file(spec)
+            if path is None:
+                raise ConfigError(f"--drive sem 'file=': {spec}", "Use um caminho ou uma spec como file=disk.img,if=virtio")
+            # Backends do QEMU (fat:, nbd:, http://...) não são arquivos locais; a imagem
+            # do --new-disk só é criada depois da validação
+            if ":" not in path and not Path(path).is_file() and Path(path).absolute() not in created:
+                raise ValidationError(f"Imagem não encontrada (--drive): {path}", path)
+        for cdrom in self.cdroms:
+            if not Path(cdrom).is_file():
+                raise ValidationError(f"ISO não encontrada (--cdrom): {cdrom}", str(cdrom))
+        if self.loadvm is not None:
+            if not self.loadvm:
+                raise ConfigError("--loadvm vazio")
+            if not self.has_qcow2():
+                raise ConfigError("--loadvm exige um disco qcow2", "O estado do 'savevm' fica na imagem; anexe com --drive ou --new-disk")
+            if self.snapshot:
+                raise ConfigError("--loadvm não combina com --snapshot", "Os overlays temporários do -snapshot não têm o estado salvo")
+            if self.firmware_vars:
+                raise ConfigError("--loadvm não combina com --firmware-vars", "O pflash raw das variáveis não suporta snapshot")
+        if self.display_mode is not None: parse_display(self.display_mode)
+        if self.serial_log is not None and self.serial_log.is_dir():
+            raise ValidationError(f"--serial-log aponta para um diretório: {self.serial_log}", str(self.serial_log))
+        if self.net not in NET_MODES:
+            raise ConfigError(f"Modo de rede inválido: {self.net}", f"Valores aceitos: {', '.join(NET_MODES)}")
+        host_ports = [parse_hostfwd(spec)[:2] for spec in self.hostfwd]
+        if len(set(host_ports)) != len(host_ports):
+            raise ConfigError("Porta do host repetida em --hostfwd", ", ".join(self.hostfwd))
+        if self.hostfwd and self.net != "user":
+            raise ConfigError("--hostfwd exige --net user", "Redirecionamentos de porta só existem na rede user do QEMU")
+        if self.net == "tap" and not self.tap_name:
+            raise ConfigError("--tap-name vazio")
+        if self.rtc_base not in ("utc", "localtime"):
+            try:
+                datetime.strptime(self.rtc_base, self.RTC_TIMESTAMP_FORMAT)
+            except ValueError:
+                raise ConfigError(
+                    f"Valor inválido para --rtc-base: {self.rtc_base}",
+                    "Use 'utc', 'localtime' ou um horário fixo no formato YYYY-MM-DDTHH:MM:SS"
+                )
+
+async def gdb_claim_stub(port: int, wait: float = GDB_PROBE_TIMEOUT):
+    """
+    Conecta ao gdb stub do QEMU e pede o motivo da parada ('?'). O stub atende um cliente
+    por vez: sem resposta, o gdb do usuário já está nele e devolve None, como na falha de
+    conexão. Senão, (reader, writer) da conexão, que fica sendo a dona do stub.
+    """
+    try: reader, writer = await asyncio.wait_for(asyncio.open_connection("127.0.0.1", port), wait)
+    except (OSError, asyncio.TimeoutError): return None
+    try:
+        writer.write(b"+$?#3f")
+        await writer.drain()
+        reply = await asyncio.wait_for(reader.readuntil(b"#"), wait)
+        if b"$" in reply: return reader, writer
+    except (OSError, asyncio.TimeoutError, asyncio.IncompleteReadError, asyncio.LimitOverrunError): pass
+    writer.close()
+    return None
+
 class QemuRunner:
     """Gerenciador de processo QEMU."""
     
-    def __init__(self, paths: Paths, config: Config, log: Optional[Logger] = None):
+    def __init__(self, paths: Paths, config: Config, log: Optional[Logger] = None, options: Optional[QemuOptions] = None):
         self.paths = paths
         self.config = config
         self.log = log or get_logger()
+        self.options = options or QemuOptions()
         self.process = None
+        # Leitor único do stdout do QEMU; com on_line definido as linhas vão para ele em vez do terminal
+        self.on_line = None
+        self.serial_task = None
+        self.gdb_task = None
+
+    def _firmware_code(self) -> str:
+        """Caminho do OVMF: o informado em --firmware-code ou o primeiro encontrado no sistema."""
+        if self.options.firmware_code: return str(self.options.firmware_code.absolute())
+        return str(find_ovmf(self.paths.root, self.config.arch))
+
+    def _firmware_vars_args(self) -> list:
+        """
+        Segundo pflash (variáveis UEFI) quando --firmware-vars é informado.
+        Usa uma cópia em dist/ para que o arquivo original não seja alterado pelo guest.
+        """
+        if not self.options.firmware_vars: return []
+        vars_copy = self.paths.dist / "OVMF_VARS.fd"
+        vars_copy.parent.mkdir(parents=True, exist_ok=True)
+        shutil.copy2(self.options.firmware_vars, vars_copy)
+        return ["-drive", f"if=pflash,format=raw,file={vars_copy.absolute()}"]
+
+    def _trace_args(self) -> list:
+        """Argumentos '-trace' para os eventos pedidos em --trace."""
+        if not self.options.trace: return []
+        if Path(self.options.trace).is_file():
+            return ["-trace", f"events={Path(self.options.trace).absolute()}"]
+        args = []
+        for pattern in filter(None, (p.strip() for p in self.options.trace.split(","))):
+            args.extend(["-trace", f"enable={pattern}"])
+        return args
+
+    def _resolve_accel(self) -> str:
+        """Resolve '--accel', caindo para TCG se o acelerador pedido não existir no host."""
+        accel = self.options.accel
+        if accel == "auto": return detect_accel(self.config.arch)
+        if not accel_available(accel, self.config.arch):
+            self.log.warning(f"Acelerador '{accel}' indisponível neste host, usando TCG (lento)")
+            return "tcg"
+        return accel
         
+    def _storage_args(self) -> list[str]:
+        """Discos e CD-ROMs extras após a partição do dist (--drive, --new-disk e --cdrom)."""
+        # Imagens do --new-disk são sempre qcow2, qualquer que seja o sufixo
+        created = [p.absolute() for p, _ in map(parse_new_disk, self.options.new_disks)]
+        args, attached = [], set()
+        for spec in self.options.drives:
+            # O caminho simples de um --new-disk é anexado abaixo, como qcow2; uma spec completa vale como está
+            if "=" not in spec and Path(spec).absolute() in created: continue
+            attached.add(Path(drive_file(spec)).absolute())
+            args += drive_args(spec)
+        for disk in created:
+            if disk not in attached: args += ["-drive", f"file={disk},format=qcow2,if=virtio"]
+        for cdrom in self.options.cdroms:
+            args += ["-drive", f"file={Path(cdrom).absolute()},media=cdrom,readonly=on"]
+        return args
+
+    def _serial_args(self) -> list[str]:
+        """
+        Serial no stdout (ou descartada com --no-serial). Com --serial-log vira um
+        chardev com logfile: o próprio QEMU grava cada byte no arquivo enquanto o
+        terminal continua recebendo a mesma saída.
+        """
+        if not self.options.serial_log: return ["-serial", "stdio" if self.options.serial else "none"]
+        log_path = self.options.serial_log.absolute()
+        log_path.parent.mkdir(parents=True, exist_ok=True)
+        # Trunca já aqui: o arquivo de uma execução anterior não fica para trás se o QEMU falhar ao abrir
+        log_path.write_bytes(b"")
+        backend = "stdio" if self.options.serial else "null"
+        # ',' separa opções no QEMU; dentro de um valor é escrita como ',,'
+        logfile = str(log_path).replace(",", ",,")
+        return ["-chardev", f"{backend},id=serial0,logfile={logfile},logappend=off", "-serial", "chardev:serial0"]
+
+    def _display_args(self) -> list[str]:
+        """
+        '-display' do frontend pedido (ou do padrão da plataforma). Sem servidor
+        gráfico no Linux, ou se o QEMU não tiver o frontend, cai para outro com
+        janela e por fim para 'none' em vez de o QEMU abortar.
+        """
+        if self.options.headless: return ["-display", "none"]
+        mode, vnc = parse_display(self.options.display_mode or default_display())
+        if mode == "vnc":
+            self.log.info(f"VNC em localhost:{5900 + vnc} (display :{vnc})")
+            return ["-display", "none", "-vnc", f":{vnc}"]
+        if mode == "none" or mode not in WINDOWED_DISPLAYS: return ["-display", mode]
+        if sys.platform.startswith("linux") and not (os.environ.get("DISPLAY") or os.environ.get("WAYLAND_DISPLAY")):
+            self.log.warning(f"Sem DISPLAY/WAYLAND_DISPLAY; rodando sem janela em vez de '{mode}'")
+            return ["-display", "none"]
+        available = available_displays(self.system.binary)
+        if available is None or mode in available: return ["-display", mode]
+        fallback = next((d for d in WINDOWED_DISPLAYS if d in available), "none")
+        self.log.warning(f"Este QEMU não tem o display '{mode}', usando '{fallback}' (disponíveis: {', '.join(available)})")
+        return ["-display", fallback]
+
+    def _snapshot_args(self) -> list[str]:
+        """-snapshot, -loadvm e o monitor em socket para o savevm quando há disco qcow2."""
+        args = ["-snapshot"] if self.options.snapshot else []
+        if self.options.loadvm: args += ["-loadvm", self.options.loadvm]
+        if not self.options.has_qcow2() or not hasattr(socket, "AF_UNIX"): return [*args, "-monitor", "none"]
+        monitor = (self.paths.dist / "qemu-monitor.sock").absolute()
+        self.log.info(f"Monitor do QEMU: socat - UNIX-CONNECT:{monitor} (ex: savevm NOME)")
+        return [*args, "-monitor", f"unix:{monitor},server=on,wait=off"]
+
+    @property
+    def system(self) -> QemuSystem:
+        return QEMU_SYSTEMS[self.config.arch]
+
+    def _machine_args(self, accel: str) -> list[str]:
+        """-machine (aarch64: virt) e a CPU: 'host' só existe com aceleração por hardware."""
+        machine = ["-machine", self.system.machine] if self.system.machine else []
+        return [*machine, "-accel", accel, "-cpu", self.system.tcg_cpu if accel == "tcg" else "host"]
+
+    def _arch_device_args(self) -> list[str]:
+        """Dispositivos que dependem da máquina: vídeo da virt e a saída do modo de teste."""
+        args = []
+        # A virt não tem placa de vídeo padrão; o ramfb dá ao firmware um framebuffer (GOP)
+        if self.system.machine == "virt" and not self.options.headless: args += ["-device", "ramfb"]
+        if self.options.test:
+            if self.config.arch == "x86_64": args += ["-device", f"isa-debug-exit,iobase={TEST_EXIT_PORT:#x},iosize=0x04"]
+            else: args += ["-semihosting-config", "enable=on,target=native"]
+        return args
+
+    def _net_args(self) -> list[str]:
+        """Placa virtio-net ligada à rede user (com hostfwd) ou a um tap do host; none remove a NIC padrão."""
+        if self.options.net == "none": return ["-nic", "none"]
+        if self.options.net == "tap":
+            if sys.platform.startswith("linux") and not Path(f"/sys/class/net/{self.options.tap_name}").exists():
+                self.log.warning(f"Interface {self.options.tap_name} não existe; crie com: "
+                                 f"sudo ip tuntap add {self.options.tap_name} mode tap user $USER")
+            netdev = f"tap,id=net0,ifname={self.options.tap_name},script=no,downscript=no"
+        else:
+            forwards = [parse_hostfwd(spec) for spec in self.options.hostfwd]
+            netdev = "user,id=net0" + "".join(f",hostfwd={p}::{h}-:{g}" for p, h, g in forwards)
+        return ["-netdev", netdev, "-device", "virtio-net-pci,netdev=net0"]
+
     def build_command(self) -> list:
         """
         Constrói o comando QEMU para rodar nativamente no Linux.
@@ -29,31 +529,43 @@ class QemuRunner:
         # Usa os caminhos do projeto (dist/qemu/)
         qemu_dir = str(self.paths.dist_qemu.absolute())
         internal_log = str((self.paths.dist / "qemu-internal.log").absolute())
-        serial_log = str((self.paths.dist / "qemu-serial.log").absolute())
 
-        # Detecta o caminho do OVMF
-        ovmf_paths = [
-            "/usr/share/OVMF/OVMF_CODE_4M.fd",
-            "/usr/share/OVMF/OVMF.fd",
-            "/usr/share/qemu/OVMF.fd",
-            "/usr/share/ovmf/OVMF.fd"
-        ]
-        ovmf_bios = next((p for p in ovmf_paths if Path(p).exists()), ovmf_paths[0])
+        # Com o backend 'log' (padrão das distros) os traces saem no arquivo -D,
+        # então ao usar --trace ele passa a ser gravado em .anvil/logs/
+        if self.options.trace:
+            self.paths.anvil_log_dir.mkdir(parents=True, exist_ok=True)
+            internal_log = str((self.paths.anvil_log_dir / "qemu-trace.log").absolute())
+
+        ovmf_bios = self._firmware_code()
+
+        accel = self._resolve_accel()
+        if self.options.gdb_port and accel != "tcg":
+            self.log.warning(f"gdb com {accel}: breakpoints de software e single-step podem se comportar de forma "
+                             "estranha; use hbreak ou --accel tcg")
 
         cmd_parts = [
-            "qemu-system-x86_64",
-            "-enable-kvm",
-            "-cpu", "host",
-            "-m", "2048M",
-            "-smp", "cpus=4",
-            "-drive", f"file=fat:rw:{qemu_dir},format=raw,if=virtio",
+            self.system.binary,
+            *self._machine_args(accel),
+            "-m", f"{self.options.memory_mb}M",
+            "-smp", f"cpus={self.options.cpus}",
+            # Com --loadvm todo disco gravável precisaria suportar snapshot, o que o vvfat não faz
+            "-drive", (f"file=fat:{qemu_dir},format=raw,if=virtio,readonly=on" if self.options.loadvm
+                       else f"file=fat:rw:{qemu_dir},format=raw,if=virtio"),
             "-drive", f"if=pflash,format=raw,readonly=on,file={ovmf_bios}",
-            "-serial", "stdio",
-            "-display", "gtk",
-            "-monitor", "none",
+            *self._firmware_vars_args(),
+            *self._storage_args(),
+            *self._net_args(),
+            *self._serial_args(),
+            *self._display_args(),
+            *self._snapshot_args(),
             "-no-reboot",
+            # Relógio do guest: fixo quando um horário é informado (testes reproduzíveis)
+            "-rtc", f"base={self.options.rtc_base}",
             "-d", "cpu_reset,int,mmu,guest_errors,unimp",
-            "-D", internal_log
+            "-D", internal_log,
+            *self._trace_args(),
+            *(["-gdb", f"tcp::{self.options.gdb_port}", "-S"] if self.options.gdb_port else []),
+            *self._arch_device_args(),
         ]
 
         return cmd_parts
@@ -66,7 +578,8 @@ class QemuRunner:
         # Isso impede que o UEFI pare no Shell
         try:
             startup_nsh = self.paths.dist_qemu / "startup.nsh"
-            startup_nsh.write_text(r"fs0:\EFI\BOOT\BOOTX64.EFI")
+            boot_efi = self.config.artifact_dest("bootloader", self.options.profile).replace("/", "\\")
+            startup_nsh.write_text(f"fs0:\\{boot_efi}")
         except Exception as e:
             self.log.warning(f"Não foi possível criar startup.nsh: {e}")
 
@@ -89,10 +602,39 @@ class QemuRunner:
 
         # Inicia tarefa para capturar e salvar serial
         serial_log = str((self.paths.dist / "qemu-serial.log").absolute())
-        asyncio.create_task(self._capture_serial(serial_log))
+        self.serial_task = asyncio.create_task(self._capture_serial(serial_log))
+        if self.options.gdb_port and self.options.gdb_timeout:
+            self.gdb_task = asyncio.create_task(self._gdb_watchdog())
 
         return self.process
 
+    async def _gdb_watchdog(self) -> None:
+        """--gdb-timeout: se nenhum debugger assumiu o stub no prazo, destrava o guest ou encerra o QEMU."""
+        process, timeout = self.process, self.options.gdb_timeout
+        try:
+            await asyncio.wait_for(process.wait(), timeout=timeout)
+            return
+        except asyncio.TimeoutError: pass
+        stub = await gdb_claim_stub(self.options.gdb_port)
+        if stub is None:
+            self.log.debug("gdb stub ocupado: um debugger está conectado")
+            return
+        reader, writer = stub
+        try:
+            if self.options.gdb_timeout_action == "continue":
+                self.log.warning(f"Nenhum debugger em {timeout}s; continuando a execução (--gdb-timeout)")
+                # 'D' (detach) retoma a CPU e libera o stub para um gdb conectar depois
+                writer.write(b"+$D#44")
+                await writer.drain()
+                try: await asyncio.wait_for(reader.readuntil(b"#"), GDB_PROBE_TIMEOUT)
+                except (asyncio.TimeoutError, asyncio.IncompleteReadError, asyncio.LimitOverrunError): pass
+            else:
+                self.log.error(f"Nenhum debugger em {timeout}s; encerrando o QEMU (--gdb-timeout-action abort)")
+                if process.returncode is None: process.terminate()
+        except OSError as e: self.log.warning(f"Falha ao falar com o gdb stub: {e}")
+        finally:
+            writer.close()
+
     async def _capture_serial(self, serial_log_path):
         """Captura stdout do QEMU e salva no arquivo serial log."""
         try:
@@ -107,13 +649,57 @@ class QemuRunner:
                     log_file.write(text)
                     log_file.flush()
 
+                    if self.on_line: self.on_line(text)
                     # Exibe no terminal (sem colorização para não poluir)
-                    print(text, end='', flush=True)
+                    else: print(text, end='', flush=True)
         except Exception as e:
             self.log.debug(f"Erro ao capturar serial: {e}")
         
+    async def boot_check(self, image: Path, image_format: str, banner: str = BOOTLOADER_BANNER, timeout: float = 15.0) -> bool:
+        """
+        Smoke test de uma imagem de disco (ou de uma ISO, com image_format "iso", anexada
+        como CD-ROM): boota headless e espera o banner do bootloader aparecer na serial.
+        Retorna True se apareceu dentro do timeout.
+        """
+        accel = self._resolve_accel()
+        if image_format == "iso": media = ["-cdrom", str(image.absolute())]
+        else: media = ["-drive", f"file={image.absolute()},format={image_format},if=virtio,snapshot=on"]
+        cmd = [
+            self.system.binary,
+            *self._machine_args(accel),
+            "-m", "512M",
+            "-drive", f"if=pflash,format=raw,readonly=on,file={self._firmware_code()}",
+            *media,
+            "-serial", "stdio",
+            "-display", "none",
+            "-monitor", "none",
+            "-no-reboot",
+        ]
+        self.log.info(f"Verificando boot de {image.name} (até {timeout:.0f}s)...")
+        self.log.debug(f"Comando: {' '.join(cmd)}")
+
+        process = await asyncio.create_subprocess_exec(
+            *cmd, stdout=asyncio.subprocess.PIPE, stderr=asyncio.subprocess.STDOUT, stdin=asyncio.subprocess.DEVNULL
+        )
+
+        async def wait_banner() -> bool:
+            while True:
+                line = await process.stdout.readline()
+                if not line: return False
+                if banner in line.decode("utf-8", errors="replace"): return True
+
+        try:
+            return await asyncio.wait_for(wait_banner(), timeout=timeout)
+        except asyncio.TimeoutError:
+            return False
+        finally:
+            if process.returncode is None:
+                process.kill()
+                await process.wait()
+
     async def stop(self):
         """Para o QEMU."""
+        if self.gdb_task and not self.gdb_task.done(): self.gdb_task.cancel()
         if self.process:
             self.log.info("Parando QEMU...")
             self.process.terminate()
@@ -8,6 +8,8 @@ Também remove caracteres de controle ANSI indesejados gerados pelo UEFI/Kernel.
 import asyncio
 import re
 
+from core.logger import colors_enabled
+
 class Colors:
     """Códigos de cor ANSI para o terminal."""
     RESET = "\033[0m"
@@ -59,6 +61,9 @@ class SerialColorizer:
         if "RAX=" in line or "EAX=" in line or "CR3=" in line or "Servicing" in line:
             return ""
 
+        # Sem cores (--color never, NO_COLOR ou saída redirecionada): só a linha limpa
+        if not colors_enabled(): return line
+
         # 3. Aplicação de padrões de tags (ex: [OK], [ERROR])
         for pattern, replacement in cls.PATTERNS:
             line = re.sub(pattern, replacement, line)
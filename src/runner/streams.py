@@ -45,18 +45,20 @@ class StreamCapture:
             try: cb(entry)
             except: pass
             
+    def feed(self, line: str):
+        """Registra uma linha de serial lida por outro leitor (ex: o QemuRunner, dono do stdout do QEMU)."""
+        entry = LogEntry(datetime.now(), StreamSource.SERIAL, line.rstrip(), len(self.serial_buffer) + 1)
+        self.serial_buffer.append(entry)
+        self._emit(entry)
+
     async def capture_serial(self, stream: asyncio.StreamReader):
         """Lê stdout do processo QEMU."""
         self._running = True
-        count = 0
         while self._running:
             try:
                 line = await asyncio.wait_for(stream.readline(), timeout=0.1)
                 if not line: break
-                count += 1
-                entry = LogEntry(datetime.now(), StreamSource.SERIAL, line.decode("utf-8", "replace").rstrip(), count)
-                self.serial_buffer.append(entry)
-                self._emit(entry)
+                self.feed(line.decode("utf-8", "replace"))
             except asyncio.TimeoutError: continue
             except: break
             
@@ -0,0 +1,29 @@
+//! Aplicativo {{name}} do RedstoneOS.
+
+#![no_std]
+#![no_main]
+
+use core::panic::PanicInfo;
+
+/// Estado do aplicativo.
+pub struct {{name_pascal}}App;
+
+impl {{name_pascal}}App {
+    pub const fn new() -> Self {
+        Self
+    }
+
+    pub fn run(&mut self) -> ! {
+        loop {}
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    {{name_pascal}}App::new().run()
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    loop {}
+}
@@ -0,0 +1,32 @@
+//! Driver {{name}} do RedstoneOS.
+
+#![no_std]
+#![no_main]
+
+use core::panic::PanicInfo;
+
+/// Estado do driver.
+pub struct {{name_pascal}}Driver;
+
+impl {{name_pascal}}Driver {
+    pub const fn new() -> Self {
+        Self
+    }
+
+    /// Detecta e inicializa o dispositivo.
+    pub fn probe(&mut self) -> bool {
+        false
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    let mut driver = {{name_pascal}}Driver::new();
+    driver.probe();
+    loop {}
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    loop {}
+}
@@ -0,0 +1,12 @@
+//! Biblioteca {{name}} do RedstoneOS.
+
+#![no_std]
+
+/// Ponto de partida da API de `{{name_snake}}`.
+pub struct {{name_pascal}};
+
+impl {{name_pascal}} {
+    pub const fn new() -> Self {
+        Self
+    }
+}
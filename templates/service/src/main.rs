@@ -0,0 +1,30 @@
+//! Serviço {{name}} do RedstoneOS (iniciado pelo supervisor).
+
+#![no_std]
+#![no_main]
+
+use core::panic::PanicInfo;
+
+/// Estado do serviço.
+pub struct {{name_pascal}}Service;
+
+impl {{name_pascal}}Service {
+    pub const fn new() -> Self {
+        Self
+    }
+
+    /// Laço principal: atende as requisições recebidas.
+    pub fn run(&mut self) -> ! {
+        loop {}
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    {{name_pascal}}Service::new().run()
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    loop {}
+}
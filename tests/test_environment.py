@@ -0,0 +1,27 @@
+"""env com --arch: targets e QEMU seguem a arquitetura do guest."""
+
+import sys
+import unittest
+from pathlib import Path
+
+sys.path.insert(0, str(Path(__file__).resolve().parents[1] / "src"))
+
+from core.environment import EnvReport, arch_tools, required_targets  # noqa: E402
+
+class ArchEnvTest(unittest.TestCase):
+    def test_aarch64_targets(self):
+        self.assertEqual(required_targets("aarch64"), ["aarch64-unknown-uefi", "aarch64-unknown-none"])
+        report = EnvReport(tools=[], installed_targets=["aarch64-unknown-none"], arch="aarch64")
+        self.assertEqual(report.missing_targets, ["aarch64-unknown-uefi"])
+
+    def test_aarch64_qemu(self):
+        names = [tool[0] for tool in arch_tools("aarch64")]
+        self.assertIn("qemu-system-aarch64", names)
+        self.assertNotIn("qemu-system-x86_64", names)
+
+    def test_x86_64_unchanged(self):
+        self.assertEqual(required_targets("x86_64"), ["x86_64-unknown-uefi", "x86_64-unknown-none"])
+        self.assertIn("qemu-system-x86_64", [tool[0] for tool in arch_tools("x86_64")])
+
+if __name__ == "__main__":
+    unittest.main()
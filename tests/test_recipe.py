@@ -0,0 +1,92 @@
+"""Receitas: a embutida 'minimal' e a herança por extends contra o anvil.toml do repositório."""
+
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+import toml
+
+ANVIL_DIR = Path(__file__).resolve().parents[1]
+sys.path.insert(0, str(ANVIL_DIR / "src"))
+
+from core.config import Config  # noqa: E402
+from core.errors import ConfigError  # noqa: E402
+from core.recipe import RecipeSpec, list_recipes, recipe_names, validate_recipe  # noqa: E402
+
+def default_config() -> Config:
+    path = ANVIL_DIR / "anvil.toml"
+    return Config.from_dict(toml.load(path), path)
+
+class ExtendsMinimalTest(unittest.TestCase):
+    def setUp(self):
+        self.tmp = tempfile.TemporaryDirectory()
+        self.recipes = Path(self.tmp.name)
+        self.config = default_config()
+
+    def tearDown(self):
+        self.tmp.cleanup()
+
+    def write(self, name: str, text: str) -> None:
+        (self.recipes / f"{name}.toml").write_text(text, encoding="utf-8")
+
+    def test_builtin_minimal_applies(self):
+        recipe = RecipeSpec.load(self.recipes, "minimal")
+        self.assertTrue(recipe.builtin)
+        config = recipe.apply(self.config)
+        self.assertEqual([s.name for s in config.components.services], ["supervisor"])
+        self.assertEqual(config.components.apps, [])
+
+    def test_child_of_minimal_resolves_and_applies(self):
+        self.write("desk", 'description = "Desktop"\nextends = "minimal"\npackages = ["firefly", "terminal"]\n')
+        recipe = RecipeSpec.load(self.recipes, "desk")
+        self.assertEqual(recipe.extends, "minimal")
+        self.assertEqual(recipe.packages, ["bootloader", "kernel", "supervisor", "firefly", "terminal"])
+        config = recipe.apply(self.config)
+        self.assertEqual([s.name for s in config.components.services], ["supervisor", "firefly"])
+        self.assertEqual([a.name for a in config.components.apps], ["terminal"])
+        # apply não altera a receita
+        self.assertEqual(recipe.packages, ["bootloader", "kernel", "supervisor", "firefly", "terminal"])
+
+    def test_inherited_builtin_package_missing_from_workspace_is_dropped(self):
+        config = default_config()
+        config.components.services = [s for s in config.components.services if s.name != "supervisor"]
+        self.write("bare", 'description = "Sem supervisor"\nextends = "minimal"\npackages = ["input"]\n')
+        recipe = RecipeSpec.load(self.recipes, "bare")
+        self.assertEqual(recipe.workspace_packages(config), ["bootloader", "kernel", "input"])
+        self.assertEqual([s.name for s in recipe.apply(config).components.services], ["input"])
+
+    def test_child_listing_missing_package_still_fails(self):
+        self.write("strict", 'description = "Estrita"\nextends = "minimal"\npackages = ["init"]\n')
+        recipe = RecipeSpec.load(self.recipes, "strict")
+        with self.assertRaises(ConfigError):
+            recipe.apply(self.config)
+
+    def test_validate_child_of_minimal(self):
+        self.write("desk", 'description = "Desktop"\nextends = "minimal"\npackages = ["firefly"]\n')
+        problems = validate_recipe(self.recipes, "desk", self.config, self.config.project_root)
+        self.assertFalse([p for p in problems if "não existe" in p], problems)
+
+class NoRecipesDirTest(unittest.TestCase):
+    """Projeto sem recipes/: as embutidas continuam listadas e validáveis."""
+
+    def setUp(self):
+        self.tmp = tempfile.TemporaryDirectory()
+        self.recipes = Path(self.tmp.name) / "recipes"
+        self.config = default_config()
+
+    def tearDown(self):
+        self.tmp.cleanup()
+
+    def test_list_shows_only_loadable_recipes(self):
+        self.assertEqual(recipe_names(self.recipes), ["minimal"])
+        recipes, errors = list_recipes(self.recipes)
+        self.assertEqual([r.name for r in recipes], ["minimal"])
+        self.assertEqual(errors, [])
+
+    def test_validate_builtin_without_dir(self):
+        problems = validate_recipe(self.recipes, "minimal", self.config, self.config.project_root)
+        self.assertFalse([p for p in problems if "não encontrada" in p or "não existe" in p], problems)
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,99 @@
+"""
+Modo reproduzível de ponta a ponta: '--reproducible dist --archive tar.gz' em dois
+projetos idênticos, em diretórios temporários diferentes, deve gerar os mesmos bytes.
+Roda sem WSL: o initfs sai do tar do host.
+"""
+
+import hashlib
+import os
+import shutil
+import subprocess
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+
+ANVIL_SRC = Path(__file__).resolve().parents[1] / "src"
+EPOCH = "1700000000"
+
+ANVIL_TOML = """\
+[project]
+name = "RedstoneOS"
+root = ".."
+
+[components]
+apps = []
+drivers = []
+
+[components.kernel]
+path = "forge"
+target = "x86_64-redstone"
+binary = "forge"
+
+[components.bootloader]
+path = "ignite"
+target = "x86_64-unknown-uefi"
+binary = "ignite.efi"
+
+[[components.services]]
+name = "supervisor"
+path = "services/supervisor"
+target = "x86_64-unknown-none"
+core = true
+
+[[components.services]]
+name = "input"
+path = "services/input"
+target = "x86_64-unknown-none"
+"""
+
+# Binários "compilados": conteúdo fixo, com datas diferentes em cada projeto
+BINARIES = {
+    "forge/target/x86_64-redstone/release/forge": b"\x7fELF kernel" * 512,
+    "ignite/target/x86_64-unknown-uefi/release/ignite.efi": b"MZ ignite" * 256,
+    "services/supervisor/target/x86_64-unknown-none/release/supervisor": b"\x7fELF supervisor" * 128,
+    "services/input/target/x86_64-unknown-none/release/input": b"\x7fELF input" * 64,
+}
+
+def make_project(root: Path, mtime: int) -> Path:
+    (root / "anvil").mkdir(parents=True)
+    (root / "anvil" / "anvil.toml").write_text(ANVIL_TOML, encoding="utf-8")
+    for rel, data in BINARIES.items():
+        path = root / rel
+        path.parent.mkdir(parents=True, exist_ok=True)
+        path.write_bytes(data)
+        os.utime(path, (mtime, mtime))
+    return root / "anvil"
+
+def host_path() -> str:
+    """PATH sem o wsl, para o teste valer também onde ele existe."""
+    return os.pathsep.join(d for d in os.environ.get("PATH", "").split(os.pathsep)
+                           if d and not (Path(d) / "wsl").exists())
+
+def digests(dist: Path) -> dict[str, str]:
+    return {p.relative_to(dist).as_posix(): hashlib.sha256(p.read_bytes()).hexdigest()
+            for p in sorted(dist.rglob("*")) if p.is_file()}
+
+@unittest.skipUnless(shutil.which("tar"), "tar não encontrado")
+class ReproducibleDistTest(unittest.TestCase):
+    def run_dist(self, anvil_dir: Path) -> None:
+        env = {**os.environ, "SOURCE_DATE_EPOCH": EPOCH, "PATH": host_path(),
+               "PYTHONPATH": os.pathsep.join(filter(None, [str(ANVIL_SRC), os.environ.get("PYTHONPATH")]))}
+        p = subprocess.run([sys.executable, str(ANVIL_SRC / "main.py"), "--reproducible", "dist", "--archive", "tar.gz"],
+                           cwd=anvil_dir, env=env, capture_output=True, text=True, stdin=subprocess.DEVNULL, timeout=120)
+        self.assertEqual(p.returncode, 0, p.stdout + p.stderr)
+
+    def test_two_runs_are_byte_identical(self):
+        with tempfile.TemporaryDirectory() as a, tempfile.TemporaryDirectory() as b:
+            first, second = Path(a) / "redstone", Path(b) / "outro" / "redstone"
+            self.run_dist(make_project(first, 1600000000))
+            self.run_dist(make_project(second, 1650000000))
+            dist_a, dist_b = digests(first / "dist"), digests(second / "dist")
+
+            for rel in ("qemu/boot/initfs", "redstone.tar.gz", "SHA256SUMS", "manifest.json"):
+                self.assertIn(rel, dist_a)
+                self.assertEqual((first / "dist" / rel).read_bytes(), (second / "dist" / rel).read_bytes(), rel)
+            self.assertEqual(dist_a, dist_b)
+
+if __name__ == "__main__":
+    unittest.main()
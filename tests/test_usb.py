@@ -0,0 +1,69 @@
+"""Cópia do dist para o pendrive: caminho sequencial, pipeline com threads e propagação de erros."""
+
+import hashlib
+import sys
+import tempfile
+import unittest
+from pathlib import Path
+from types import SimpleNamespace
+from unittest import mock
+
+sys.path.insert(0, str(Path(__file__).resolve().parents[1] / "src"))
+
+from build import usb  # noqa: E402
+from build.usb import UsbWriter, verify_copy  # noqa: E402
+from core.logger import get_logger  # noqa: E402
+
+class CopyTreeTest(unittest.TestCase):
+    def setUp(self):
+        self.tmp = tempfile.TemporaryDirectory()
+        root = Path(self.tmp.name)
+        self.src, self.dest = root / "qemu", root / "usb"
+        self.dest.mkdir()
+        files = {"EFI/BOOT/BOOTX64.EFI": b"efi" * 1000, "boot/kernel": bytes(range(256)) * 20000,
+                 "boot/initfs": b"", "system/services/a/a.app": b"x" * (usb.COPY_CHUNK + 7)}
+        for rel, data in files.items():
+            (self.src / rel).parent.mkdir(parents=True, exist_ok=True)
+            (self.src / rel).write_bytes(data)
+        self.expected = {rel: hashlib.sha256(data).hexdigest() for rel, data in files.items()}
+        self.writer = UsbWriter(SimpleNamespace(dist_qemu=self.src), None, get_logger())
+
+    def tearDown(self):
+        self.tmp.cleanup()
+
+    def check_copy(self):
+        total, hashes = self.writer._copy_tree(self.dest)
+        self.assertEqual(hashes, self.expected)
+        self.assertEqual(total, sum(f.stat().st_size for f in self.src.rglob("*") if f.is_file()))
+        for rel in self.expected: self.assertEqual((self.dest / rel).read_bytes(), (self.src / rel).read_bytes())
+        verify_copy(hashes, self.dest, get_logger())
+
+    def test_small_tree_is_sequential(self):
+        with mock.patch.object(UsbWriter, "_copy_pipeline", side_effect=AssertionError("pipeline")):
+            self.check_copy()
+
+    def test_large_tree_uses_pipeline(self):
+        with mock.patch.object(usb, "PIPELINE_THRESHOLD", 0), \
+                mock.patch.object(UsbWriter, "_copy_sequential", side_effect=AssertionError("sequencial")):
+            self.check_copy()
+
+    def test_pipeline_propagates_write_errors(self):
+        real_open = open
+        def failing_open(path, mode="r", *args, **kwargs):
+            if "w" in mode and Path(path).name == "kernel": raise OSError(28, "No space left on device")
+            return real_open(path, mode, *args, **kwargs)
+        with mock.patch.object(usb, "PIPELINE_THRESHOLD", 0), mock.patch("builtins.open", failing_open):
+            with self.assertRaises(OSError):
+                self.writer._copy_tree(self.dest)
+
+    def test_pipeline_propagates_read_errors(self):
+        real_open = open
+        def failing_open(path, mode="r", *args, **kwargs):
+            if "r" in mode and Path(path).name == "initfs": raise PermissionError(13, "Permission denied")
+            return real_open(path, mode, *args, **kwargs)
+        with mock.patch.object(usb, "PIPELINE_THRESHOLD", 0), mock.patch("builtins.open", failing_open):
+            with self.assertRaises(PermissionError):
+                self.writer._copy_tree(self.dest)
+
+if __name__ == "__main__":
+    unittest.main()